@@ -16,7 +16,7 @@ use crate::{
 pub struct AnimatedObjectStorage {
     // host data
     skeleton: Skeleton,
-    animation_data: AnimationData,
+    animations: Vec<AnimationData>, // the clips of the model
 
     instance_host: Vec<AnimatedObjectInstanceHost>,
 
@@ -27,6 +27,8 @@ pub struct AnimatedObjectStorage {
     update_done: usize,
 
     instances: Vec<deferred_animation_shader::Instance>,
+    active_instances: Vec<deferred_animation_shader::Instance>,
+    nr_active_instances: u32,
     instance_buffer: deferred_animation_shader::InstanceBuffer<deferred_animation_shader::Instance>,
 }
 
@@ -40,9 +42,8 @@ impl AnimatedObjectStorage {
         let animation_object_data = GltfImporter::create(glb_bin);
 
         let skeleton = Skeleton::new(&animation_object_data);
-        let animation_data = animation_object_data.animations[0].clone();
-        // let animation_0 = Animation::new(&animation_data);
-        let animation_uniform = deferred_animation_shader::AnimationUniform::zero();
+        let animations = animation_object_data.animations.clone();
+        // let animation_0 = Animation::new(&animations[0]);
 
         // let instance = deferred_animation_shader::Instance {
         //     position: [0.0, 20.0, 5.0],
@@ -59,53 +60,31 @@ impl AnimatedObjectStorage {
 
         let mut instance_host = Vec::with_capacity(max_instances);
         let mut instance_device = Vec::with_capacity(max_instances);
-
-        for i in 0..max_instances {
-            instance_host.push(AnimatedObjectInstanceHost {
-                animation: Animation::new(&animation_data),
-                is_active: false,
-                animation_uniform,
-                _instance: deferred_animation_shader::Instance {
-                    position: [0.0, 20.0, 5.0],
-                    color: [0.5, 0.5, 0.8],
-                    entity: [i as u32 | ENTITY_ANT_BIT, 0, 0],
-                },
-            });
-        }
-
-        for _i in 0..max_instances {
-            instance_device.push(AnimatedObjectInstanceDevice {
-                animation_uniform_buffer: deferred_animation_shader::AnimationUniformBuffer::new(
-                    wgpu_renderer.device(),
-                    animation_bind_group_layout,
-                ),
-                _instance_buffer: deferred_animation_shader::InstanceBuffer::new(
-                    wgpu_renderer.device(),
-                    &[deferred_animation_shader::Instance::new()],
-                ),
-            });
-        }
-
         let mut instances = Vec::with_capacity(max_instances);
         for i in 0..max_instances {
-            instances.push(deferred_animation_shader::Instance {
-                position: [0.0, 20.0, 5.0],
-                color: [0.5, 0.5, 0.8],
-                entity: [i as u32 | ENTITY_ANT_BIT, 0, 0],
-            });
+            instance_host.push(create_instance_host(i, &animations[0]));
+            instance_device.push(create_instance_device(
+                wgpu_renderer,
+                animation_bind_group_layout,
+            ));
+            instances.push(create_instance(i));
         }
 
+        let active_instances = instances.clone();
+
         let instance_buffer =
             deferred_animation_shader::InstanceBuffer::new(wgpu_renderer.device(), &instances);
 
         Self {
             skeleton,
-            animation_data,
+            animations,
             mesh,
             instance_host,
             instance_device,
             update_done: 0,
             instances,
+            active_instances,
+            nr_active_instances: 0,
             instance_buffer,
         }
     }
@@ -117,7 +96,7 @@ impl AnimatedObjectStorage {
                 elem.animation.increment_time(dt);
                 elem.animation.update_animation_uniform(
                     &self.skeleton,
-                    &self.animation_data,
+                    &self.animations[elem.clip],
                     &mut elem.animation_uniform,
                 );
             }
@@ -146,22 +125,86 @@ impl AnimatedObjectStorage {
             // }
         }
 
+        // only the active instances are drawn
+        let mut j = 0;
+        for (i, elem) in self.instance_host.iter().enumerate() {
+            if elem.is_active {
+                self.active_instances[j] = self.instances[i];
+                j += 1;
+            }
+        }
+        self.nr_active_instances = j as u32;
+
         self.instance_buffer
-            .update(renderer.queue(), &self.instances);
+            .update(renderer.queue(), &self.active_instances[0..j]);
     }
 
     pub fn max_instances(&self) -> usize {
         self.instance_host.len()
     }
 
+    /// Makes space for more instances, the new ones are inactive
+    pub fn grow(
+        &mut self,
+        wgpu_renderer: &mut dyn WgpuRendererInterface,
+        animation_bind_group_layout: &deferred_animation_shader::AnimationBindGroupLayout,
+        max_instances: usize,
+    ) {
+        if max_instances <= self.max_instances() {
+            return;
+        }
+
+        for i in self.max_instances()..max_instances {
+            self.instance_host
+                .push(create_instance_host(i, &self.animations[0]));
+            self.instance_device.push(create_instance_device(
+                wgpu_renderer,
+                animation_bind_group_layout,
+            ));
+            self.instances.push(create_instance(i));
+        }
+        self.active_instances = self.instances.clone();
+
+        self.instance_buffer =
+            deferred_animation_shader::InstanceBuffer::new(wgpu_renderer.device(), &self.instances);
+    }
+
     pub fn set_pos(&mut self, id: usize, pos: cgmath::Vector3<f32>) {
         // self.instance_host[id].instance.position = pos.into();
         self.instances[id].position = pos.into();
     }
 
+    pub fn set_rot_z(&mut self, id: usize, rot_z: f32) {
+        self.instances[id].rot_z = rot_z;
+    }
+
+    /// The host data of all instances, e.g. to update many of them in one pass
+    pub fn instances_mut(&mut self) -> &mut [deferred_animation_shader::Instance] {
+        &mut self.instances
+    }
+
+    /// Plays the clip with the index from the start, the first clip if the model has no such clip
+    pub fn set_clip(&mut self, id: usize, clip: usize) {
+        let clip = if clip < self.animations.len() {
+            clip
+        } else {
+            0
+        };
+
+        let elem = &mut self.instance_host[id];
+        if elem.clip != clip {
+            elem.clip = clip;
+            elem.animation = Animation::new(&self.animations[clip]);
+        }
+    }
+
     pub fn set_active(&mut self, id: usize) {
         self.instance_host[id].is_active = true;
     }
+
+    pub fn set_inactive(&mut self, id: usize) {
+        self.instance_host[id].is_active = false;
+    }
 }
 
 impl std::fmt::Debug for AnimatedObjectStorage {
@@ -172,8 +215,8 @@ impl std::fmt::Debug for AnimatedObjectStorage {
         writeln!(f, "Skeleton:")?;
         writeln!(f, "{:?}", self.skeleton)?;
 
-        writeln!(f, "Animation:")?;
-        writeln!(f, "{:?}", self.animation_data)?;
+        writeln!(f, "Animations:")?;
+        writeln!(f, "{:?}", self.animations)?;
 
         Ok(())
     }
@@ -188,12 +231,49 @@ impl DeferredAnimationShaderDraw for AnimatedObjectStorage {
             render_pass,
             &self.instance_device[0].animation_uniform_buffer,
             &self.instance_buffer,
+            self.nr_active_instances,
         );
     }
 }
 
+fn create_instance(i: usize) -> deferred_animation_shader::Instance {
+    deferred_animation_shader::Instance {
+        position: [0.0, 20.0, 5.0],
+        color: [0.5, 0.5, 0.8],
+        entity: [i as u32 | ENTITY_ANT_BIT, 0, 0],
+        rot_z: 0.0,
+    }
+}
+
+fn create_instance_host(i: usize, animation_data: &AnimationData) -> AnimatedObjectInstanceHost {
+    AnimatedObjectInstanceHost {
+        animation: Animation::new(animation_data),
+        clip: 0,
+        is_active: false,
+        animation_uniform: deferred_animation_shader::AnimationUniform::zero(),
+        _instance: create_instance(i),
+    }
+}
+
+fn create_instance_device(
+    wgpu_renderer: &mut dyn WgpuRendererInterface,
+    animation_bind_group_layout: &deferred_animation_shader::AnimationBindGroupLayout,
+) -> AnimatedObjectInstanceDevice {
+    AnimatedObjectInstanceDevice {
+        animation_uniform_buffer: deferred_animation_shader::AnimationUniformBuffer::new(
+            wgpu_renderer.device(),
+            animation_bind_group_layout,
+        ),
+        _instance_buffer: deferred_animation_shader::InstanceBuffer::new(
+            wgpu_renderer.device(),
+            &[deferred_animation_shader::Instance::new()],
+        ),
+    }
+}
+
 struct AnimatedObjectInstanceHost {
     pub animation: Animation,
+    pub clip: usize, // index of the played animation
     pub animation_uniform: deferred_animation_shader::AnimationUniform,
     pub _instance: deferred_animation_shader::Instance,
 
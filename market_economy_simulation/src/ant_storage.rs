@@ -1,10 +1,13 @@
 //! Manages all the ant objects on the GPU
 //!
+//! The storages grow when an ant id exceeds their capacity.
 
-use market_economy_simulation_server::{ants, point_lights::Attenuation};
+use market_economy_simulation_server::{ants, behaviour::Task, point_lights::Attenuation};
+use wgpu_renderer::wgpu_renderer::WgpuRendererInterface;
 
 use crate::{
     animated_object_storage::AnimatedObjectStorage,
+    deferred_animation_shader::AnimationBindGroupLayout,
     point_light_storage::{PointLightIndex, PointLightInterface, PointLightStorage},
 };
 
@@ -12,9 +15,23 @@ pub struct AntStorage {
     pub point_light_storage: PointLightStorage,
     pub animated_object_storage: AnimatedObjectStorage,
 
+    tasks: Vec<Task>, // indexed by the ant id
     max_ants: usize,
 }
 
+/// Index of the animation clip of the model played while doing the task
+fn clip(task: Task) -> usize {
+    match task {
+        Task::Idle => 0,
+        Task::MoveTo => 1,
+        Task::Gather => 2,
+        Task::Carry => 3,
+        Task::Deliver => 4,
+        Task::Rest => 5,
+        Task::Forage => 1, // walks like moving
+    }
+}
+
 impl AntStorage {
     pub fn new(
         point_light_storage: PointLightStorage,
@@ -27,32 +44,169 @@ impl AntStorage {
         Self {
             point_light_storage,
             animated_object_storage,
+            tasks: vec![Task::Idle; max_ants],
             max_ants,
         }
     }
 
-    pub fn set_ant(&mut self, ant: &ants::Ant) {
-        if ant.id < self.max_ants {
-            let pos = cgmath::Vector3::new(ant.pos.x, ant.pos.y, 2.0);
-            // let pos = cgmath::Vector3::new(0.0, 0.0, 0.0);
-            self.animated_object_storage.set_pos(ant.id, pos);
-            // self.point_light_storage.set_position(&PointLightIndex{ instance_index: ant.id }, pos);
-            self.point_light_storage.set_light(
-                PointLightIndex {
-                    instance_index: ant.id,
-                },
-                pos,
-                ant.light_color,
-                Attenuation::_200,
-            );
-
-            self.animated_object_storage.set_active(ant.id);
-            self.point_light_storage.set_active(
-                &PointLightIndex {
-                    instance_index: ant.id,
-                },
-                true,
-            );
+    /// Grows the storages, at least doubling them, to hold the ants up to the id
+    fn reserve(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        animation_bind_group_layout: &AnimationBindGroupLayout,
+        id: usize,
+    ) {
+        if id < self.max_ants {
+            return;
+        }
+
+        let max_ants = (id + 1).max(self.max_ants * 2);
+        self.point_light_storage.grow(renderer, max_ants);
+        self.animated_object_storage
+            .grow(renderer, animation_bind_group_layout, max_ants);
+        self.tasks.resize(max_ants, Task::Idle);
+        self.max_ants = max_ants;
+    }
+
+    pub fn set_ant(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        animation_bind_group_layout: &AnimationBindGroupLayout,
+        ant: &ants::Ant,
+    ) {
+        self.reserve(renderer, animation_bind_group_layout, ant.id);
+        let pos = cgmath::Vector3::new(ant.pos.x, ant.pos.y, 2.0);
+        // let pos = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        self.animated_object_storage.set_pos(ant.id, pos);
+        self.animated_object_storage.set_rot_z(ant.id, ant.rot_z);
+        // self.point_light_storage.set_position(&PointLightIndex{ instance_index: ant.id }, pos);
+        self.point_light_storage.set_light(
+            PointLightIndex {
+                instance_index: ant.id,
+            },
+            pos,
+            ant.light_color * ant.light_strength,
+            Attenuation::_200,
+        );
+
+        self.set_task(ant.id, ant.task);
+        self.animated_object_storage.set_active(ant.id);
+        self.point_light_storage.set_active(
+            &PointLightIndex {
+                instance_index: ant.id,
+            },
+            true,
+        );
+    }
+
+    /// Applies the changes of many ants, writing straight into the instances
+    pub fn update_ants(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        animation_bind_group_layout: &AnimationBindGroupLayout,
+        batch: &ants::AntBatch,
+    ) {
+        // the ids are in ascending order
+        let last_id = [
+            batch.moved.last(),
+            batch.recoloured.last(),
+            batch.retasked.last(),
+        ]
+        .into_iter()
+        .flatten()
+        .max();
+        if let Some(id) = last_id {
+            self.reserve(renderer, animation_bind_group_layout, *id);
+        }
+
+        // the ants of the chunk missing in a keyframe are dead,
+        // e.g. a stale batch revived them after their death was received
+        if batch.is_keyframe {
+            let start = batch.chunk * ants::BATCH_SIZE;
+            let end = (start + ants::BATCH_SIZE).min(self.max_ants);
+            let mut listed = batch.moved.iter().peekable();
+            for id in start..end {
+                if listed.next_if_eq(&&id).is_none() {
+                    self.remove_ant(id);
+                }
+            }
+        }
+
+        // the lights of the ants of a keyframe are (re)created with their attenuation
+        if batch.is_keyframe {
+            for (&id, pos) in batch.moved.iter().zip(&batch.positions) {
+                if id < self.max_ants {
+                    let index = PointLightIndex { instance_index: id };
+                    let color = self.point_light_storage.instances_mut()[id].light_color;
+                    self.point_light_storage.set_light(
+                        index,
+                        cgmath::Vector3::new(pos.x, pos.y, 2.0),
+                        color.into(),
+                        Attenuation::_200,
+                    );
+                    self.animated_object_storage.set_active(id);
+                }
+            }
+        }
+
+        for (&id, &task) in batch.retasked.iter().zip(&batch.tasks) {
+            if id < self.max_ants {
+                self.set_task(id, task);
+            }
+        }
+
+        let objects = self.animated_object_storage.instances_mut();
+        let lights = self.point_light_storage.instances_mut();
+
+        for ((&id, pos), &rot_z) in batch
+            .moved
+            .iter()
+            .zip(&batch.positions)
+            .zip(&batch.rotations)
+        {
+            if id < self.max_ants {
+                let pos = [pos.x, pos.y, 2.0];
+                objects[id].position = pos;
+                objects[id].rot_z = rot_z;
+                lights[id].position = pos;
+            }
+        }
+
+        // the strength scales the color of the light
+        for ((&id, &light_strength), light_color) in batch
+            .recoloured
+            .iter()
+            .zip(&batch.light_strengths)
+            .zip(&batch.light_colors)
+        {
+            if id < self.max_ants {
+                lights[id].light_color = (*light_color * light_strength).into();
+            }
+        }
+    }
+
+    /// What the ant is doing, e.g. to show it for the selected ant
+    pub fn task(&self, id: usize) -> Option<Task> {
+        self.tasks.get(id).copied()
+    }
+
+    fn set_task(&mut self, id: usize, task: Task) {
+        self.tasks[id] = task;
+        self.animated_object_storage.set_clip(id, clip(task));
+    }
+
+    pub fn remove_ant(&mut self, id: usize) {
+        if id < self.max_ants {
+            self.animated_object_storage.set_inactive(id);
+            self.point_light_storage
+                .set_active(&PointLightIndex { instance_index: id }, false);
+        }
+    }
+
+    /// Removes all ants, e.g. before the state of a snapshot is received
+    pub fn clear(&mut self) {
+        for id in 0..self.max_ants {
+            self.remove_ant(id);
         }
     }
 }
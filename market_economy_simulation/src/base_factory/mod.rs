@@ -4,21 +4,14 @@ mod base_factory_mesh;
 
 pub use base_factory_mesh::BaseFactoryMesh;
 
+use market_economy_simulation_server::factories::FactoryState;
+
 use crate::{
-    ecs2::{AgentBlueToken, Agents, BlueToken, EntityInterface, Resources2},
+    ecs2::{BlueToken, EntityInterface, Resources2},
     ground_plane::Location,
 };
 
-pub enum Recipe {
-    Agent,
-}
-
-#[allow(dead_code)]
-pub enum Result {
-    Blue(BlueToken),
-    Agent(AgentBlueToken),
-}
-
+/// The production is simulated by the server, the factory only shows its state
 #[allow(dead_code)]
 pub struct BaseFactory {
     entity_index: usize,
@@ -28,13 +21,7 @@ pub struct BaseFactory {
     // greens: Vec<GreenToken>,
     blues: Vec<BlueToken>,
 
-    progress: f64,
-
-    agent: Option<AgentBlueToken>,
-
-    recipe: Recipe,
-
-    result: Vec<Result>,
+    state: Option<FactoryState>, // last state received from the server
 }
 
 impl BaseFactory {
@@ -45,10 +32,7 @@ impl BaseFactory {
             // reds: Vec::new(),
             // greens: Vec::new(),
             blues: Vec::new(),
-            progress: 0.0,
-            agent: None,
-            recipe: Recipe::Agent,
-            result: Vec::new(),
+            state: None,
         }
     }
 
@@ -64,12 +48,15 @@ impl BaseFactory {
         self.blues.push(blue_token);
     }
 
-    pub fn produce(
-        &mut self,
-        // dt: instant::Duration,
-        _resources: &mut Resources2,
-        _agents: &mut Agents,
-    ) {
+    pub fn update_state(&mut self, state: FactoryState) {
+        self.state = Some(state);
+    }
+
+    pub fn progress(&self) -> f64 {
+        match &self.state {
+            Some(state) => state.progress,
+            None => 0.0,
+        }
     }
 
     pub fn location(&self) -> &Location {
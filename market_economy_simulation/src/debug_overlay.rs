@@ -2,11 +2,14 @@
 //!
 
 use cgmath::Zero;
+use market_economy_simulation_server::behaviour::Task;
 use wgpu_renderer::{
     vertex_texture_shader::{self, VertexTextureShaderDraw},
     wgpu_renderer::WgpuRendererInterface,
 };
 
+use crate::price_history::PriceHistory;
+
 pub struct DebugOverlay {
     entity_index_label: wgpu_renderer::label::Label,
     entity_index_mesh: wgpu_renderer::label::LabelMesh,
@@ -22,6 +25,19 @@ pub struct DebugOverlay {
 
     z_coord_label: wgpu_renderer::label::Label,
     z_coord_mesh: wgpu_renderer::label::LabelMesh,
+
+    task_label: wgpu_renderer::label::Label,
+    task_mesh: wgpu_renderer::label::LabelMesh,
+
+    prices_label: wgpu_renderer::label::Label,
+    prices_mesh: wgpu_renderer::label::LabelMesh,
+
+    progress_label: wgpu_renderer::label::Label,
+    progress_mesh: wgpu_renderer::label::LabelMesh,
+
+    critical_label: wgpu_renderer::label::Label,
+    critical_mesh: wgpu_renderer::label::LabelMesh,
+    has_critical: bool, // the last critical message of the game logic is shown
 }
 
 impl DebugOverlay {
@@ -109,6 +125,70 @@ impl DebugOverlay {
             },
         );
 
+        let task_label = wgpu_renderer::label::Label::new(font, scale, "          ");
+        let task_mesh = wgpu_renderer::label::LabelMesh::new(
+            renderer,
+            task_label.get_image(),
+            texture_bind_group_layout,
+            &vertex_texture_shader::Instance {
+                position: pos_0
+                    + cgmath::Vector3 {
+                        x: 0.0,
+                        y: (scale + 2.0) * 5.0,
+                        z: 0.0,
+                    },
+                rotation: cgmath::Quaternion::zero(),
+            },
+        );
+
+        let prices_label = wgpu_renderer::label::Label::new(font, scale, "          ");
+        let prices_mesh = wgpu_renderer::label::LabelMesh::new(
+            renderer,
+            prices_label.get_image(),
+            texture_bind_group_layout,
+            &vertex_texture_shader::Instance {
+                position: pos_0
+                    + cgmath::Vector3 {
+                        x: 0.0,
+                        y: (scale + 2.0) * 7.0,
+                        z: 0.0,
+                    },
+                rotation: cgmath::Quaternion::zero(),
+            },
+        );
+
+        let progress_label = wgpu_renderer::label::Label::new(font, scale, "          ");
+        let progress_mesh = wgpu_renderer::label::LabelMesh::new(
+            renderer,
+            progress_label.get_image(),
+            texture_bind_group_layout,
+            &vertex_texture_shader::Instance {
+                position: pos_0
+                    + cgmath::Vector3 {
+                        x: 0.0,
+                        y: (scale + 2.0) * 8.0,
+                        z: 0.0,
+                    },
+                rotation: cgmath::Quaternion::zero(),
+            },
+        );
+
+        let critical_label = wgpu_renderer::label::Label::new(font, scale, "          ");
+        let critical_mesh = wgpu_renderer::label::LabelMesh::new(
+            renderer,
+            critical_label.get_image(),
+            texture_bind_group_layout,
+            &vertex_texture_shader::Instance {
+                position: pos_0
+                    + cgmath::Vector3 {
+                        x: 0.0,
+                        y: (scale + 2.0) * 6.0,
+                        z: 0.0,
+                    },
+                rotation: cgmath::Quaternion::zero(),
+            },
+        );
+
         Self {
             entity_index_label,
             entity_index_mesh,
@@ -120,6 +200,15 @@ impl DebugOverlay {
             y_coord_mesh,
             z_coord_label,
             z_coord_mesh,
+            task_label,
+            task_mesh,
+            prices_label,
+            prices_mesh,
+            progress_label,
+            progress_mesh,
+            critical_label,
+            critical_mesh,
+            has_critical: false,
         }
     }
 
@@ -168,6 +257,69 @@ impl DebugOverlay {
                 .update_texture(renderer.queue(), self.z_coord_label.get_image());
         }
     }
+
+    /// Shows what the selected ant is doing
+    pub fn update_task(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        font: &rusttype::Font<'static>,
+        task: Option<Task>,
+    ) {
+        let text = match task {
+            Some(task) => format!("task: {:?}", task),
+            None => "task: -".to_string(),
+        };
+        self.task_label.update(font, &text);
+        self.task_mesh
+            .update_texture(renderer.queue(), self.task_label.get_image());
+    }
+
+    /// Shows the last trade price of every good, ordered by the good id
+    pub fn update_prices(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        font: &rusttype::Font<'static>,
+        price_history: &PriceHistory,
+    ) {
+        let prices: Vec<String> = (0..price_history.nr_goods())
+            .map(
+                |good| match price_history.prices(good).and_then(|prices| prices.back()) {
+                    Some((_tick, price)) => price.to_string(),
+                    None => "-".to_string(),
+                },
+            )
+            .collect();
+
+        let text = format!("prices: {}", prices.join(" "));
+        self.prices_label.update(font, &text);
+        self.prices_mesh
+            .update_texture(renderer.queue(), self.prices_label.get_image());
+    }
+
+    /// Shows the progress of the current production cycle of the base factory
+    pub fn update_progress(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        font: &rusttype::Font<'static>,
+        progress: f64,
+    ) {
+        let text = format!("factory: {:.0}%", progress * 100.0);
+        self.progress_label.update(font, &text);
+        self.progress_mesh
+            .update_texture(renderer.queue(), self.progress_label.get_image());
+    }
+
+    pub fn show_critical(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        font: &rusttype::Font<'static>,
+        text: &str,
+    ) {
+        self.critical_label.update(font, text);
+        self.critical_mesh
+            .update_texture(renderer.queue(), self.critical_label.get_image());
+        self.has_critical = true;
+    }
 }
 
 impl VertexTextureShaderDraw for DebugOverlay {
@@ -177,5 +329,11 @@ impl VertexTextureShaderDraw for DebugOverlay {
         self.x_coord_mesh.draw(render_pass);
         self.y_coord_mesh.draw(render_pass);
         self.z_coord_mesh.draw(render_pass);
+        self.task_mesh.draw(render_pass);
+        self.prices_mesh.draw(render_pass);
+        self.progress_mesh.draw(render_pass);
+        if self.has_critical {
+            self.critical_mesh.draw(render_pass);
+        }
     }
 }
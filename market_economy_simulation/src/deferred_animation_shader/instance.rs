@@ -0,0 +1,56 @@
+//! Contains the position, the heading and the color of an animated object
+//!
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub entity: [u32; 3],
+    pub rot_z: f32, // counterclockwise around the z axis, zero faces along the x axis
+}
+
+impl Instance {
+    pub fn new() -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            color: [0.0, 0.0, 0.0],
+            entity: [0, 0, 0],
+            rot_z: 0.0,
+        }
+    }
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Instance>() as wgpu::BufferAddress,
+            // We need to switch from using a step mode of Vertex to Instance
+            // This means that our shaders will only change to use the next
+            // instance when the shader stats processing a new instance
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    // the vertex uses the locations 0 to 3, the instance starts at 5 like the other shaders
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Uint32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
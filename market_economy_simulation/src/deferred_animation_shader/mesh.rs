@@ -128,13 +128,14 @@ impl Mesh {
         render_pass: &mut wgpu::RenderPass<'a>,
         animation_buffer: &'a AnimationUniformBuffer,
         instance_buffer: &'a InstanceBuffer<Instance>,
+        nr_instances: u32,
     ) {
         self.vertex_buffer.bind(render_pass);
         self.index_buffer.bind(render_pass);
         animation_buffer.bind(render_pass);
         instance_buffer.bind(render_pass);
 
-        render_pass.draw_indexed(0..self.index_buffer.size(), 0, 0..instance_buffer.size());
+        render_pass.draw_indexed(0..self.index_buffer.size(), 0, 0..nr_instances);
     }
 }
 
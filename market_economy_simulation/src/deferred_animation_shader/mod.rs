@@ -8,6 +8,7 @@ mod pipeline;
 mod animation_bind_group_layout;
 mod animation_uniform;
 mod animation_uniform_buffer;
+mod instance;
 mod vertex;
 
 pub use animation_bind_group_layout::AnimationBindGroupLayout;
@@ -18,8 +19,8 @@ pub use mesh::Mesh;
 pub use pipeline::Pipeline;
 pub use vertex::Vertex;
 
-pub use super::deferred_color_shader::Instance;
 pub use super::deferred_color_shader::InstanceBuffer;
 pub use super::deferred_color_shader::VertexBuffer;
+pub use instance::Instance;
 
 pub use wgpu_renderer::vertex_color_shader::IndexBuffer;
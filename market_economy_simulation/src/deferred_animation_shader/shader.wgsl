@@ -31,6 +31,7 @@ struct InstanceInput {
     @location(5) position: vec3<f32>,
     @location(6) color: vec3<f32>,
     @location(7) entity: vec3<u32>,
+    @location(8) rot_z: f32,
 }
 
 struct VertexOutput {
@@ -68,8 +69,18 @@ fn vs_main(
         total_local_normal += local_normal * joint_weight;
     }
 
+    // turn to the heading of the instance
+    let c = cos(instance.rot_z);
+    let s = sin(instance.rot_z);
+    let rotation = mat3x3<f32>(
+        vec3<f32>(c, s, 0.0),
+        vec3<f32>(-s, c, 0.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+    );
+    let world_normal = rotation * total_local_normal.xyz;
+
     // move to the instance position
-    let world_position = instance.position + total_local_pos.xyz;
+    let world_position = instance.position + rotation * total_local_pos.xyz;
 
     // calculate lighting
     // let light_intensity = 0.8;
@@ -87,7 +98,7 @@ fn vs_main(
     out.clip_position = clip_position;
     out.color = color;
     out.position = world_position;
-    out.normal = total_local_normal.xyz;
+    out.normal = world_normal;
     out.entity = instance.entity;
 
     return out;
@@ -4,11 +4,16 @@
 mod deferred_heightmap_shader_draw;
 mod instance;
 mod mesh;
+mod pheromone_bind_group_layout;
+mod pheromone_texture;
 mod pipeline;
 mod vertex;
 
 pub use deferred_heightmap_shader_draw::DeferredHeightMapShaderDraw;
 pub use mesh::Mesh;
+pub use pheromone_bind_group_layout::PheromoneBindGroupLayout;
+pub use pheromone_texture::PheromoneTexture;
+pub use pheromone_texture::PheromoneUniform;
 pub use pipeline::Pipeline;
 
 pub use wgpu_renderer::vertex_color_shader::IndexBuffer;
@@ -0,0 +1,47 @@
+//! A bind group to create a pheromone texture for this shader
+//!
+
+pub struct PheromoneBindGroupLayout {
+    pheromone_bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl PheromoneBindGroupLayout {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let pheromone_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        // intensities
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // placement in the world
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("pheromone_bind_group_layout"),
+            });
+
+        Self {
+            pheromone_bind_group_layout,
+        }
+    }
+
+    pub fn get(&self) -> &wgpu::BindGroupLayout {
+        &self.pheromone_bind_group_layout
+    }
+}
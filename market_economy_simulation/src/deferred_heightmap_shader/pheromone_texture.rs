@@ -0,0 +1,146 @@
+//! Contains the intensities of the pheromone trails, drawn over the terrain
+//!
+
+use wgpu::util::DeviceExt;
+use wgpu_renderer::wgpu_renderer::WgpuRendererInterface;
+
+use super::PheromoneBindGroupLayout;
+
+/// Placement of the texture in the world
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PheromoneUniform {
+    pub origin: [f32; 2], // lower corner of the first texel
+    pub cell_size: f32,   // world units covered by one texel
+    pub _padding: f32,
+}
+
+pub struct PheromoneTexture {
+    texture: wgpu::Texture,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    width: u32,
+    height: u32,
+}
+
+impl PheromoneTexture {
+    pub fn new(
+        renderer: &mut dyn WgpuRendererInterface,
+        pheromone_bind_group_layout: &PheromoneBindGroupLayout,
+        uniform: PheromoneUniform,
+        intensities: &[f32],
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = renderer.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("Pheromone Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let uniform_buffer =
+            renderer
+                .device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Pheromone Buffer"),
+                    contents: bytemuck::cast_slice(&[uniform]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+
+        let bind_group = renderer
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: pheromone_bind_group_layout.get(),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("pheromone_bind_group"),
+            });
+
+        let mut pheromone_texture = Self {
+            texture,
+            uniform_buffer,
+            bind_group,
+            width,
+            height,
+        };
+        pheromone_texture.write_intensities(renderer.queue(), intensities);
+
+        pheromone_texture
+    }
+
+    /// Replaces the intensities, the texture is recreated if the size changed
+    pub fn update(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        pheromone_bind_group_layout: &PheromoneBindGroupLayout,
+        uniform: PheromoneUniform,
+        intensities: &[f32],
+        width: u32,
+        height: u32,
+    ) {
+        if width != self.width || height != self.height {
+            *self = Self::new(
+                renderer,
+                pheromone_bind_group_layout,
+                uniform,
+                intensities,
+                width,
+                height,
+            );
+            return;
+        }
+
+        renderer
+            .queue()
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.write_intensities(renderer.queue(), intensities);
+    }
+
+    fn write_intensities(&self, queue: &wgpu::Queue, intensities: &[f32]) {
+        assert_eq!(intensities.len(), (self.width * self.height) as usize);
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(intensities),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * self.width),
+                rows_per_image: Some(self.height),
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    pub fn bind<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_bind_group(3, &self.bind_group, &[]);
+    }
+}
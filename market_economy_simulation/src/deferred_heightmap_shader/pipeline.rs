@@ -7,6 +7,7 @@ use super::EntityBuffer;
 use super::GBuffer;
 use super::HeightmapBindGroupLayout;
 use super::Instance;
+use super::PheromoneBindGroupLayout;
 use super::TextureBindGroupLayout;
 use super::Vertex;
 use wgpu_renderer::vertex_color_shader;
@@ -22,6 +23,7 @@ impl Pipeline {
         camera_bind_group_layout: &CameraBindGroupLayout,
         texture_bind_group_layout: &TextureBindGroupLayout,
         heightmap_bind_group_layout: &HeightmapBindGroupLayout,
+        pheromone_bind_group_layout: &PheromoneBindGroupLayout,
         _surface_format: wgpu::TextureFormat,
     ) -> Self {
         // Shader
@@ -38,6 +40,7 @@ impl Pipeline {
                     camera_bind_group_layout.get(),
                     texture_bind_group_layout.get(),
                     heightmap_bind_group_layout.get(),
+                    pheromone_bind_group_layout.get(),
                 ],
                 push_constant_ranges: &[],
             });
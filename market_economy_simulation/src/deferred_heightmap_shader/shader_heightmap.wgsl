@@ -75,6 +75,20 @@ var t_texture: texture_2d<f32>;
 @group(1) @binding(1)
 var s_texture: sampler;
 
+struct PheromoneUniform {
+    origin: vec2<f32>,
+    cell_size: f32,
+    _padding: f32,
+};
+
+@group(3) @binding(0)
+var t_pheromones: texture_2d<f32>;
+@group(3) @binding(1)
+var<uniform> pheromones: PheromoneUniform;
+
+const TRAIL_COLOR: vec3<f32> = vec3<f32>(0.9, 0.5, 0.1);
+const TRAIL_SATURATION: f32 = 2.0; // intensity drawn with the full trail color
+
 struct FragmentOutput {
     // @location(0) surface: vec4<f32>,
     @location(0) position: vec4<f32>,
@@ -92,7 +106,8 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
     var entity3 = (in.entity >> 24u) & 0xffu;
 
     let color = textureSample(t_texture, s_texture, in.tex_coords);
-    let color_out = vec4<f32>(in.color.xyz, color[3]);
+    let trail = clamp(get_pheromones(in.position.xy) / TRAIL_SATURATION, 0.0, 1.0);
+    let color_out = vec4<f32>(mix(in.color.xyz, TRAIL_COLOR, trail), color[3]);
 
     var out: FragmentOutput;
     // out.surface = vec4<f32>(in.color, 1.0);
@@ -142,4 +157,31 @@ fn get_height(uv: vec2<u32>, u_offset: i32, v_offset: i32) -> f32
     let v: u32 = u32(i32(uv.y) + v_offset);
 
     return textureLoad(t_heightmap, vec2(u, v), 0).r; 
-}
\ No newline at end of file
+}
+
+// bilinear interpolation of the pheromone intensities at the middle of the cells,
+// zero outside of the field
+fn get_pheromones(world_pos: vec2<f32>) -> f32
+{
+    let dim = vec2<i32>(textureDimensions(t_pheromones));
+    let uv = (world_pos - pheromones.origin) / pheromones.cell_size - vec2<f32>(0.5, 0.5);
+    let cell = vec2<i32>(floor(uv));
+    let t = uv - floor(uv);
+
+    let i00 = get_pheromone(cell, dim, 0, 0);
+    let i10 = get_pheromone(cell, dim, 1, 0);
+    let i01 = get_pheromone(cell, dim, 0, 1);
+    let i11 = get_pheromone(cell, dim, 1, 1);
+
+    return mix(mix(i00, i10, t.x), mix(i01, i11, t.x), t.y);
+}
+
+fn get_pheromone(cell: vec2<i32>, dim: vec2<i32>, u_offset: i32, v_offset: i32) -> f32
+{
+    let uv = cell + vec2<i32>(u_offset, v_offset);
+    if (any(uv < vec2<i32>(0, 0)) || any(uv >= dim)) {
+        return 0.0;
+    }
+
+    return textureLoad(t_pheromones, uv, 0).r;
+}
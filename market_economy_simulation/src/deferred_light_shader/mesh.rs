@@ -77,6 +77,13 @@ impl Mesh {
         self.vertex_buffer.update(queue, vertices);
     }
 
+    /// Replaces the instance buffer by one with space for all the instances
+    pub fn resize_instance_buffer(&mut self, device: &wgpu::Device, instances: &[Instance]) {
+        self.instance_buffer = InstanceBuffer::new(device, instances);
+        self.max_instances = instances.len() as u32;
+        self.nr_instances = u32::min(self.nr_instances, self.max_instances);
+    }
+
     pub fn update_instance_buffer(&mut self, queue: &wgpu::Queue, instances: &[Instance]) {
         self.instance_buffer.update(queue, instances);
         self.nr_instances = u32::min(instances.len() as u32, self.max_instances);
@@ -1,5 +1,7 @@
 //! contains all data of the world
 
+use market_economy_simulation_server::random;
+
 use crate::{
     base_factory::BaseFactory,
     ground_plane::{GroundPlane, GroundResource, Location},
@@ -19,7 +21,7 @@ pub struct World {
 }
 
 impl World {
-    pub fn new() -> Self {
+    pub fn new(seed: u64) -> Self {
         // entities
         let mut entities: Vec<Entity> = Vec::new();
 
@@ -27,9 +29,10 @@ impl World {
         let ground_plane_height = 100;
         let ground_plane_width = 100;
         let mut ground_plane = GroundPlane::new(ground_plane_width, ground_plane_height);
-        ground_plane.generate_resource(0.005, GroundResource::Red);
-        ground_plane.generate_resource(0.01, GroundResource::Blue);
-        ground_plane.generate_resource(0.001, GroundResource::Green);
+        let mut rng = random::rng(seed, random::Stream::Resources);
+        ground_plane.generate_resource(&mut rng, 0.005, GroundResource::Red);
+        ground_plane.generate_resource(&mut rng, 0.01, GroundResource::Blue);
+        ground_plane.generate_resource(&mut rng, 0.001, GroundResource::Green);
 
         for y in 0..ground_plane_height {
             for x in 0..ground_plane_width {
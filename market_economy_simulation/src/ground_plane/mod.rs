@@ -40,8 +40,12 @@ impl GroundPlane {
         }
     }
 
-    pub fn generate_resource(&mut self, probability: f64, resource: GroundResource) {
-        let mut rng = fastrand::Rng::new();
+    pub fn generate_resource(
+        &mut self,
+        rng: &mut fastrand::Rng,
+        probability: f64,
+        resource: GroundResource,
+    ) {
         for elem in &mut self.fields {
             let rand = rng.f64();
             if rand < probability {
@@ -19,6 +19,7 @@ mod geometry;
 mod ground_plane;
 mod performance_monitor;
 mod point_light_storage;
+mod price_history;
 mod renderer;
 mod selector;
 mod settings;
@@ -28,10 +29,15 @@ mod world_mesh;
 use animated_object_storage::AnimatedObjectStorage;
 use ant_storage::AntStorage;
 use debug_overlay::DebugOverlay;
-use market_economy_simulation_server::game_logic::game_logic_interface::{
-    GameLogicInterface, GameLogicMessageHeavy, GameLogicMessageLight, GameLogicMessageMedium,
+use market_economy_simulation_server::{
+    clock, factories,
+    game_logic::game_logic_interface::{
+        GameLogicInterface, GameLogicMessageCritical, GameLogicMessageHeavy, GameLogicMessageLight,
+        GameLogicMessageMedium, GameLogicMessageRequest, Request, RequestId,
+    },
 };
 use point_light_storage::PointLightStorage;
+use price_history::PriceHistory;
 use selector::Selector;
 use terrain_storage::TerrainStorage;
 use wgpu_renderer::{
@@ -46,19 +52,22 @@ use winit::event::{ElementState, WindowEvent};
 use wasm_bindgen::prelude::*;
 
 const WATCH_POINTS_SIZE: usize = 7;
+const RECORDING_FILE: &str = "recording.mes"; // written on F9, replayed by the headless server
+const SNAPSHOT_FILE: &str = "snapshot.mes"; // written on F6, loaded on F7
 
 struct MarketEconomySimulation {
-    _settings: settings::Settings,
+    settings: settings::Settings,
 
     size: winit::dpi::PhysicalSize<u32>,
     scale_factor: f32,
 
     renderer: renderer::Renderer,
-    _world: ecs2::World,
+    world: ecs2::World,
     // world_mesh: world_mesh::WorldMesh,
 
     // ant: deferred_color_shader::Mesh,
     ant_storage: AntStorage,
+    price_history: PriceHistory,
 
     // performance monitor
     watch_fps: watch::Watch<WATCH_POINTS_SIZE>,
@@ -74,6 +83,8 @@ struct MarketEconomySimulation {
     debug_overlay: DebugOverlay,
 
     game_logic: market_economy_simulation_server::GameLogicServer,
+    clock_state: Option<clock::ClockState>, // last state of the simulation clock
+    next_request_id: RequestId,             // id of the next request to the game logic
     ant: ant::Ant,
 
     ambient_light_quad: deferred_light_shader::Mesh, // Quad running the global ambient light shader
@@ -98,16 +109,7 @@ impl MarketEconomySimulation {
         let font = wgpu_renderer::freefont::create_font_free_mono();
 
         // world
-        let mut world = ecs2::World::new();
-
-        let blue_token = world.resources.blues2.create(0.0, 1.0, 1.0);
-
-        world
-            .base_factory
-            .add_blue(blue_token, &mut world.resources);
-        world
-            .base_factory
-            .produce(&mut world.resources, &mut world.agents);
+        let world = create_world(settings.seed);
 
         // world mesh
         let _world_mesh = world_mesh::WorldMesh::new(renderer_interface, &world);
@@ -171,9 +173,22 @@ impl MarketEconomySimulation {
             settings.max_nr_ants,
         );
 
+        // market prices
+        let price_history = PriceHistory::new();
+
         // create game server
-        let game_logic =
-            market_economy_simulation_server::GameLogicServer::new(settings.get_server_settings());
+        let game_logic = match &settings.server_address {
+            Some(addr) => market_economy_simulation_server::GameLogicServer::connect(addr)
+                .unwrap_or_else(|err| {
+                    log::warn!("{}, running the simulation locally", err);
+                    market_economy_simulation_server::GameLogicServer::new(
+                        settings.get_server_settings(),
+                    )
+                }),
+            None => market_economy_simulation_server::GameLogicServer::new(
+                settings.get_server_settings(),
+            ),
+        };
 
         // create ant
         let ant = ant::Ant::new(renderer_interface);
@@ -205,22 +220,24 @@ impl MarketEconomySimulation {
             settings.get_terrain_settings(),
             renderer_interface,
             &renderer.texture_bind_group_layout,
+            &renderer.pheromone_bind_group_layout,
         );
 
         // selector
         let selector = Selector::new();
 
         Self {
-            _settings: settings,
+            settings,
 
             size,
             scale_factor,
 
             renderer,
 
-            _world: world,
+            world,
             // world_mesh,
             ant_storage,
+            price_history,
 
             watch_fps,
             performance_monitor_fps,
@@ -234,6 +251,8 @@ impl MarketEconomySimulation {
             debug_overlay,
 
             game_logic,
+            clock_state: None,
+            next_request_id: 0,
 
             ant,
 
@@ -244,6 +263,62 @@ impl MarketEconomySimulation {
             selector,
         }
     }
+
+    /// Controls the simulation clock and restarts the simulation, returns if the key was used
+    fn process_simulation_keyboard(&mut self, key: winit::keyboard::KeyCode) -> bool {
+        let is_paused = self.clock_state.is_some_and(|elem| elem.is_paused);
+        let time_scale = self.clock_state.map_or(1.0, |elem| elem.time_scale);
+
+        let request = match key {
+            winit::keyboard::KeyCode::KeyP if is_paused => GameLogicMessageRequest::Resume,
+            winit::keyboard::KeyCode::KeyP => GameLogicMessageRequest::Pause,
+            winit::keyboard::KeyCode::Period => GameLogicMessageRequest::Step,
+            winit::keyboard::KeyCode::Equal => {
+                GameLogicMessageRequest::SetTimeScale(time_scale * 2.0)
+            }
+            winit::keyboard::KeyCode::Minus => {
+                GameLogicMessageRequest::SetTimeScale(time_scale / 2.0)
+            }
+            winit::keyboard::KeyCode::F5 => {
+                self.price_history = PriceHistory::new();
+                GameLogicMessageRequest::Reset(self.settings.get_server_settings())
+            }
+            winit::keyboard::KeyCode::F6 => {
+                GameLogicMessageRequest::SaveSnapshot(SNAPSHOT_FILE.to_string())
+            }
+            winit::keyboard::KeyCode::F7 => {
+                GameLogicMessageRequest::LoadSnapshot(SNAPSHOT_FILE.to_string())
+            }
+            winit::keyboard::KeyCode::F9 => {
+                GameLogicMessageRequest::SaveRecording(RECORDING_FILE.to_string())
+            }
+            _ => return false,
+        };
+
+        let res = self.game_logic.send_messages().send(Request {
+            id: self.next_request_id,
+            message: request,
+        });
+        self.next_request_id += 1;
+        match res {
+            Ok(_) => {}
+            Err(err) => log::error!("{}", err),
+        }
+
+        true
+    }
+}
+
+fn create_world(seed: u64) -> ecs2::World {
+    let mut world = ecs2::World::new(seed);
+
+    let blue_token = world.resources.blues2.create(0.0, 1.0, 1.0);
+
+    world
+        .base_factory
+        .add_blue(blue_token, &mut world.resources);
+
+    world
 }
 
 #[allow(unused)]
@@ -302,7 +377,7 @@ impl DefaultApplicationInterface for MarketEconomySimulation {
         self.debug_overlay
             .update_entity(renderer_interface, &self.font, self.entity_index);
 
-        self.game_logic.update();
+        self.game_logic.update(dt);
 
         self.watch_fps.start(3, "Update data");
         {
@@ -321,11 +396,24 @@ impl DefaultApplicationInterface for MarketEconomySimulation {
                         // );
                     }
                     GameLogicMessageLight::UpdateAnt(ant) => {
-                        self.ant_storage.set_ant(&ant);
+                        self.ant_storage.set_ant(
+                            renderer_interface,
+                            &self.renderer.animation_bind_group_layout,
+                            &ant,
+                        );
+                    }
+                    GameLogicMessageLight::UpdateAnts(batch) => {
+                        self.ant_storage.update_ants(
+                            renderer_interface,
+                            &self.renderer.animation_bind_group_layout,
+                            &batch,
+                        );
                     }
                 }
             }
 
+            let mut prices_changed = false;
+            let mut progress_changed = false;
             let medium_messages = self.game_logic.get_medium_messages();
             for msg in medium_messages.try_iter() {
                 match msg {
@@ -336,19 +424,134 @@ impl DefaultApplicationInterface for MarketEconomySimulation {
                             &watch_viewer_data,
                         );
                     }
+                    GameLogicMessageMedium::UpdateClock(clock_state) => {
+                        self.clock_state = Some(clock_state);
+                    }
+                    GameLogicMessageMedium::MarketFill(trade) => {
+                        self.price_history.add_trade(&trade);
+                        prices_changed = true;
+                    }
+                    GameLogicMessageMedium::UpdateFactory(factory_state) => {
+                        if factory_state.id == factories::BASE_FACTORY_ID {
+                            self.world.base_factory.update_state(factory_state);
+                            progress_changed = true;
+                        }
+                    }
+                    GameLogicMessageMedium::AntBorn(ant) => {
+                        self.ant_storage.set_ant(
+                            renderer_interface,
+                            &self.renderer.animation_bind_group_layout,
+                            &ant,
+                        );
+                    }
+                    GameLogicMessageMedium::AntDied { id, cause: _ } => {
+                        self.ant_storage.remove_ant(id);
+                    }
+                    GameLogicMessageMedium::UpdateChannelStatistics(channel_statistics) => {
+                        let medium = channel_statistics.medium;
+                        let light = channel_statistics.light;
+                        let info = format!(
+                            "coalesced {} dropped {} backlog {}",
+                            medium.coalesced + light.coalesced,
+                            medium.dropped + light.dropped,
+                            channel_statistics.heavy.backlog + medium.backlog + light.backlog,
+                        );
+                        self.performance_monitor_ups.update_info(
+                            renderer_interface,
+                            &self.font,
+                            info,
+                        );
+                    }
+                    GameLogicMessageMedium::SnapshotLoaded(settings) => {
+                        // the alive ants follow as births
+                        self.ant_storage.clear();
+                        self.price_history = PriceHistory::new();
+                        prices_changed = true;
+
+                        if settings.seed != self.settings.seed {
+                            self.settings.seed = settings.seed;
+                            self.world = create_world(settings.seed);
+                            self.terrain_storage.clear();
+                        }
+                    }
+                    GameLogicMessageMedium::Synced {
+                        id: _,
+                        settings,
+                        clock,
+                        ants,
+                        factories: factory_states,
+                    } => {
+                        // joined a running session, the state is replaced like after loading a snapshot
+                        self.ant_storage.clear();
+                        self.price_history = PriceHistory::new();
+                        prices_changed = true;
+
+                        if settings.seed != self.settings.seed {
+                            self.settings.seed = settings.seed;
+                            self.world = create_world(settings.seed);
+                            self.terrain_storage.clear();
+                        }
+
+                        self.clock_state = Some(clock);
+                        for ant in &ants {
+                            self.ant_storage.set_ant(
+                                renderer_interface,
+                                &self.renderer.animation_bind_group_layout,
+                                ant,
+                            );
+                        }
+                        for factory_state in factory_states {
+                            if factory_state.id == factories::BASE_FACTORY_ID {
+                                self.world.base_factory.update_state(factory_state);
+                                progress_changed = true;
+                            }
+                        }
+                    }
+                }
+            }
+            if prices_changed {
+                self.debug_overlay.update_prices(
+                    renderer_interface,
+                    &self.font,
+                    &self.price_history,
+                );
+            }
+            if progress_changed {
+                self.debug_overlay.update_progress(
+                    renderer_interface,
+                    &self.font,
+                    self.world.base_factory.progress(),
+                );
+            }
+
+            let critical_messages = self.game_logic.get_critical_messages();
+            for msg in critical_messages.try_iter() {
+                log::error!("{}", msg);
+                if let GameLogicMessageCritical::ServerPanicked(_) = msg {
+                    log::error!("the simulation stopped");
                 }
+                self.debug_overlay
+                    .show_critical(renderer_interface, &self.font, &msg.to_string());
             }
 
             let heavy_messages = self.game_logic.get_heavy_messages();
             for msg in heavy_messages.try_iter() {
                 match msg {
-                    GameLogicMessageHeavy::Terrain(height_map) => {
+                    GameLogicMessageHeavy::Terrain { id, height_map } => {
                         self.terrain_storage.update_height_map(
                             renderer_interface,
                             &self.renderer.heightmap_bind_group_layout,
+                            id,
                             height_map,
                         );
                     }
+                    GameLogicMessageHeavy::Pheromones(field) => {
+                        self.terrain_storage.update_pheromones(
+                            renderer_interface,
+                            &self.renderer.pheromone_bind_group_layout,
+                            &field,
+                        );
+                    }
                 }
             }
 
@@ -357,7 +560,7 @@ impl DefaultApplicationInterface for MarketEconomySimulation {
             self.terrain_storage
                 .update_view_position(&self.renderer.get_view_position());
             self.terrain_storage
-                .submit_requests(self.game_logic.send_messages());
+                .submit_requests(self.game_logic.send_messages(), &mut self.next_request_id);
         }
         self.watch_fps.stop(3);
 
@@ -383,7 +586,13 @@ impl DefaultApplicationInterface for MarketEconomySimulation {
                             &triangle.p,
                         );
                     }
-                    selector::Result::Ant(_ant) => {}
+                    selector::Result::Ant(ant) => {
+                        self.debug_overlay.update_task(
+                            renderer_interface,
+                            &self.font,
+                            self.ant_storage.task(ant),
+                        );
+                    }
                 }
             }
         }
@@ -444,6 +653,15 @@ impl DefaultApplicationInterface for MarketEconomySimulation {
                 self.performance_monitor_ups.show = !self.performance_monitor_ups.show;
                 true
             }
+            WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        physical_key: winit::keyboard::PhysicalKey::Code(key),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if self.process_simulation_keyboard(*key) => true,
             WindowEvent::KeyboardInput {
                 event:
                     winit::event::KeyEvent {
@@ -503,6 +721,15 @@ impl DefaultApplicationInterface for MarketEconomySimulation {
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 pub async fn run() {
+    // the renderer may have set up the logger already
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            let _res = console_log::init_with_level(log::Level::Warn);
+        } else {
+            let _res = env_logger::try_init();
+        }
+    }
+
     let event_loop = winit::event_loop::EventLoop::new().unwrap();
     let mut application: DefaultApplication<MarketEconomySimulation> = DefaultApplication::new();
     event_loop.run_app(&mut application).unwrap();
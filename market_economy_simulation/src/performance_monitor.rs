@@ -20,6 +20,11 @@ pub struct PerformanceMonitor<const SIZE: usize> {
     // label_120fps: wgpu_renderer::label::LabelMesh,
     table: sorted_table::SortedTable<SIZE>,
 
+    // additional line below the table, e.g. the message channel statistics
+    label_info_host: wgpu_renderer::label::Label,
+    label_info: wgpu_renderer::label::LabelMesh,
+    info: String,
+
     pub show: bool,
 }
 
@@ -74,6 +79,25 @@ impl<const SIZE: usize> PerformanceMonitor<SIZE> {
             },
         );
 
+        let label_info_host = wgpu_renderer::label::Label::new(
+            font,
+            scale,
+            "............................................................",
+        );
+        let label_info = wgpu_renderer::label::LabelMesh::new(
+            wgpu_renderer,
+            label_info_host.get_image(),
+            texture_bind_group_layout,
+            &vertex_color_shader::Instance {
+                position: cgmath::Vector3::new(
+                    graph_host.get_width() as f32 + 5.0,
+                    10.0 + (SIZE + 1) as f32 * scale,
+                    0.0,
+                ),
+                rotation: cgmath::Quaternion::zero(),
+            },
+        );
+
         Self {
             graph_host,
             graph_device,
@@ -81,6 +105,11 @@ impl<const SIZE: usize> PerformanceMonitor<SIZE> {
             label_60fps,
 
             table,
+
+            label_info_host,
+            label_info,
+            info: String::new(),
+
             show,
         }
     }
@@ -100,6 +129,20 @@ impl<const SIZE: usize> PerformanceMonitor<SIZE> {
             self.table.update_device(wgpu_renderer, font);
         }
     }
+
+    pub fn update_info(
+        &mut self,
+        wgpu_renderer: &mut dyn WgpuRendererInterface,
+        font: &rusttype::Font<'static>,
+        info: String,
+    ) {
+        if self.show && info != self.info {
+            self.label_info_host.update(font, info.as_str()); // this is an expensive operation
+            self.label_info
+                .update_texture(wgpu_renderer.queue(), self.label_info_host.get_image());
+            self.info = info;
+        }
+    }
 }
 
 impl<const SIZE: usize> VertexColorShaderDraw for PerformanceMonitor<SIZE> {
@@ -132,6 +175,10 @@ impl<const SIZE: usize> VertexTextureShaderDraw for PerformanceMonitor<SIZE> {
             for elem in &self.table.mesh_names {
                 elem.draw(render_pass);
             }
+
+            if !self.info.is_empty() {
+                self.label_info.draw(render_pass);
+            }
         }
     }
 }
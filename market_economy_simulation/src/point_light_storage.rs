@@ -83,6 +83,28 @@ impl PointLightStorage {
     pub fn max_instances(&self) -> usize {
         self.max_instances
     }
+
+    /// Makes space for more instances, the new ones are inactive
+    pub fn grow(&mut self, renderer: &mut dyn WgpuRendererInterface, max_instances: usize) {
+        if max_instances <= self.max_instances {
+            return;
+        }
+
+        self.instances
+            .resize(max_instances, deferred_light_shader::Instance::new());
+        self.is_active.resize(max_instances, false);
+        self.active_instances
+            .resize(max_instances, deferred_light_shader::Instance::new());
+        self.max_instances = max_instances;
+
+        self.light
+            .resize_instance_buffer(renderer.device(), &self.instances);
+    }
+
+    /// The host data of all instances, e.g. to update many of them in one pass
+    pub fn instances_mut(&mut self) -> &mut [deferred_light_shader::Instance] {
+        &mut self.instances
+    }
 }
 
 #[allow(unused)]
@@ -184,7 +206,7 @@ impl PointLightInterface for PointLightStorage {
     fn set_color(&mut self, index: &PointLightIndex, color: cgmath::Vector3<f32>) {
         let i = index.instance_index;
 
-        self.instances[i].position = color.into();
+        self.instances[i].light_color = color.into();
     }
 
     fn set_active(&mut self, index: &PointLightIndex, is_active: bool) {
@@ -0,0 +1,39 @@
+//! Keeps the recent trade prices of all goods to chart them
+//!
+
+use std::collections::VecDeque;
+
+use market_economy_simulation_server::{goods, market};
+
+const HISTORY_SIZE: usize = 256;
+
+pub struct PriceHistory {
+    prices: Vec<VecDeque<(u64, market::Price)>>, // (tick, price) indexed by the good id
+}
+
+impl PriceHistory {
+    pub fn new() -> Self {
+        Self { prices: Vec::new() }
+    }
+
+    pub fn add_trade(&mut self, trade: &market::Trade) {
+        if trade.good >= self.prices.len() {
+            self.prices.resize(trade.good + 1, VecDeque::new());
+        }
+
+        let prices = &mut self.prices[trade.good];
+        if prices.len() == HISTORY_SIZE {
+            prices.pop_front();
+        }
+        prices.push_back((trade.tick, trade.price));
+    }
+
+    /// Number of goods up to the highest traded one, goods without trades have no prices
+    pub fn nr_goods(&self) -> usize {
+        self.prices.len()
+    }
+
+    pub fn prices(&self, good: goods::GoodId) -> Option<&VecDeque<(u64, market::Price)>> {
+        self.prices.get(good)
+    }
+}
@@ -54,6 +54,7 @@ pub struct Renderer {
     pipeline_deferred_animated: deferred_animation_shader::Pipeline,
 
     pub heightmap_bind_group_layout: deferred_heightmap_shader::HeightmapBindGroupLayout,
+    pub pheromone_bind_group_layout: deferred_heightmap_shader::PheromoneBindGroupLayout,
     pipeline_deferred_heightmap: deferred_heightmap_shader::Pipeline,
 
     post_processing_bind_group_layout: fxaa_shader::PostProcessingTextureBindGroupLayout,
@@ -173,11 +174,14 @@ impl Renderer {
         // pipeline deferred heightmap
         let heightmap_bind_group_layout =
             deferred_heightmap_shader::HeightmapBindGroupLayout::new(wgpu_renderer.device());
+        let pheromone_bind_group_layout =
+            deferred_heightmap_shader::PheromoneBindGroupLayout::new(wgpu_renderer.device());
         let pipeline_deferred_heightmap = deferred_heightmap_shader::Pipeline::new(
             wgpu_renderer.device(),
             &camera_bind_group_layout,
             &texture_bind_group_layout,
             &heightmap_bind_group_layout,
+            &pheromone_bind_group_layout,
             surface_format,
         );
 
@@ -257,6 +261,7 @@ impl Renderer {
             pipeline_deferred_animated,
 
             heightmap_bind_group_layout,
+            pheromone_bind_group_layout,
             pipeline_deferred_heightmap,
 
             post_processing_bind_group_layout,
@@ -30,6 +30,15 @@ pub struct Settings {
 
     pub max_nr_ants: usize,
 
+    // threads generating the terrain, falls back to the game logic thread on the web
+    pub nr_terrain_workers: usize,
+
+    // feeds all random sources, the same seed creates the same world
+    pub seed: u64,
+
+    // connects to a server process instead of running the simulation locally (e.g. "127.0.0.1:7878")
+    pub server_address: Option<String>,
+
     // miscellaneous
     /// Maximum number of instances of the point lights
     // pub max_point_light_instances: usize,
@@ -58,6 +67,9 @@ impl Settings {
             enable_multithreading: true,
             // max_nr_ants: 10000,
             max_nr_ants: 100,
+            nr_terrain_workers: 2,
+            seed: 1,
+            server_address: None,
 
             // miscellaneous
             // max_point_light_instances: 65536,
@@ -88,6 +100,8 @@ impl Settings {
         market_economy_simulation_server::game_logic::GameLogicSettings {
             enable_multithreading: self.enable_multithreading,
             max_nr_ants: self.max_nr_ants,
+            seed: self.seed,
+            nr_terrain_workers: self.nr_terrain_workers,
         }
     }
 
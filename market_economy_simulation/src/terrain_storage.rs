@@ -5,12 +5,14 @@ pub mod lod_quad_tree;
 pub mod quad_tree_draw;
 pub mod terrain_texture_details;
 
+use std::collections::BTreeMap;
 use std::sync::mpsc;
 
 use cgmath::Zero;
 use lod_quad_tree::LodQuadTree;
 use market_economy_simulation_server::{
-    game_logic::game_logic_interface::GameLogicMessageRequest, heightmap_generator,
+    game_logic::game_logic_interface::{GameLogicMessageRequest, Request, RequestId},
+    heightmap_generator, pheromones,
 };
 use quad_tree_draw::QuadTreeDraw;
 use terrain_texture_details::TerrainTextureDetails;
@@ -37,6 +39,7 @@ pub struct TerrainStorage {
     texture: deferred_heightmap_shader::Texture,
     heightmap_textures: Vec<deferred_heightmap_shader::HeightmapTexture>,
     instances: Vec<deferred_heightmap_shader::InstanceBuffer<deferred_heightmap_shader::Instance>>,
+    pheromone_texture: deferred_heightmap_shader::PheromoneTexture, // drawn over all tiles
     pub height_map_details: Vec<TerrainTextureDetails>,
     pub height_maps: Vec<Vec<f32>>,
 
@@ -48,6 +51,8 @@ pub struct TerrainStorage {
     nr_tiles: usize,
 
     requests: Vec<TerrainTextureDetails>,
+    cancellations: Vec<usize>,
+    pending_requests: BTreeMap<usize, RequestId>, // the requests sent for the nodes, until the data arrives
 }
 
 impl TerrainStorage {
@@ -56,6 +61,7 @@ impl TerrainStorage {
         renderer: &mut dyn WgpuRendererInterface,
         texture_bind_group_layout: &deferred_heightmap_shader::TextureBindGroupLayout,
         // heightmap_bind_group_layout: &deferred_heightmap_shader::HeightmapBindGroupLayout,
+        pheromone_bind_group_layout: &deferred_heightmap_shader::PheromoneBindGroupLayout,
     ) -> Self {
         let max_depth = settings.max_depth;
         let nr_tiles = settings.nr_tiles;
@@ -80,6 +86,20 @@ impl TerrainStorage {
         )
         .unwrap();
 
+        // pheromone_texture, empty until the first field arrives
+        let pheromone_texture = deferred_heightmap_shader::PheromoneTexture::new(
+            renderer,
+            pheromone_bind_group_layout,
+            deferred_heightmap_shader::PheromoneUniform {
+                origin: [0.0, 0.0],
+                cell_size: 1.0,
+                _padding: 0.0,
+            },
+            &[0.0],
+            1,
+            1,
+        );
+
         // heightmap_textures
         let heightmap_textures = Vec::new();
         let instances = Vec::new();
@@ -94,6 +114,8 @@ impl TerrainStorage {
 
         // requests
         let requests = Vec::new();
+        let cancellations = Vec::new();
+        let pending_requests = BTreeMap::new();
 
         Self {
             _settings: settings,
@@ -101,6 +123,7 @@ impl TerrainStorage {
             texture,
             heightmap_textures,
             instances,
+            pheromone_texture,
             height_map_details: heightmap_details,
             height_maps,
             lod_quad_tree,
@@ -108,6 +131,8 @@ impl TerrainStorage {
             max_depth,
             nr_tiles,
             requests,
+            cancellations,
+            pending_requests,
         }
     }
 
@@ -115,8 +140,16 @@ impl TerrainStorage {
         &mut self,
         renderer: &mut dyn WgpuRendererInterface,
         heightmap_bind_group_layout: &deferred_heightmap_shader::HeightmapBindGroupLayout,
+        id: RequestId,
         height_map: heightmap_generator::HeightMap,
     ) {
+        // the response to a cancelled request or from before a reset
+        let node_index = height_map.details.node_index;
+        if self.pending_requests.get(&node_index) != Some(&id) {
+            return;
+        }
+        self.pending_requests.remove(&node_index);
+
         let pos_0 = height_map.details.pos_0;
         let pos_1 = height_map.details.pos_1;
         let point_distance = height_map.details.point_distance;
@@ -188,6 +221,41 @@ impl TerrainStorage {
         self.lod_quad_tree.set_data_index(node_index, data_index);
     }
 
+    /// Replaces the pheromone trails drawn over the terrain
+    pub fn update_pheromones(
+        &mut self,
+        renderer: &mut dyn WgpuRendererInterface,
+        pheromone_bind_group_layout: &deferred_heightmap_shader::PheromoneBindGroupLayout,
+        field: &pheromones::PheromoneField,
+    ) {
+        let uniform = deferred_heightmap_shader::PheromoneUniform {
+            origin: [field.origin.x as f32, field.origin.y as f32],
+            cell_size: field.cell_size as f32,
+            _padding: 0.0,
+        };
+
+        self.pheromone_texture.update(
+            renderer,
+            pheromone_bind_group_layout,
+            uniform,
+            &field.intensities,
+            field.size_x as u32,
+            field.size_y as u32,
+        );
+    }
+
+    /// Drops all height maps, the visible tiles are requested again
+    pub fn clear(&mut self) {
+        self.heightmap_textures.clear();
+        self.instances.clear();
+        self.height_map_details.clear();
+        self.height_maps.clear();
+        self.lod_quad_tree = LodQuadTree::new(self.max_depth, self.nr_tiles);
+        self.requests.clear();
+        self.cancellations.clear();
+        self.pending_requests.clear();
+    }
+
     pub fn update_view_position(&mut self, view_position: &cgmath::Vector3<f32>) {
         self.view_position = cgmath::Vector3::new(
             view_position.x as isize,
@@ -196,23 +264,59 @@ impl TerrainStorage {
         );
     }
 
-    pub fn submit_requests(&mut self, sender: &mpsc::Sender<GameLogicMessageRequest>) {
+    /// Sends the requests for the missing tiles and cancels the ones no longer in view,
+    /// the ids are taken from `next_request_id`
+    pub fn submit_requests(
+        &mut self,
+        sender: &mpsc::Sender<Request>,
+        next_request_id: &mut RequestId,
+    ) {
+        for node_index in &self.cancellations {
+            if let Some(id) = self.pending_requests.remove(node_index) {
+                let _res = sender.send(Request {
+                    id: *next_request_id,
+                    message: GameLogicMessageRequest::Cancel(id),
+                });
+                *next_request_id += 1;
+            }
+        }
+
+        // the server generates the tiles closest to the camera first
+        if !self.requests.is_empty() {
+            let _res = sender.send(Request {
+                id: *next_request_id,
+                message: GameLogicMessageRequest::SetViewPosition(cgmath::Vector2::new(
+                    self.view_position.x as f32,
+                    self.view_position.y as f32,
+                )),
+            });
+            *next_request_id += 1;
+        }
+
         for elem in &self.requests {
-            let _res = sender.send(GameLogicMessageRequest::GetTerrain(
-                heightmap_generator::HeightMapDetails {
-                    pos_0: elem.pos_0,
-                    pos_1: elem.pos_1,
-                    point_distance: elem.point_distance,
-                    size_0: elem.size_0,
-                    size_1: elem.size_1,
-                    nr_tiles: elem.nr_tiles,
-                    depth: elem.depth,
-                    node_index: elem.node_index,
-                },
-            ));
+            let id = *next_request_id;
+            *next_request_id += 1;
+            self.pending_requests.insert(elem.node_index, id);
+
+            let _res = sender.send(Request {
+                id,
+                message: GameLogicMessageRequest::GetTerrain(
+                    heightmap_generator::HeightMapDetails {
+                        pos_0: elem.pos_0,
+                        pos_1: elem.pos_1,
+                        point_distance: elem.point_distance,
+                        size_0: elem.size_0,
+                        size_1: elem.size_1,
+                        nr_tiles: elem.nr_tiles,
+                        depth: elem.depth,
+                        node_index: elem.node_index,
+                    },
+                ),
+            });
         }
 
         self.requests.clear();
+        self.cancellations.clear();
     }
 }
 
@@ -223,6 +327,7 @@ impl DeferredHeightMapShaderDraw for TerrainStorage {
         let texture = &self.texture;
         let heightmap_textures = &self.heightmap_textures;
         let instances = &self.instances;
+        let pheromone_texture = &self.pheromone_texture;
 
         // quad tree data
         let lod_quad_tree = &mut self.lod_quad_tree;
@@ -233,6 +338,7 @@ impl DeferredHeightMapShaderDraw for TerrainStorage {
         // draw
         mesh.bind(render_pass);
         texture.bind(render_pass);
+        pheromone_texture.bind(render_pass);
 
         let quad_tree_draw = &mut QuadTreeDraw::new(max_depth, nr_tiles, |data_index| {
             heightmap_textures[data_index].bind(render_pass);
@@ -243,5 +349,6 @@ impl DeferredHeightMapShaderDraw for TerrainStorage {
         lod_quad_tree.traverse_leaves(view_position, quad_tree_draw);
 
         self.requests = quad_tree_draw.requests.clone();
+        self.cancellations = quad_tree_draw.cancellations.clone();
     }
 }
@@ -7,6 +7,7 @@ use super::terrain_texture_details::depth_to_distance;
 
 pub trait QuadTreeInterface {
     fn request_data(&mut self, node: usize, square: Square, depth: usize);
+    fn cancel_data(&mut self, node: usize);
     fn do_work(&mut self, index: usize, square: Square, depth: usize);
 }
 
@@ -103,6 +104,19 @@ impl LodQuadTree {
             // check children
             let first_child_index = self.nodes[node].first_child.unwrap();
 
+            // request the children again, that were cancelled while out of range
+            for quadrant in 0..4 {
+                let child = &mut self.nodes[first_child_index + quadrant];
+                if child.data.is_none() && !child.is_requested {
+                    child.is_requested = true;
+                    data_interface.request_data(
+                        first_child_index + quadrant,
+                        square.quadrant(quadrant),
+                        depth + 1,
+                    );
+                }
+            }
+
             let data_available = self.nodes[first_child_index].data.is_some()
                 && self.nodes[first_child_index + 1].data.is_some()
                 && self.nodes[first_child_index + 2].data.is_some()
@@ -142,11 +156,31 @@ impl LodQuadTree {
             }
         }
 
+        // the requested data below is not needed anymore
+        if !is_in_range {
+            if let Some(first_child) = self.nodes[node].first_child {
+                self.cancel_requests(first_child, data_interface);
+            }
+        }
+
         // work with data
         if let Some(data_index) = self.nodes[node].data {
             data_interface.do_work(data_index, square, depth)
         }
     }
+
+    fn cancel_requests(&mut self, first_child: usize, data_interface: &mut impl QuadTreeInterface) {
+        for child in first_child..first_child + 4 {
+            if self.nodes[child].is_requested && self.nodes[child].data.is_none() {
+                self.nodes[child].is_requested = false;
+                data_interface.cancel_data(child);
+            }
+
+            if let Some(first_grandchild) = self.nodes[child].first_child {
+                self.cancel_requests(first_grandchild, data_interface);
+            }
+        }
+    }
 }
 
 struct QuadNode {
@@ -159,6 +193,9 @@ struct QuadNode {
 
     /// Points to the index of the data
     data: Option<usize>,
+
+    /// The data is on its way, nodes are created when their data is requested
+    is_requested: bool,
 }
 
 impl QuadNode {
@@ -166,6 +203,7 @@ impl QuadNode {
         Self {
             first_child: None,
             data: None,
+            is_requested: true,
         }
     }
 }
@@ -193,6 +231,16 @@ impl Square {
         cgmath::Vector2 { x: pos_x, y: pos_y }
     }
 
+    /// The quadrants in the order of the children
+    pub fn quadrant(&self, index: usize) -> Square {
+        match index {
+            0 => self.quadrant_sw(),
+            1 => self.quadrant_se(),
+            2 => self.quadrant_ne(),
+            _ => self.quadrant_nw(),
+        }
+    }
+
     pub fn quadrant_sw(&self) -> Square {
         let pos_x = self.pos_0.x;
         let pos_y = self.pos_0.y;
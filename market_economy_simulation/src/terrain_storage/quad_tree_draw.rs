@@ -16,6 +16,7 @@ where
     draw_function: T,
 
     pub requests: Vec<TerrainTextureDetails>,
+    pub cancellations: Vec<usize>, // nodes whose requested data is not needed anymore
 }
 
 impl<T> QuadTreeDraw<T>
@@ -28,6 +29,7 @@ where
             nr_tiles,
             draw_function,
             requests: Vec::new(),
+            cancellations: Vec::new(),
         }
     }
 }
@@ -59,6 +61,10 @@ where
         self.requests.push(details);
     }
 
+    fn cancel_data(&mut self, node: usize) {
+        self.cancellations.push(node);
+    }
+
     fn do_work(&mut self, index: usize, _square: super::lod_quad_tree::Square, _depth: usize) {
         (self.draw_function)(index)
     }
@@ -0,0 +1,277 @@
+//! Money and the accounts of all participants of the economy
+//!
+//! Every movement of money is recorded as a balanced ledger entry, taking the
+//! amount from one account and adding it to another. Money is only created by
+//! minting and destroyed by burning, so the sum of all balances always equals
+//! the minted minus the burned money.
+//! Sellers pay a share of their revenue as tax to the treasury.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::market::Trader;
+use crate::wire::{self, Decode, Encode};
+
+pub type Money = u64;
+
+const LEDGER_SIZE: usize = 4096; // nr of the most recent entries kept in the ledger
+
+pub const SALES_TAX: Money = 20; // sellers pay 1/SALES_TAX of their revenue
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Account {
+    Ant(usize),
+    Factory(usize),
+    Household(usize), // indexed by the house id
+    Treasury,         // collects the taxes
+}
+
+impl From<Trader> for Account {
+    fn from(trader: Trader) -> Self {
+        match trader {
+            Trader::Ant(id) => Account::Ant(id),
+            Trader::Factory(id) => Account::Factory(id),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    Mint,
+    Burn,
+    Trade,
+    Wage,
+    Tax,
+    Inheritance,
+}
+
+/// Movement of money, `None` stands for money entering or leaving the economy
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub tick: u64,
+    pub kind: EntryKind,
+    pub from: Option<Account>,
+    pub to: Option<Account>,
+    pub amount: Money,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InsufficientFunds {
+    pub account: Account,
+    pub balance: Money,
+    pub amount: Money,
+}
+
+impl std::fmt::Display for InsufficientFunds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "insufficient funds in {:?}: balance {}, required {}",
+            self.account, self.balance, self.amount
+        )
+    }
+}
+
+impl std::error::Error for InsufficientFunds {}
+
+#[derive(Default)]
+pub struct Accounts {
+    balances: BTreeMap<Account, Money>,
+    ledger: VecDeque<Entry>,
+
+    minted: Money,
+    burned: Money,
+}
+
+impl Accounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn balance(&self, account: Account) -> Money {
+        self.balances.get(&account).copied().unwrap_or(0)
+    }
+
+    /// Most recent entries, the oldest first
+    pub fn ledger(&self) -> &VecDeque<Entry> {
+        &self.ledger
+    }
+
+    pub fn minted(&self) -> Money {
+        self.minted
+    }
+
+    pub fn burned(&self) -> Money {
+        self.burned
+    }
+
+    /// Sum of all balances
+    pub fn total(&self) -> Money {
+        self.balances.values().sum()
+    }
+
+    /// Checks that money was only created or destroyed by minting and burning
+    pub fn is_conserved(&self) -> bool {
+        self.burned <= self.minted && self.total() == self.minted - self.burned
+    }
+
+    /// Checks that the money is conserved and the accounts belong to existing participants
+    pub fn validate(
+        &self,
+        nr_ants: usize,
+        nr_factories: usize,
+        nr_houses: usize,
+    ) -> Result<(), wire::DecodeError> {
+        if !self.is_conserved() {
+            return Err(wire::DecodeError::Inconsistent("money supply"));
+        }
+        let exists = |account: &Account| match *account {
+            Account::Ant(id) => id < nr_ants,
+            Account::Factory(id) => id < nr_factories,
+            Account::Household(id) => id < nr_houses,
+            Account::Treasury => true,
+        };
+        if !self.balances.keys().all(exists) {
+            return Err(wire::DecodeError::Inconsistent("owners of the accounts"));
+        }
+
+        Ok(())
+    }
+
+    /// Creates new money
+    pub fn mint(&mut self, tick: u64, account: Account, amount: Money) {
+        *self.balances.entry(account).or_insert(0) += amount;
+        self.minted += amount;
+
+        self.record(Entry {
+            tick,
+            kind: EntryKind::Mint,
+            from: None,
+            to: Some(account),
+            amount,
+        });
+    }
+
+    /// Destroys the whole balance of the account, returns the burned amount
+    pub fn burn(&mut self, tick: u64, account: Account) -> Money {
+        let amount = self.balances.remove(&account).unwrap_or(0);
+        self.burned += amount;
+
+        self.record(Entry {
+            tick,
+            kind: EntryKind::Burn,
+            from: Some(account),
+            to: None,
+            amount,
+        });
+
+        amount
+    }
+
+    /// Moves money between two accounts, fails without changes if the balance is too low
+    pub fn transfer(
+        &mut self,
+        tick: u64,
+        kind: EntryKind,
+        from: Account,
+        to: Account,
+        amount: Money,
+    ) -> Result<(), InsufficientFunds> {
+        let balance = self.balance(from);
+        if balance < amount {
+            return Err(InsufficientFunds {
+                account: from,
+                balance,
+                amount,
+            });
+        }
+
+        self.balances.insert(from, balance - amount);
+        *self.balances.entry(to).or_insert(0) += amount;
+
+        self.record(Entry {
+            tick,
+            kind,
+            from: Some(from),
+            to: Some(to),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    fn record(&mut self, entry: Entry) {
+        if entry.amount == 0 {
+            return;
+        }
+
+        if self.ledger.len() >= LEDGER_SIZE {
+            self.ledger.pop_front();
+        }
+        self.ledger.push_back(entry);
+    }
+}
+
+impl Encode for Accounts {
+    fn encode(&self, writer: &mut wire::Writer) {
+        writer.write_usize(self.balances.len());
+        for (account, balance) in &self.balances {
+            account.encode(writer);
+            writer.write_u64(*balance);
+        }
+        self.ledger.encode(writer);
+        writer.write_u64(self.minted);
+        writer.write_u64(self.burned);
+    }
+}
+
+impl Decode for Accounts {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        let balances: Vec<(Account, Money)> = Vec::decode(reader)?;
+
+        Ok(Self {
+            balances: balances.into_iter().collect(),
+            ledger: VecDeque::decode(reader)?,
+            minted: reader.read_u64()?,
+            burned: reader.read_u64()?,
+        })
+    }
+}
+
+#[test]
+fn test_money_is_conserved() {
+    let mut accounts = Accounts::new();
+    accounts.mint(0, Account::Factory(0), 100);
+    accounts.mint(0, Account::Ant(0), 10);
+
+    let res = accounts.transfer(1, EntryKind::Wage, Account::Factory(0), Account::Ant(0), 30);
+    assert!(res.is_ok());
+    assert_eq!(accounts.balance(Account::Factory(0)), 70);
+    assert_eq!(accounts.balance(Account::Ant(0)), 40);
+
+    // a failed transfer changes nothing
+    let res = accounts.transfer(2, EntryKind::Trade, Account::Ant(0), Account::Ant(1), 50);
+    assert!(res.is_err());
+    assert_eq!(accounts.balance(Account::Ant(0)), 40);
+    assert_eq!(accounts.balance(Account::Ant(1)), 0);
+
+    assert_eq!(accounts.burn(3, Account::Ant(0)), 40);
+    assert_eq!(accounts.total(), 70);
+    assert!(accounts.is_conserved());
+}
+
+#[test]
+fn test_inconsistent_accounts_are_refused() {
+    let mut accounts = Accounts::new();
+    accounts.mint(0, Account::Household(1), 10);
+    assert!(accounts.validate(0, 0, 2).is_ok());
+    assert!(matches!(
+        accounts.validate(0, 0, 1),
+        Err(wire::DecodeError::Inconsistent(_))
+    ));
+
+    accounts.burned = accounts.minted + 1;
+    assert!(matches!(
+        accounts.validate(0, 0, 2),
+        Err(wire::DecodeError::Inconsistent(_))
+    ));
+}
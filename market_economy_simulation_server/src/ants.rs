@@ -1,12 +1,34 @@
 //! Manages all ants instances
 //!
+//! Changes of the ants are tracked, so only the changed fields are sent to the client.
+//! All ants are sent periodically as a keyframe, so a client can not drift apart.
+//! The alive ants are kept in a spatial grid, to find the ants around a position.
+//! The ids of dead ants are recycled for the next births, more ants are added when none is free.
 
-use std::sync::mpsc;
+use crate::{
+    behaviour::Task,
+    game_logic::game_logic_interface::{GameLogicMessageLight, KEYFRAME_INTERVAL},
+    inventory::Inventory,
+    outbox::Outbox,
+    spatial_grid::SpatialGrid,
+    wire::{self, Decode, Encode},
+};
 
-use crate::game_logic::game_logic_interface::GameLogicMessageLight;
+pub const BATCH_SIZE: usize = 1024; // max nr ants in one update message
+pub const MAX_NR_ANTS: usize = 1 << 24; // the ids have to fit into the entity index of the client
+const GRID_CELL_SIZE: f32 = 8.0;
 
 pub struct Ants {
     ants: Vec<Ant>,
+    is_alive: Vec<bool>,         // indexed by the ant id
+    inventories: Vec<Inventory>, // indexed by the ant id
+    grid: SpatialGrid,           // positions of the alive ants
+    free_ids: Vec<usize>,        // ids of the dead ants, the last one is reused first
+
+    // changes since the last update, indexed by the ant id
+    moved: Vec<bool>,
+    recoloured: Vec<bool>,
+    retasked: Vec<bool>,
 }
 
 impl Ants {
@@ -15,27 +37,10 @@ impl Ants {
 
         let size: usize = max_nr_ants.isqrt();
 
-        let gradient = colorous::TURBO;
-
         let mut id = 0;
         for y in 0..size {
             for x in 0..size {
-                let color0 = gradient.eval_rational(x % 10, 10);
-                let color1 = gradient.eval_rational(y % 10, 10);
-
-                let color0: cgmath::Vector3<f32> = cgmath::Vector3::new(
-                    color0.r as f32 / 255.0,
-                    color0.g as f32 / 255.0,
-                    color0.b as f32 / 255.0,
-                );
-
-                let color1: cgmath::Vector3<f32> = cgmath::Vector3::new(
-                    color1.r as f32 / 255.0,
-                    color1.g as f32 / 255.0,
-                    color1.b as f32 / 255.0,
-                );
-
-                let color = color0 / 2.0 + color1 / 2.0;
+                let color = gradient_color(x) / 2.0 + gradient_color(y) / 2.0;
 
                 ants.push(Ant {
                     id,
@@ -45,6 +50,7 @@ impl Ants {
                         y: y as f32 * 10.0,
                     },
                     rot_z: 0.0,
+                    task: Task::Idle,
                     light_strength: 1.0,
                     // light_color: cgmath::Vector3::new(1.0, 1.0, 1.0),
                     light_color: color,
@@ -54,16 +60,207 @@ impl Ants {
             }
         }
 
-        Self { ants }
+        let is_alive = vec![true; ants.len()];
+        let inventories = vec![Inventory::new(); ants.len()];
+        let moved = vec![false; ants.len()];
+        let recoloured = vec![false; ants.len()];
+        let retasked = vec![false; ants.len()];
+        let grid = create_grid(&ants, &is_alive);
+
+        Self {
+            ants,
+            is_alive,
+            inventories,
+            grid,
+            free_ids: Vec::new(),
+            moved,
+            recoloured,
+            retasked,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ants.len()
+    }
+
+    /// Checks that the ants only carry known goods
+    pub fn validate(&self, nr_goods: usize) -> Result<(), wire::DecodeError> {
+        for inventory in &self.inventories {
+            inventory.validate(nr_goods)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ants.is_empty()
+    }
+
+    pub fn get(&self, id: usize) -> &Ant {
+        &self.ants[id]
+    }
+
+    pub fn is_alive(&self, id: usize) -> bool {
+        self.is_alive[id]
+    }
+
+    /// Gives birth to an ant with the id of a dead ant or a new one, returns its id
+    pub fn spawn(&mut self, pos: cgmath::Vector2<f32>) -> Option<usize> {
+        let id = match self.free_ids.pop() {
+            Some(id) => id,
+            None if self.ants.len() < MAX_NR_ANTS => self.add(),
+            None => return None,
+        };
+
+        self.ants[id].pos = pos;
+        self.ants[id].rot_z = 0.0;
+        self.ants[id].task = Task::Idle;
+        self.is_alive[id] = true;
+        self.inventories[id] = Inventory::new();
+        self.grid.insert(id, pos);
+
+        // the birth is sent with the whole ant
+        self.moved[id] = false;
+        self.recoloured[id] = false;
+        self.retasked[id] = false;
+
+        Some(id)
+    }
+
+    /// Adds a dead ant with a new id
+    fn add(&mut self) -> usize {
+        let id = self.ants.len();
+        self.ants.push(Ant {
+            id,
+            pos: cgmath::Vector2::new(0.0, 0.0),
+            rot_z: 0.0,
+            task: Task::Idle,
+            light_strength: 1.0,
+            light_color: gradient_color(id),
+        });
+        self.is_alive.push(false);
+        self.inventories.push(Inventory::new());
+        self.moved.push(false);
+        self.recoloured.push(false);
+        self.retasked.push(false);
+
+        id
+    }
+
+    pub fn kill(&mut self, id: usize) {
+        if self.is_alive[id] {
+            self.is_alive[id] = false;
+            self.grid.remove(id);
+            self.free_ids.push(id);
+        }
+    }
+
+    pub fn set_pos(&mut self, id: usize, pos: cgmath::Vector2<f32>, rot_z: f32) {
+        let ant = &mut self.ants[id];
+        if ant.pos != pos || ant.rot_z != rot_z {
+            ant.pos = pos;
+            ant.rot_z = rot_z;
+            self.moved[id] = true;
+
+            if self.is_alive[id] {
+                self.grid.insert(id, pos);
+            }
+        }
+    }
+
+    pub fn set_light(&mut self, id: usize, light_strength: f32, light_color: cgmath::Vector3<f32>) {
+        let ant = &mut self.ants[id];
+        if ant.light_strength != light_strength || ant.light_color != light_color {
+            ant.light_strength = light_strength;
+            ant.light_color = light_color;
+            self.recoloured[id] = true;
+        }
+    }
+
+    pub fn set_task(&mut self, id: usize, task: Task) {
+        let ant = &mut self.ants[id];
+        if ant.task != task {
+            ant.task = task;
+            self.retasked[id] = true;
+        }
+    }
+
+    /// Alive ants within the radius around the center, sorted by their id
+    pub fn in_radius(&self, center: cgmath::Vector2<f32>, radius: f32) -> Vec<usize> {
+        self.grid.in_radius(center, radius)
+    }
+
+    /// Calls the function for the alive ants within the radius around the center with their position
+    pub fn visit_radius(
+        &self,
+        center: cgmath::Vector2<f32>,
+        radius: f32,
+        f: impl FnMut(usize, cgmath::Vector2<f32>),
+    ) {
+        self.grid.visit_radius(center, radius, f);
+    }
+
+    /// Alive ants inside the rectangle between the corners, e.g. of a selection, sorted by their id
+    pub fn in_rect(&self, min: cgmath::Vector2<f32>, max: cgmath::Vector2<f32>) -> Vec<usize> {
+        self.grid.in_rect(min, max)
     }
 
-    pub fn update(&mut self, channel: &mpsc::Sender<GameLogicMessageLight>) {
-        // if self.requires_update {
-        for elem in &mut self.ants {
-            // elem.position.x += 0.02;
-            // elem.color.x = (elem.color.x + 0.001) % 1.0;
+    /// The k alive ants closest to the position, the closest first
+    pub fn k_nearest(&self, pos: cgmath::Vector2<f32>, k: usize) -> Vec<usize> {
+        self.grid.k_nearest(pos, k)
+    }
+
+    pub fn inventory(&self, id: usize) -> &Inventory {
+        &self.inventories[id]
+    }
+
+    pub fn inventory_mut(&mut self, id: usize) -> &mut Inventory {
+        &mut self.inventories[id]
+    }
+
+    /// Sends the changes of the alive ants, or all of them on a keyframe,
+    /// as one batch for every BATCH_SIZE ants.
+    /// A keyframe is sent for every chunk, also without alive ants, so the client drops the dead ones.
+    pub fn update(&mut self, tick: u64, channel: &mut Outbox<GameLogicMessageLight>) {
+        let is_keyframe = tick.is_multiple_of(KEYFRAME_INTERVAL);
+
+        for (chunk, ants) in self.ants.chunks(BATCH_SIZE).enumerate() {
+            let mut batch = AntBatch {
+                is_keyframe,
+                chunk,
+                ..AntBatch::default()
+            };
+
+            for elem in ants {
+                let id = elem.id;
+                if !self.is_alive[id] {
+                    continue;
+                }
+
+                // elem.position.x += 0.02;
+                // elem.color.x = (elem.color.x + 0.001) % 1.0;
+
+                if is_keyframe || self.moved[id] {
+                    batch.moved.push(id);
+                    batch.positions.push(elem.pos);
+                    batch.rotations.push(elem.rot_z);
+                }
+                if is_keyframe || self.recoloured[id] {
+                    batch.recoloured.push(id);
+                    batch.light_strengths.push(elem.light_strength);
+                    batch.light_colors.push(elem.light_color);
+                }
+                if is_keyframe || self.retasked[id] {
+                    batch.retasked.push(id);
+                    batch.tasks.push(elem.task);
+                }
+            }
+
+            if batch.is_empty() && !is_keyframe {
+                continue;
+            }
 
-            let res = channel.send(GameLogicMessageLight::UpdateAnt(elem.clone()));
+            let res = channel.send(GameLogicMessageLight::UpdateAnts(batch));
             match res {
                 Ok(_) => {}
                 Err(_err) => {
@@ -71,8 +268,135 @@ impl Ants {
                 }
             }
         }
+
+        self.moved.fill(false);
+        self.recoloured.fill(false);
+        self.retasked.fill(false);
+    }
+}
+
+/// Changes of up to BATCH_SIZE consecutive ants, the arrays are indexed in parallel to the ids
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AntBatch {
+    pub is_keyframe: bool, // contains all alive ants, the client activates them and drops the others
+    pub chunk: usize,      // index of the BATCH_SIZE ants the batch belongs to
+
+    pub moved: Vec<usize>, // ids in ascending order
+    pub positions: Vec<cgmath::Vector2<f32>>,
+    pub rotations: Vec<f32>,
+
+    pub recoloured: Vec<usize>, // ids in ascending order
+    pub light_strengths: Vec<f32>,
+    pub light_colors: Vec<cgmath::Vector3<f32>>,
+
+    pub retasked: Vec<usize>, // ids in ascending order
+    pub tasks: Vec<Task>,
+}
+
+impl AntBatch {
+    pub fn is_empty(&self) -> bool {
+        self.moved.is_empty() && self.recoloured.is_empty() && self.retasked.is_empty()
+    }
+
+    /// Adds the changes of a newer batch of the same ants, which win over the own ones.
+    /// A newer keyframe replaces the batch, the ants it does not list are gone.
+    pub fn merge(&mut self, newer: AntBatch) {
+        if newer.is_keyframe {
+            *self = newer;
+            return;
+        }
+
+        let moved = merge_sorted(
+            std::mem::take(&mut self.moved),
+            zip(&mut self.positions, &mut self.rotations),
+            newer.moved,
+            newer.positions.into_iter().zip(newer.rotations).collect(),
+        );
+        self.moved = moved.0;
+        (self.positions, self.rotations) = moved.1.into_iter().unzip();
+
+        let recoloured = merge_sorted(
+            std::mem::take(&mut self.recoloured),
+            zip(&mut self.light_strengths, &mut self.light_colors),
+            newer.recoloured,
+            newer
+                .light_strengths
+                .into_iter()
+                .zip(newer.light_colors)
+                .collect(),
+        );
+        self.recoloured = recoloured.0;
+        (self.light_strengths, self.light_colors) = recoloured.1.into_iter().unzip();
+
+        (self.retasked, self.tasks) = merge_sorted(
+            std::mem::take(&mut self.retasked),
+            std::mem::take(&mut self.tasks),
+            newer.retasked,
+            newer.tasks,
+        );
+    }
+}
+
+fn zip<A, B>(a: &mut Vec<A>, b: &mut Vec<B>) -> Vec<(A, B)> {
+    std::mem::take(a)
+        .into_iter()
+        .zip(std::mem::take(b))
+        .collect()
+}
+
+/// Merges two lists of values sorted by their ids, the newer value wins for the same id
+fn merge_sorted<T>(
+    ids: Vec<usize>,
+    values: Vec<T>,
+    newer_ids: Vec<usize>,
+    newer_values: Vec<T>,
+) -> (Vec<usize>, Vec<T>) {
+    let mut res_ids = Vec::with_capacity(ids.len() + newer_ids.len());
+    let mut res_values = Vec::with_capacity(ids.len() + newer_ids.len());
+
+    let mut old = ids.into_iter().zip(values).peekable();
+    let mut new = newer_ids.into_iter().zip(newer_values).peekable();
+    loop {
+        let next = match (old.peek(), new.peek()) {
+            (Some(a), Some(b)) if a.0 < b.0 => old.next(),
+            (Some(a), Some(b)) if a.0 == b.0 => {
+                old.next();
+                new.next()
+            }
+            (Some(_a), Some(_b)) => new.next(),
+            (Some(_a), None) => old.next(),
+            (None, Some(_b)) => new.next(),
+            (None, None) => break,
+        };
+
+        if let Some((id, value)) = next {
+            res_ids.push(id);
+            res_values.push(value);
+        }
+    }
+
+    (res_ids, res_values)
+}
+
+fn gradient_color(i: usize) -> cgmath::Vector3<f32> {
+    let color = colorous::TURBO.eval_rational(i % 10, 10);
+
+    cgmath::Vector3::new(
+        color.r as f32 / 255.0,
+        color.g as f32 / 255.0,
+        color.b as f32 / 255.0,
+    )
+}
+
+fn create_grid(ants: &[Ant], is_alive: &[bool]) -> SpatialGrid {
+    let mut grid = SpatialGrid::new(GRID_CELL_SIZE);
+    for (id, ant) in ants.iter().enumerate() {
+        if is_alive[id] {
+            grid.insert(id, ant.pos);
+        }
     }
-    // }
+
+    grid
 }
 
 #[derive(Clone)]
@@ -81,7 +405,135 @@ pub struct Ant {
 
     pub pos: cgmath::Vector2<f32>,
     pub rot_z: f32,
+    pub task: Task,
 
     pub light_strength: f32,
     pub light_color: cgmath::Vector3<f32>,
 }
+
+impl Encode for Ants {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.ants.encode(writer);
+        self.is_alive.encode(writer);
+        self.inventories.encode(writer);
+        self.free_ids.encode(writer);
+    }
+}
+
+impl Decode for Ants {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        let ants: Vec<Ant> = Vec::decode(reader)?;
+        let is_alive: Vec<bool> = Vec::decode(reader)?;
+        let inventories: Vec<Inventory> = Vec::decode(reader)?;
+        let free_ids: Vec<usize> = Vec::decode(reader)?;
+
+        let len = ants.len();
+        if len > MAX_NR_ANTS || is_alive.len() != len || inventories.len() != len {
+            return Err(wire::DecodeError::Inconsistent("number of ants"));
+        }
+        if ants.iter().enumerate().any(|(id, ant)| ant.id != id) {
+            return Err(wire::DecodeError::Inconsistent("ant ids"));
+        }
+        if free_ids.iter().any(|id| *id >= len || is_alive[*id]) {
+            return Err(wire::DecodeError::Inconsistent("free ant ids"));
+        }
+        let grid = create_grid(&ants, &is_alive);
+
+        // the client receives the loaded ants as births
+        Ok(Self {
+            ants,
+            is_alive,
+            inventories,
+            grid,
+            free_ids,
+            moved: vec![false; len],
+            recoloured: vec![false; len],
+            retasked: vec![false; len],
+        })
+    }
+}
+
+#[test]
+fn test_only_changes_are_sent_between_keyframes() {
+    let (tx, rx) = std::sync::mpsc::sync_channel(16);
+    let mut channel = Outbox::new(tx, 16);
+    let mut ants = Ants::new(4);
+
+    let mut updates = |ants: &mut Ants, tick: u64| -> Vec<AntBatch> {
+        ants.update(tick, &mut channel);
+        rx.try_iter()
+            .map(|msg| match msg {
+                GameLogicMessageLight::UpdateAnts(batch) => batch,
+                _ => panic!("unexpected message"),
+            })
+            .collect()
+    };
+
+    let batches = updates(&mut ants, 0);
+    assert_eq!(batches.len(), 1);
+    assert!(batches[0].is_keyframe);
+    assert_eq!(batches[0].moved, vec![0, 1, 2, 3]);
+    assert_eq!(batches[0].recoloured, vec![0, 1, 2, 3]);
+
+    ants.set_pos(2, cgmath::Vector2::new(1.0, 2.0), 0.5);
+    ants.set_pos(3, ants.get(3).pos, 0.0); // unchanged
+    let batches = updates(&mut ants, 1);
+    assert_eq!(batches.len(), 1);
+    assert!(!batches[0].is_keyframe);
+    assert_eq!(batches[0].moved, vec![2]);
+    assert!(batches[0].recoloured.is_empty());
+
+    assert!(updates(&mut ants, 2).is_empty());
+    assert_eq!(updates(&mut ants, KEYFRAME_INTERVAL).len(), 1);
+
+    // the keyframe of a chunk without alive ants tells the client to drop them
+    for id in 0..4 {
+        ants.kill(id);
+    }
+    let batches = updates(&mut ants, 2 * KEYFRAME_INTERVAL);
+    assert_eq!(batches.len(), 1);
+    assert!(batches[0].is_keyframe);
+    assert!(batches[0].is_empty());
+}
+
+#[test]
+fn test_merged_batch_keeps_the_newest_changes() {
+    let position = |x: f32| cgmath::Vector2::new(x, 0.0);
+    let mut batch = AntBatch {
+        moved: vec![1, 3],
+        positions: vec![position(1.0), position(3.0)],
+        rotations: vec![0.0, 0.0],
+        ..AntBatch::default()
+    };
+
+    batch.merge(AntBatch {
+        moved: vec![2, 3],
+        positions: vec![position(2.0), position(4.0)],
+        rotations: vec![0.5, 0.5],
+        ..AntBatch::default()
+    });
+
+    assert_eq!(batch.moved, vec![1, 2, 3]);
+    assert_eq!(
+        batch.positions,
+        vec![position(1.0), position(2.0), position(4.0)]
+    );
+    assert_eq!(batch.rotations, vec![0.0, 0.5, 0.5]);
+    assert!(batch.recoloured.is_empty());
+}
+
+#[test]
+fn test_births_recycle_the_ids_of_dead_ants() {
+    let mut ants = Ants::new(4);
+    let pos = cgmath::Vector2::new(5.0, 5.0);
+
+    ants.kill(1);
+    ants.kill(2);
+    assert_eq!(ants.spawn(pos), Some(2));
+    assert_eq!(ants.spawn(pos), Some(1));
+    assert_eq!(ants.spawn(pos), Some(4));
+    assert_eq!(ants.len(), 5);
+
+    assert!(ants.is_alive(4));
+    assert_eq!(ants.in_radius(pos, 0.1), vec![1, 2, 4]);
+}
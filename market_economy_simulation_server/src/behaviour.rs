@@ -0,0 +1,342 @@
+//! Tasks of the ants, driven by their needs and jobs
+//!
+//! Tired ants walk home and rest there, or rest where they are without a home.
+//! Employed ants forage on the way to the field of their factory, gather resources there,
+//! carry them to the factory and deliver them, then start over. Unemployed ants are idle.
+//! The gathered goods are kept in the inventory of the ant until they are delivered
+//! into the inventory of the factory, which uses them as inputs.
+//! Ants changing or losing their job drop the goods gathered for their previous employer.
+//! The task of an ant decides where it walks to.
+
+use cgmath::InnerSpace;
+
+use crate::{
+    ants::Ants,
+    factories::Factories,
+    goods::GoodsCatalogue,
+    houses::Houses,
+    labour::LabourMarket,
+    needs::Needs,
+    recipes::Recipes,
+    wire::{self, Decode, Encode},
+};
+
+const GATHER_DURATION: f32 = 2.0; // seconds
+const DELIVER_DURATION: f32 = 1.0; // seconds
+const FIELD_OFFSET: cgmath::Vector2<f32> = cgmath::Vector2::new(-8.0, 0.0); // from the factory
+const ARRIVAL_DISTANCE: f32 = 2.0; // ants closer to their destination have arrived
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Task {
+    #[default]
+    Idle,
+    MoveTo,  // walking home
+    Gather,  // at the field of the factory
+    Carry,   // walking from the field to the factory
+    Deliver, // at the factory
+    Rest,
+    Forage, // walking to the field, following the pheromone trails
+}
+
+#[derive(Clone, Debug, Default)]
+struct Behaviour {
+    task: Task,
+    time: f32,               // seconds since the task started
+    employer: Option<usize>, // the factory the carried goods were gathered for
+}
+
+impl Behaviour {
+    /// Advances the task by the elapsed simulation time, returns it with the position to walk to
+    fn advance(
+        &mut self,
+        dt: f32,
+        pos: cgmath::Vector2<f32>,
+        is_resting: bool,
+        home: Option<cgmath::Vector2<f32>>,
+        factory: Option<cgmath::Vector2<f32>>,
+    ) -> (Task, Option<cgmath::Vector2<f32>>) {
+        self.time += dt;
+        let (task, goal) = self.next(pos, is_resting, home, factory);
+        if task != self.task {
+            self.task = task;
+            self.time = 0.0;
+        }
+
+        (task, goal)
+    }
+
+    /// The task to do now and the position to walk to
+    fn next(
+        &self,
+        pos: cgmath::Vector2<f32>,
+        is_resting: bool,
+        home: Option<cgmath::Vector2<f32>>,
+        factory: Option<cgmath::Vector2<f32>>,
+    ) -> (Task, Option<cgmath::Vector2<f32>>) {
+        let has_arrived =
+            |destination: cgmath::Vector2<f32>| (destination - pos).magnitude() < ARRIVAL_DISTANCE;
+
+        if is_resting {
+            return match home {
+                Some(home) if !has_arrived(home) => (Task::MoveTo, Some(home)),
+                _ => (Task::Rest, None),
+            };
+        }
+
+        let Some(factory) = factory else {
+            return (Task::Idle, None);
+        };
+        let field = factory + FIELD_OFFSET;
+
+        match self.task {
+            Task::Gather if self.time < GATHER_DURATION => (Task::Gather, None),
+            Task::Gather => (Task::Carry, Some(factory)),
+            Task::Carry if has_arrived(factory) => (Task::Deliver, None),
+            Task::Carry => (Task::Carry, Some(factory)),
+            Task::Deliver if self.time < DELIVER_DURATION => (Task::Deliver, None),
+            _ if has_arrived(field) => (Task::Gather, None),
+            _ => (Task::Forage, Some(field)),
+        }
+    }
+}
+
+pub struct Behaviours {
+    behaviours: Vec<Behaviour>, // indexed by the ant id
+}
+
+impl Behaviours {
+    pub fn new(nr_ants: usize) -> Self {
+        Self {
+            behaviours: vec![Behaviour::default(); nr_ants],
+        }
+    }
+
+    /// Resets the task of a newborn ant
+    pub fn born(&mut self, id: usize) {
+        if id >= self.behaviours.len() {
+            self.behaviours.resize(id + 1, Behaviour::default());
+        }
+        self.behaviours[id] = Behaviour::default();
+    }
+
+    /// Checks that there is a behaviour for every ant and that their employers exist
+    pub fn validate(&self, nr_ants: usize, nr_factories: usize) -> Result<(), wire::DecodeError> {
+        if self.behaviours.len() != nr_ants {
+            return Err(wire::DecodeError::Inconsistent("number of behaviours"));
+        }
+        let is_employer =
+            |behaviour: &Behaviour| behaviour.employer.is_none_or(|id| id < nr_factories);
+        if !self.behaviours.iter().all(is_employer) {
+            return Err(wire::DecodeError::Inconsistent(
+                "employers of the behaviours",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Advances the tasks of the alive ants by the elapsed simulation time and moves the
+    /// gathered goods, returns the position every ant walks to, indexed by the ant id
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        tick: u64,
+        dt: f32,
+        ants: &mut Ants,
+        needs: &Needs,
+        labour_market: &LabourMarket,
+        houses: &Houses,
+        factories: &mut Factories,
+        catalogue: &GoodsCatalogue,
+        recipes: &Recipes,
+    ) -> Vec<Option<cgmath::Vector2<f32>>> {
+        let mut goals = Vec::with_capacity(self.behaviours.len());
+        for (id, behaviour) in self.behaviours.iter_mut().enumerate() {
+            if !ants.is_alive(id) {
+                goals.push(None);
+                continue;
+            }
+
+            let live_states = needs.get(id);
+            let home = live_states.home.map(|home| houses.get(home).pos);
+            let employer = labour_market.employer(id);
+            let factory = employer.map(|factory| factories.get(factory).pos);
+
+            if behaviour.employer != employer {
+                if let Some(previous) = behaviour.employer {
+                    let recipe = recipes.get(factories.get(previous).recipe);
+                    for (good, _quantity) in &recipe.gathered {
+                        let quantity = ants.inventory(id).quantity(*good);
+                        ants.inventory_mut(id).take(*good, quantity);
+                    }
+                }
+                behaviour.employer = employer;
+            }
+
+            let previous = behaviour.task;
+            let (task, goal) =
+                behaviour.advance(dt, ants.get(id).pos, live_states.is_resting, home, factory);
+            ants.set_task(id, task);
+            goals.push(goal);
+
+            let Some(employer) = employer else {
+                continue;
+            };
+            let factory = factories.get_mut(employer);
+            let recipe = recipes.get(factory.recipe);
+            match (previous, task) {
+                (Task::Gather, Task::Carry) => {
+                    for (good, quantity) in &recipe.gathered {
+                        ants.inventory_mut(id)
+                            .add(catalogue, *good, *quantity, tick);
+                    }
+                }
+                (Task::Deliver, task) if task != Task::Deliver => {
+                    for (good, _quantity) in &recipe.gathered {
+                        let quantity = ants.inventory(id).quantity(*good);
+                        ants.inventory_mut(id)
+                            .transfer(&mut factory.inventory, *good, quantity);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        goals
+    }
+}
+
+impl Encode for Behaviour {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.task.encode(writer);
+        writer.write_f32(self.time);
+        self.employer.encode(writer);
+    }
+}
+
+impl Decode for Behaviour {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            task: Task::decode(reader)?,
+            time: reader.read_f32()?,
+            employer: Option::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Behaviours {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.behaviours.encode(writer);
+    }
+}
+
+impl Decode for Behaviours {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            behaviours: Vec::decode(reader)?,
+        })
+    }
+}
+
+#[test]
+fn test_workers_gather_carry_and_deliver() {
+    let factory = cgmath::Vector2::new(20.0, 0.0);
+    let field = factory + FIELD_OFFSET;
+
+    let mut behaviour = Behaviour::default();
+    let step = |behaviour: &mut Behaviour, pos: cgmath::Vector2<f32>, dt: f32| {
+        behaviour.advance(dt, pos, false, None, Some(factory))
+    };
+
+    assert_eq!(
+        step(&mut behaviour, cgmath::Vector2::new(0.0, 0.0), 0.1),
+        (Task::Forage, Some(field))
+    );
+    assert_eq!(step(&mut behaviour, field, 0.1), (Task::Gather, None));
+    assert_eq!(step(&mut behaviour, field, 1.0), (Task::Gather, None));
+    assert_eq!(
+        step(&mut behaviour, field, 1.0),
+        (Task::Carry, Some(factory))
+    );
+    assert_eq!(step(&mut behaviour, factory, 0.1), (Task::Deliver, None));
+    assert_eq!(
+        step(&mut behaviour, factory, 1.0),
+        (Task::Forage, Some(field))
+    );
+}
+
+#[test]
+fn test_tired_ants_rest_at_home() {
+    let home = cgmath::Vector2::new(0.0, -20.0);
+    let factory = Some(cgmath::Vector2::new(20.0, 0.0));
+    let behaviour = Behaviour {
+        task: Task::Gather,
+        time: 0.0,
+        employer: None,
+    };
+
+    let pos = cgmath::Vector2::new(12.0, 0.0);
+    assert_eq!(
+        behaviour.next(pos, true, Some(home), factory),
+        (Task::MoveTo, Some(home))
+    );
+    assert_eq!(
+        behaviour.next(home, true, Some(home), factory),
+        (Task::Rest, None)
+    );
+    assert_eq!(behaviour.next(pos, true, None, factory), (Task::Rest, None));
+    assert_eq!(
+        behaviour.next(pos, false, Some(home), None),
+        (Task::Idle, None)
+    );
+}
+
+#[test]
+fn test_ants_losing_their_job_drop_the_gathered_goods() {
+    use crate::{
+        accounts::{Account, Accounts},
+        recipes::DEFAULT_RECIPES,
+    };
+
+    let catalogue = GoodsCatalogue::default();
+    let recipes = Recipes::new(&catalogue, DEFAULT_RECIPES);
+    let mut factories = Factories::create_default(&catalogue, &recipes);
+    let mut ants = Ants::new(1);
+    let houses = Houses::create_default(1);
+    let needs = Needs::new(&catalogue, &[None], &mut fastrand::Rng::with_seed(0));
+    let mut accounts = Accounts::new();
+    for id in 0..factories.len() {
+        accounts.mint(0, Account::Factory(id), 1000);
+    }
+
+    let mut labour_market = LabourMarket::new(&factories, &recipes, ants.len());
+    let mut rng = fastrand::Rng::with_seed(0);
+    labour_market.update(1, &mut factories, &ants, &needs, &mut accounts, &mut rng);
+    let employer = labour_market.employer(0).unwrap();
+
+    let mut behaviours = Behaviours::new(ants.len());
+    let mut update = |ants: &mut Ants, labour_market: &LabourMarket, factories: &mut Factories| {
+        behaviours.update(
+            1,
+            0.1,
+            ants,
+            &needs,
+            labour_market,
+            &houses,
+            factories,
+            &catalogue,
+            &recipes,
+        );
+    };
+    update(&mut ants, &labour_market, &mut factories);
+
+    let (gathered, _quantity) = recipes.get(factories.get(employer).recipe).gathered[0];
+    let food = catalogue.find("food").unwrap();
+    ants.inventory_mut(0).add(&catalogue, gathered, 4, 1);
+    ants.inventory_mut(0).add(&catalogue, food, 1, 1);
+
+    labour_market.quit(0);
+    update(&mut ants, &labour_market, &mut factories);
+    assert_eq!(ants.inventory(0).quantity(gathered), 0);
+    assert_eq!(ants.inventory(0).quantity(food), 1);
+}
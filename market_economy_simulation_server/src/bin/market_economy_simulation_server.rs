@@ -0,0 +1,305 @@
+//! Runs the simulation without a renderer and writes summary statistics
+//!
+//! Usage: market_economy_simulation_server [--ticks N] [--ants N] [--seed N] [--output FILE]
+//!
+//! With `--listen ADDR` the simulation runs in real time instead and serves
+//! clients connecting over TCP, until the process is stopped.
+//! With `--replay FILE` a recorded session is reproduced and summarized.
+//!
+//! The trades are counted from the fills sent to the client, which are dropped if
+//! the client falls behind. The run fails if any fill was dropped.
+
+use std::io::Write;
+
+use market_economy_simulation_server::{
+    GameLogicSingleThreaded, clock,
+    factories::FactoryState,
+    game_logic::{
+        GameLogicSettings,
+        game_logic_interface::{GameLogicInterface, GameLogicMessageMedium},
+    },
+    goods::GoodsCatalogue,
+    market::Price,
+    needs::CauseOfDeath,
+    network::GameLogicNetworkServer,
+    recipes::{DEFAULT_RECIPES, Recipes},
+    recording::Recording,
+};
+
+const NR_TERRAIN_WORKERS: usize = 2; // threads generating the terrain requested by the clients
+
+struct Arguments {
+    ticks: u64,
+    max_nr_ants: usize,
+    seed: u64,
+    output: Option<String>,
+    listen: Option<String>,
+    replay: Option<String>,
+}
+
+impl Arguments {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut arguments = Self {
+            ticks: 1000,
+            max_nr_ants: 100,
+            seed: 1,
+            output: None,
+            listen: None,
+            replay: None,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+
+            match arg.as_str() {
+                "--ticks" => arguments.ticks = parse_number(&value()?)?,
+                "--ants" => arguments.max_nr_ants = parse_number(&value()?)?,
+                "--seed" => arguments.seed = parse_number(&value()?)?,
+                "--output" => arguments.output = Some(value()?),
+                "--listen" => arguments.listen = Some(value()?),
+                "--replay" => arguments.replay = Some(value()?),
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+
+        Ok(arguments)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_err| format!("invalid number {}", value))
+}
+
+#[derive(Clone, Default)]
+struct GoodStatistics {
+    nr_trades: u64,
+    volume: u64,
+    turnover: u64,
+    last_price: Option<Price>,
+}
+
+#[derive(Default)]
+struct Statistics {
+    goods: Vec<GoodStatistics>,           // indexed by the good id
+    factories: Vec<Option<FactoryState>>, // indexed by the factory id
+    births: u64,
+    deaths_starvation: u64,
+    deaths_exhaustion: u64,
+    deaths_age: u64,
+    dropped_fills: u64, // the trades are incomplete if not zero
+}
+
+impl Statistics {
+    fn add(&mut self, msg: GameLogicMessageMedium) {
+        match msg {
+            GameLogicMessageMedium::UpdateWatchPoints(_watch_viewer_data) => {}
+            GameLogicMessageMedium::UpdateClock(_clock_state) => {}
+            GameLogicMessageMedium::SnapshotLoaded(_settings) => {}
+            GameLogicMessageMedium::Synced { .. } => {}
+            GameLogicMessageMedium::UpdateChannelStatistics(_channel_statistics) => {}
+            GameLogicMessageMedium::MarketFill(trade) => {
+                if trade.good >= self.goods.len() {
+                    self.goods.resize(trade.good + 1, GoodStatistics::default());
+                }
+
+                let good = &mut self.goods[trade.good];
+                good.nr_trades += 1;
+                good.volume += trade.quantity as u64;
+                good.turnover += trade.quantity as u64 * trade.price;
+                good.last_price = Some(trade.price);
+            }
+            GameLogicMessageMedium::UpdateFactory(factory_state) => {
+                let id = factory_state.id;
+                if id >= self.factories.len() {
+                    self.factories.resize(id + 1, None);
+                }
+                self.factories[id] = Some(factory_state);
+            }
+            GameLogicMessageMedium::AntBorn(_ant) => self.births += 1,
+            GameLogicMessageMedium::AntDied { id: _, cause } => match cause {
+                CauseOfDeath::Starvation => self.deaths_starvation += 1,
+                CauseOfDeath::Exhaustion => self.deaths_exhaustion += 1,
+                CauseOfDeath::Age => self.deaths_age += 1,
+            },
+        }
+    }
+
+    fn write(
+        &self,
+        out: &mut dyn Write,
+        arguments: &Arguments,
+        catalogue: &GoodsCatalogue,
+        recipes: &Recipes,
+    ) -> std::io::Result<()> {
+        writeln!(out, "ticks {}", arguments.ticks)?;
+        writeln!(out, "ants {}", arguments.max_nr_ants)?;
+        writeln!(out, "seed {}", arguments.seed)?;
+        writeln!(out, "births {}", self.births)?;
+        writeln!(out, "deaths_starvation {}", self.deaths_starvation)?;
+        writeln!(out, "deaths_exhaustion {}", self.deaths_exhaustion)?;
+        writeln!(out, "deaths_age {}", self.deaths_age)?;
+        writeln!(out, "dropped_fills {}", self.dropped_fills)?;
+
+        for good in catalogue.iter() {
+            let stats = self.goods.get(good.id).cloned().unwrap_or_default();
+            let mean_price = stats.turnover.checked_div(stats.volume).unwrap_or(0);
+
+            writeln!(
+                out,
+                "good {} trades {} volume {} mean_price {} last_price {}",
+                good.name,
+                stats.nr_trades,
+                stats.volume,
+                mean_price,
+                stats.last_price.unwrap_or(0)
+            )?;
+        }
+
+        for factory in self.factories.iter().flatten() {
+            writeln!(
+                out,
+                "factory {} recipe \"{}\" workers {} wage {} produced_agents {}",
+                factory.id,
+                recipes.get(factory.recipe).name,
+                factory.workers,
+                factory.wage,
+                factory.produced_agents
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let mut arguments = match Arguments::parse(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!(
+                "usage: market_economy_simulation_server [--ticks N] [--ants N] [--seed N] [--output FILE] [--listen ADDR] [--replay FILE]"
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let mut statistics = Statistics::default();
+
+    let (mut game_logic, nr_ticks) = match &arguments.replay {
+        Some(path) => {
+            let recording = match Recording::load(path) {
+                Ok(recording) => recording,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            };
+            arguments.ticks = recording.end_tick;
+            arguments.max_nr_ants = recording.settings.max_nr_ants;
+            arguments.seed = recording.settings.seed;
+
+            let mut game_logic = GameLogicSingleThreaded::replay(recording);
+            drain(&game_logic, &mut statistics);
+            while game_logic.is_replaying() {
+                game_logic.step();
+                drain(&game_logic, &mut statistics);
+            }
+
+            (game_logic, 0)
+        }
+        None => {
+            let settings = GameLogicSettings {
+                enable_multithreading: false,
+                max_nr_ants: arguments.max_nr_ants,
+                seed: arguments.seed,
+                nr_terrain_workers: NR_TERRAIN_WORKERS,
+            };
+
+            (GameLogicSingleThreaded::new(settings), arguments.ticks)
+        }
+    };
+
+    if let Some(addr) = &arguments.listen {
+        let res = serve(&mut game_logic, addr);
+        match res {
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    for _i in 0..nr_ticks {
+        if !game_logic.is_running() {
+            break;
+        }
+        game_logic.step();
+        drain(&game_logic, &mut statistics);
+    }
+
+    statistics.dropped_fills = game_logic.channel_statistics().medium.dropped;
+
+    let catalogue = GoodsCatalogue::default();
+    let recipes = Recipes::new(&catalogue, DEFAULT_RECIPES);
+
+    let res = match &arguments.output {
+        Some(path) => std::fs::File::create(path)
+            .and_then(|mut file| statistics.write(&mut file, &arguments, &catalogue, &recipes)),
+        None => statistics.write(&mut std::io::stdout(), &arguments, &catalogue, &recipes),
+    };
+    match res {
+        Ok(_) => {}
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+
+    if statistics.dropped_fills > 0 {
+        eprintln!(
+            "{} market fills were dropped, the trades are undercounted",
+            statistics.dropped_fills
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Empties all channels, so the messages do not pile up
+fn drain(game_logic: &GameLogicSingleThreaded, statistics: &mut Statistics) {
+    for _msg in game_logic.get_heavy_messages().try_iter() {}
+    for msg in game_logic.get_medium_messages().try_iter() {
+        statistics.add(msg);
+    }
+    for _msg in game_logic.get_light_messages().try_iter() {}
+    for msg in game_logic.get_critical_messages().try_iter() {
+        log::error!("{}", msg);
+    }
+}
+
+/// Runs the simulation in real time and forwards its messages to the connected clients
+fn serve(game_logic: &mut GameLogicSingleThreaded, addr: &str) -> std::io::Result<()> {
+    let mut server = GameLogicNetworkServer::bind(addr, game_logic.send_messages().clone())?;
+    println!("listening on {}", server.local_addr()?);
+
+    let interval = std::time::Duration::from_secs_f64(clock::TICK_DURATION);
+    let mut last_time = instant::Instant::now();
+    while game_logic.is_running() {
+        let start_time = instant::Instant::now();
+        game_logic.update(start_time - last_time);
+        server.forward(game_logic);
+        last_time = start_time;
+
+        let time_passed = instant::Instant::now() - start_time;
+        if time_passed < interval {
+            std::thread::sleep(interval - time_passed);
+        }
+    }
+
+    Ok(())
+}
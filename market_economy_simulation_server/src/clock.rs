@@ -0,0 +1,161 @@
+//! Simulation clock with a fixed tick length
+//!
+//! The elapsed real time is scaled and accumulated, every full tick length
+//! of accumulated time advances the simulation by one tick.
+//! While paused, the simulation only advances by explicitly requested single steps.
+
+use crate::wire::{self, Decode, Encode};
+
+pub const TICK_DURATION: f64 = 0.016; // simulation time of one tick in seconds
+
+pub const MIN_TIME_SCALE: f64 = 0.25;
+pub const MAX_TIME_SCALE: f64 = 16.0;
+
+const MAX_TICKS_PER_UPDATE: u32 = 64; // limits the catch up after a stall
+
+/// State of the clock as seen by the client
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockState {
+    pub tick: u64,
+    pub time: f64, // simulation time in seconds
+    pub is_paused: bool,
+    pub time_scale: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Clock {
+    tick: u64,
+    is_paused: bool,
+    time_scale: f64,
+
+    accumulator: f64, // scaled real time not yet simulated in seconds
+    pending_steps: u32,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            tick: 0,
+            is_paused: false,
+            time_scale: 1.0,
+            accumulator: 0.0,
+            pending_steps: 0,
+        }
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Simulation time in seconds
+    pub fn time(&self) -> f64 {
+        self.tick as f64 * TICK_DURATION
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    pub fn state(&self) -> ClockState {
+        ClockState {
+            tick: self.tick,
+            time: self.time(),
+            is_paused: self.is_paused,
+            time_scale: self.time_scale,
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.is_paused = true;
+        self.accumulator = 0.0;
+    }
+
+    pub fn resume(&mut self) {
+        self.is_paused = false;
+    }
+
+    /// Sets the speed of the simulation relative to the real time
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+    }
+
+    /// Requests a single tick, also while paused
+    pub fn step(&mut self) {
+        self.pending_steps += 1;
+    }
+
+    /// Returns the number of ticks to simulate for the elapsed real time in seconds
+    pub fn advance(&mut self, real_dt: f64) -> u32 {
+        let mut nr_ticks = std::mem::take(&mut self.pending_steps);
+
+        if !self.is_paused {
+            self.accumulator += real_dt * self.time_scale;
+
+            let ticks = (self.accumulator / TICK_DURATION).floor();
+            self.accumulator -= ticks * TICK_DURATION;
+            nr_ticks += ticks as u32;
+        }
+
+        if nr_ticks > MAX_TICKS_PER_UPDATE {
+            nr_ticks = MAX_TICKS_PER_UPDATE;
+            self.accumulator = 0.0;
+        }
+
+        nr_ticks
+    }
+
+    /// Marks the current tick as simulated
+    pub fn next_tick(&mut self) {
+        self.tick += 1;
+    }
+}
+
+impl Encode for Clock {
+    fn encode(&self, writer: &mut wire::Writer) {
+        writer.write_u64(self.tick);
+        writer.write_bool(self.is_paused);
+        writer.write_f64(self.time_scale);
+        writer.write_f64(self.accumulator);
+        writer.write_u32(self.pending_steps);
+    }
+}
+
+impl Decode for Clock {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            tick: reader.read_u64()?,
+            is_paused: reader.read_bool()?,
+            time_scale: reader.read_f64()?,
+            accumulator: reader.read_f64()?,
+            pending_steps: reader.read_u32()?,
+        })
+    }
+}
+
+#[test]
+fn test_pause_scale_and_step() {
+    let mut clock = Clock::new();
+    assert_eq!(clock.advance(TICK_DURATION * 3.5), 3);
+    assert_eq!(clock.advance(TICK_DURATION), 1);
+
+    clock.set_time_scale(100.0);
+    assert_eq!(clock.time_scale(), MAX_TIME_SCALE);
+    assert_eq!(clock.advance(TICK_DURATION), 16);
+
+    clock.pause();
+    assert_eq!(clock.advance(1.0), 0);
+    clock.step();
+    clock.step();
+    assert_eq!(clock.advance(1.0), 2);
+    assert_eq!(clock.advance(1.0), 0);
+}
@@ -0,0 +1,409 @@
+//! Factories executing the production recipes
+//!
+//! Every tick a factory advances its production cycle, offers its outputs
+//! on the market and bids for the inputs of its next production cycles,
+//! except for the inputs its workers gather and deliver.
+//! The prices are adapted depending on whether the orders of the last tick were filled.
+//! The factories are kept in a spatial grid, to find the closest one to a position.
+
+use crate::{
+    accounts::{Account, Accounts},
+    game_logic::game_logic_interface::GameLogicMessageMedium,
+    goods::{GoodId, GoodsCatalogue},
+    inventory::Inventory,
+    market::{self, Price, Quotes, Side, Trader},
+    outbox::Outbox,
+    recipes::{Recipe, RecipeId, Recipes},
+    spatial_grid::SpatialGrid,
+    wire::{self, Decode, Encode},
+};
+
+const INITIAL_PRICE: Price = 100;
+const INPUT_STOCK_CYCLES: u32 = 2; // nr production cycles of inputs kept in stock
+const OUTPUT_STOCK_CYCLES: u32 = 8; // production pauses if that many cycles are unsold
+const GRID_CELL_SIZE: f32 = 32.0;
+
+pub const BASE_FACTORY_ID: usize = 0; // the factory controlled by the player
+
+/// State of a factory as seen by the client
+#[derive(Clone, Debug, PartialEq)]
+pub struct FactoryState {
+    pub id: usize,
+    pub pos: cgmath::Vector2<f32>,
+    pub recipe: RecipeId,
+    pub workers: u32,
+    pub wage: Price,
+
+    pub progress: f64, // progress of the current production cycle between 0.0 and 1.0
+    pub is_producing: bool,
+    pub produced_agents: u32,
+
+    pub stock: Vec<(GoodId, u32)>,
+}
+
+pub struct Factory {
+    pub id: usize,
+    pub pos: cgmath::Vector2<f32>,
+    pub recipe: RecipeId,
+    pub inventory: Inventory,
+    pub workers: u32, // nr employed ants currently working
+    pub wage: Price,  // paid per tick worked
+
+    progress: f64, // progress of the current production cycle between 0.0 and 1.0
+    is_producing: bool,
+    produced_agents: u32,
+
+    quotes: Quotes,
+
+    last_state: Option<FactoryState>, // last state sent to the client
+}
+
+impl Factory {
+    pub fn is_producing(&self) -> bool {
+        self.is_producing
+    }
+
+    pub fn progress(&self) -> f64 {
+        self.progress
+    }
+
+    pub fn produced_agents(&self) -> u32 {
+        self.produced_agents
+    }
+
+    pub fn state(&self) -> FactoryState {
+        FactoryState {
+            id: self.id,
+            pos: self.pos,
+            recipe: self.recipe,
+            workers: self.workers,
+            wage: self.wage,
+            progress: self.progress,
+            is_producing: self.is_producing,
+            produced_agents: self.produced_agents,
+            stock: self.inventory.iter().collect(),
+        }
+    }
+
+    /// Advances the production by the elapsed simulation time, returns the nr of emitted agents
+    fn produce(&mut self, recipe: &Recipe, catalogue: &GoodsCatalogue, tick: u64, dt: f64) -> u32 {
+        let is_staffed = self.workers >= recipe.required_workers;
+
+        if !self.is_producing && is_staffed && self.inventory.contains(&recipe.inputs) {
+            let is_sold = recipe.outputs.iter().all(|(good, quantity)| {
+                self.inventory.quantity(*good) < quantity * OUTPUT_STOCK_CYCLES
+            });
+
+            if is_sold {
+                for (good, quantity) in &recipe.inputs {
+                    self.inventory.take(*good, *quantity);
+                }
+                self.is_producing = true;
+                self.progress = 0.0;
+            }
+        }
+
+        // the production halts without enough workers
+        if self.is_producing && is_staffed {
+            self.progress = (self.progress + dt / recipe.duration).min(1.0);
+
+            if self.progress >= 1.0 {
+                for (good, quantity) in &recipe.outputs {
+                    self.inventory.add(catalogue, *good, *quantity, tick);
+                }
+                self.produced_agents += recipe.agents;
+                self.is_producing = false;
+                self.progress = 0.0;
+
+                return recipe.agents;
+            }
+        }
+
+        0
+    }
+
+    /// Offers the outputs and bids for the missing inputs as far as the balance allows
+    fn trade(&mut self, recipe: &Recipe, market: &mut market::Market, accounts: &Accounts) {
+        self.quotes.update_prices(market);
+
+        let trader = Trader::Factory(self.id);
+
+        for (good, _quantity) in &recipe.outputs {
+            let available = self.inventory.quantity(*good);
+            self.quotes
+                .post(market, trader, *good, Side::Ask, available);
+        }
+
+        let mut budget = accounts.balance(Account::Factory(self.id));
+        for (good, quantity) in recipe
+            .inputs
+            .iter()
+            .filter(|(good, _quantity)| !recipe.is_gathered(*good))
+        {
+            let required =
+                (quantity * INPUT_STOCK_CYCLES).saturating_sub(self.inventory.quantity(*good));
+            if required > 0 {
+                self.quotes.limit_price(*good, budget);
+            }
+
+            let price = self.quotes.price(*good);
+            let affordable = (budget / price).min(required as u64) as u32;
+            budget -= affordable as u64 * price;
+
+            self.quotes
+                .post(market, trader, *good, Side::Bid, affordable);
+        }
+    }
+}
+
+pub struct Factories {
+    factories: Vec<Factory>, // indexed by the factory id
+    grid: SpatialGrid,       // positions of the factories
+
+    emitted_agents: Vec<usize>, // factory ids of the agents emitted during the last update
+}
+
+impl Default for Factories {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Factories {
+    pub fn new() -> Self {
+        Self {
+            factories: Vec::new(),
+            grid: SpatialGrid::new(GRID_CELL_SIZE),
+            emitted_agents: Vec::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        catalogue: &GoodsCatalogue,
+        pos: cgmath::Vector2<f32>,
+        recipe: RecipeId,
+        workers: u32,
+    ) -> usize {
+        let id = self.factories.len();
+        self.factories.push(Factory {
+            id,
+            pos,
+            recipe,
+            inventory: Inventory::new(),
+            workers,
+            wage: 0,
+            progress: 0.0,
+            is_producing: false,
+            produced_agents: 0,
+            quotes: Quotes::new(catalogue.len(), INITIAL_PRICE),
+            last_state: None,
+        });
+        self.grid.insert(id, pos);
+
+        id
+    }
+
+    /// Creates the base factory and one factory for every other recipe
+    ///
+    /// The factories are staffed by the labour market
+    pub fn create_default(catalogue: &GoodsCatalogue, recipes: &Recipes) -> Self {
+        let mut factories = Self::new();
+
+        let base_recipe = recipes
+            .find("breed ant")
+            .expect("missing base factory recipe");
+        let base_factory =
+            factories.add(catalogue, cgmath::Vector2::new(10.0, 10.0), base_recipe, 0);
+        assert_eq!(base_factory, BASE_FACTORY_ID);
+
+        for (i, recipe) in recipes
+            .iter()
+            .filter(|elem| elem.id != base_recipe)
+            .enumerate()
+        {
+            let pos = cgmath::Vector2::new(-20.0, i as f32 * 20.0);
+            factories.add(catalogue, pos, recipe.id, 0);
+        }
+
+        factories
+    }
+
+    pub fn get(&self, id: usize) -> &Factory {
+        &self.factories[id]
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> &mut Factory {
+        &mut self.factories[id]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Factory> {
+        self.factories.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.factories.len()
+    }
+
+    /// The factory closest to the position for which the filter is true, e.g. the closest one producing a recipe
+    pub fn nearest_where(
+        &self,
+        pos: cgmath::Vector2<f32>,
+        filter: impl Fn(&Factory) -> bool,
+    ) -> Option<usize> {
+        self.grid
+            .nearest_where(pos, |id| filter(&self.factories[id]))
+    }
+
+    /// Checks that the factories produce known recipes
+    pub fn validate(&self, nr_recipes: usize, nr_goods: usize) -> Result<(), wire::DecodeError> {
+        if self
+            .factories
+            .iter()
+            .any(|factory| factory.recipe >= nr_recipes)
+        {
+            return Err(wire::DecodeError::Inconsistent("recipes of the factories"));
+        }
+        for factory in &self.factories {
+            factory.inventory.validate(nr_goods)?;
+            factory.quotes.validate(nr_goods)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.factories.is_empty()
+    }
+
+    /// Factory ids of the agents emitted during the last update, one entry per agent
+    pub fn emitted_agents(&self) -> &[usize] {
+        &self.emitted_agents
+    }
+
+    /// Advances the production of all factories by the elapsed simulation time and posts their orders
+    pub fn update(
+        &mut self,
+        tick: u64,
+        dt: f64,
+        catalogue: &GoodsCatalogue,
+        recipes: &Recipes,
+        market: &mut market::Market,
+        accounts: &Accounts,
+    ) {
+        self.emitted_agents.clear();
+
+        for factory in &mut self.factories {
+            let recipe = recipes.get(factory.recipe);
+
+            factory.inventory.spoil(tick);
+            let agents = factory.produce(recipe, catalogue, tick, dt);
+            factory.trade(recipe, market, accounts);
+
+            for _i in 0..agents {
+                self.emitted_agents.push(factory.id);
+            }
+        }
+    }
+
+    /// Sends the state of all factories which changed since the last call
+    pub fn send_updates(&mut self, channel: &mut Outbox<GameLogicMessageMedium>) {
+        for factory in &mut self.factories {
+            let state = factory.state();
+            if factory.last_state.as_ref() == Some(&state) {
+                continue;
+            }
+
+            let res = channel.send(GameLogicMessageMedium::UpdateFactory(state.clone()));
+            match res {
+                Ok(_) => {}
+                Err(_err) => {
+                    // println!("{}", err)
+                }
+            }
+
+            factory.last_state = Some(state);
+        }
+    }
+}
+
+impl Encode for Factory {
+    fn encode(&self, writer: &mut wire::Writer) {
+        writer.write_usize(self.id);
+        self.pos.encode(writer);
+        writer.write_usize(self.recipe);
+        self.inventory.encode(writer);
+        writer.write_u32(self.workers);
+        writer.write_u64(self.wage);
+        writer.write_f64(self.progress);
+        writer.write_bool(self.is_producing);
+        writer.write_u32(self.produced_agents);
+        self.quotes.encode(writer);
+    }
+}
+
+impl Decode for Factory {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            id: reader.read_usize()?,
+            pos: cgmath::Vector2::decode(reader)?,
+            recipe: reader.read_usize()?,
+            inventory: Inventory::decode(reader)?,
+            workers: reader.read_u32()?,
+            wage: reader.read_u64()?,
+            progress: reader.read_f64()?,
+            is_producing: reader.read_bool()?,
+            produced_agents: reader.read_u32()?,
+            quotes: Quotes::decode(reader)?,
+            last_state: None, // the state is sent again to the client
+        })
+    }
+}
+
+impl Encode for Factories {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.factories.encode(writer);
+    }
+}
+
+impl Decode for Factories {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        let factories: Vec<Factory> = Vec::decode(reader)?;
+        if factories
+            .iter()
+            .enumerate()
+            .any(|(id, factory)| factory.id != id)
+        {
+            return Err(wire::DecodeError::Inconsistent("factory ids"));
+        }
+
+        let mut grid = SpatialGrid::new(GRID_CELL_SIZE);
+        for factory in &factories {
+            grid.insert(factory.id, factory.pos);
+        }
+
+        Ok(Self {
+            factories,
+            grid,
+            emitted_agents: Vec::new(),
+        })
+    }
+}
+
+#[test]
+fn test_nearest_factory() {
+    let catalogue = GoodsCatalogue::default();
+    let recipes = Recipes::new(&catalogue, crate::recipes::DEFAULT_RECIPES);
+    let factories = Factories::create_default(&catalogue, &recipes);
+
+    let pos = cgmath::Vector2::new(12.0, 8.0);
+    assert_eq!(
+        factories.nearest_where(pos, |_factory| true),
+        Some(BASE_FACTORY_ID)
+    );
+
+    let bakery = recipes.find("bake food").unwrap();
+    let nearest = factories.nearest_where(pos, |factory| factory.recipe == bakery);
+    assert_eq!(factories.get(nearest.unwrap()).recipe, bakery);
+    assert_eq!(factories.nearest_where(pos, |_factory| false), None);
+}
@@ -1,75 +1,242 @@
-use std::sync::mpsc;
+use std::collections::VecDeque;
+use std::sync::{Arc, mpsc};
 
 use game_logic_interface::{
     GameLogicMessageCritical, GameLogicMessageHeavy, GameLogicMessageLight, GameLogicMessageMedium,
-    GameLogicMessageRequest,
+    GameLogicMessageRequest, KEYFRAME_INTERVAL, Request, RequestId,
 };
 use wgpu_renderer::performance_monitor::watch;
 
+use crate::accounts;
 use crate::ants;
+use crate::behaviour;
+use crate::clock::{self, TICK_DURATION};
+use crate::factories;
+use crate::goods;
 use crate::heightmap_generator;
+use crate::heightmap_workers::HeightMapWorkers;
+use crate::houses;
+use crate::inventory::Inventory;
+use crate::labour;
+use crate::market;
+use crate::movement;
+use crate::needs;
+use crate::outbox::{ChannelStatistics, Outbox};
+use crate::pheromones;
+use crate::random;
+use crate::recipes;
+use crate::recording;
+use crate::wire::{self, Decode, Encode};
 
 pub mod game_logic_interface;
 
+const INITIAL_ANT_MONEY: accounts::Money = 1_000;
+const INITIAL_FACTORY_MONEY: accounts::Money = 100_000;
+const WORLD_MARGIN: f32 = 16.0; // walkable border around the ants, houses and factories
+const PHEROMONE_INTERVAL: u64 = 16; // ticks between two updates of the pheromone field on the client
+
+const REQUEST_TIME_BUDGET: f64 = 0.25 * TICK_DURATION; // seconds per tick, at least one request is processed
+
+#[derive(Clone)]
 pub struct GameLogicSettings {
     // pub map_size: usize,
     pub enable_multithreading: bool,
     pub max_nr_ants: usize,
+    pub seed: u64,                 // feeds all random sources of the simulation
+    pub nr_terrain_workers: usize, // threads generating the terrain, 0 generates it in the game logic
 }
 
 pub struct GameLogic {
-    _settings: GameLogicSettings,
+    settings: GameLogicSettings,
 
-    channel_0_rx: mpsc::Receiver<GameLogicMessageRequest>,
-    channel_1_tx: mpsc::Sender<GameLogicMessageHeavy>,
-    channel_2_tx: mpsc::Sender<GameLogicMessageMedium>,
-    channel_3_tx: mpsc::Sender<GameLogicMessageLight>,
-    _channel_4_tx: mpsc::Sender<GameLogicMessageCritical>,
+    channel_0_rx: mpsc::Receiver<Request>,
+    pending_requests: VecDeque<Request>, // received, but not processed yet
+    channel_1_tx: Outbox<GameLogicMessageHeavy>,
+    channel_2_tx: Outbox<GameLogicMessageMedium>,
+    channel_3_tx: Outbox<GameLogicMessageLight>,
+    channel_4_tx: Outbox<GameLogicMessageCritical>,
 
-    heightmap_generator: heightmap_generator::HeightMapGenerator,
+    heightmap_generator: Arc<heightmap_generator::HeightMapGenerator>,
+    heightmap_workers: HeightMapWorkers,
     ants: ants::Ants,
+    houses: houses::Houses,
+    needs: needs::Needs,
+    behaviours: behaviour::Behaviours,
+    goods: goods::GoodsCatalogue,
+    recipes: recipes::Recipes,
+    factories: factories::Factories,
+    labour_market: labour::LabourMarket,
+    market: market::Market,
+    movement: movement::Movement,
+    pheromones: pheromones::Pheromones,
+    accounts: accounts::Accounts,
+    decisions: fastrand::Rng, // random stream of the choices of the ants
+    clock: clock::Clock,
+    recording: recording::Recording,
+    replay: Option<recording::Recording>, // the recorded session being reproduced
+    is_running: bool, // false after a shutdown request or when the client disconnected
     // terrain: terrain::Terrain,
     // point_lights: point_lights::PointLights,
     watch: watch::Watch<{ game_logic_interface::WATCH_POINT_SIZE }>,
 }
 
-impl GameLogic {
-    pub fn new(
-        settings: GameLogicSettings,
-        channel_0_rx: mpsc::Receiver<GameLogicMessageRequest>,
-        channel_1_tx: mpsc::Sender<GameLogicMessageHeavy>,
-        channel_2_tx: mpsc::Sender<GameLogicMessageMedium>,
-        channel_3_tx: mpsc::Sender<GameLogicMessageLight>,
-        channel_4_tx: mpsc::Sender<GameLogicMessageCritical>,
-    ) -> Self {
+/// The parts of the game logic created from the settings, independent of the channels to the client
+struct Simulation {
+    heightmap_generator: Arc<heightmap_generator::HeightMapGenerator>,
+    ants: ants::Ants,
+    houses: houses::Houses,
+    needs: needs::Needs,
+    behaviours: behaviour::Behaviours,
+    goods: goods::GoodsCatalogue,
+    recipes: recipes::Recipes,
+    factories: factories::Factories,
+    labour_market: labour::LabourMarket,
+    market: market::Market,
+    movement: movement::Movement,
+    pheromones: pheromones::Pheromones,
+    accounts: accounts::Accounts,
+    decisions: fastrand::Rng,
+    recording: recording::Recording,
+}
+
+impl Simulation {
+    fn new(settings: &GameLogicSettings) -> Self {
         // let size = settings.map_size;
 
-        let heightmap_generator = heightmap_generator::HeightMapGenerator::new();
+        let heightmap_generator = Arc::new(heightmap_generator::HeightMapGenerator::new(
+            random::noise_seed(settings.seed, random::Stream::Terrain),
+        ));
         let ants = ants::Ants::new(settings.max_nr_ants);
 
+        let goods = goods::GoodsCatalogue::default();
+        let recipes = recipes::Recipes::new(&goods, recipes::DEFAULT_RECIPES);
+        let factories = factories::Factories::create_default(&goods, &recipes);
+        let labour_market = labour::LabourMarket::new(&factories, &recipes, ants.len());
+        let market = market::Market::new(goods.len());
+
+        let mut houses = houses::Houses::create_default(ants.len());
+        let homes: Vec<Option<usize>> = (0..ants.len())
+            .map(|id| houses.move_in(id, ants.get(id).pos))
+            .collect();
+        let mut needs_rng = random::rng(settings.seed, random::Stream::Needs);
+        let needs = needs::Needs::new(&goods, &homes, &mut needs_rng);
+        let behaviours = behaviour::Behaviours::new(ants.len());
+
+        let mut accounts = accounts::Accounts::new();
+        for id in 0..ants.len() {
+            accounts.mint(0, accounts::Account::Ant(id), INITIAL_ANT_MONEY);
+        }
+        for factory in factories.iter() {
+            accounts.mint(
+                0,
+                accounts::Account::Factory(factory.id),
+                INITIAL_FACTORY_MONEY,
+            );
+        }
+
+        let positions = (0..ants.len())
+            .map(|id| ants.get(id).pos)
+            .chain(houses.iter().map(|house| house.pos))
+            .chain(factories.iter().map(|factory| factory.pos));
+        let (min, max) = bounding_box(positions);
+        let margin = cgmath::Vector2::new(WORLD_MARGIN, WORLD_MARGIN);
+        let movement = movement::Movement::new(&heightmap_generator, min - margin, max + margin);
+        let pheromones = pheromones::Pheromones::new(min - margin, max + margin);
+
         // let terrain = terrain::Terrain::new(size, size, 1.0);
         // let point_lights = point_lights::PointLights::new(&terrain);
 
+        let decisions = random::rng(settings.seed, random::Stream::Decisions);
+        let recording = recording::Recording::new(settings.clone());
+
+        Self {
+            heightmap_generator,
+            ants,
+            houses,
+            needs,
+            behaviours,
+            goods,
+            recipes,
+            factories,
+            labour_market,
+            market,
+            movement,
+            pheromones,
+            accounts,
+            decisions,
+            recording,
+        }
+    }
+}
+
+impl GameLogic {
+    pub fn new(
+        settings: GameLogicSettings,
+        channel_0_rx: mpsc::Receiver<Request>,
+        channel_1_tx: mpsc::SyncSender<GameLogicMessageHeavy>,
+        channel_2_tx: mpsc::SyncSender<GameLogicMessageMedium>,
+        channel_3_tx: mpsc::SyncSender<GameLogicMessageLight>,
+        channel_4_tx: mpsc::SyncSender<GameLogicMessageCritical>,
+    ) -> Self {
+        let Simulation {
+            heightmap_generator,
+            ants,
+            houses,
+            needs,
+            behaviours,
+            goods,
+            recipes,
+            factories,
+            labour_market,
+            market,
+            movement,
+            pheromones,
+            accounts,
+            decisions,
+            recording,
+        } = Simulation::new(&settings);
+        let heightmap_workers = HeightMapWorkers::new(settings.nr_terrain_workers);
         let watch = watch::Watch::new();
 
         Self {
-            _settings: settings,
+            settings,
 
             channel_0_rx,
-            channel_1_tx,
-            channel_2_tx,
-            channel_3_tx,
-            _channel_4_tx: channel_4_tx,
+            pending_requests: VecDeque::new(),
+            channel_1_tx: Outbox::new(channel_1_tx, game_logic_interface::BACKLOG_LIMIT),
+            channel_2_tx: Outbox::new(channel_2_tx, game_logic_interface::BACKLOG_LIMIT),
+            channel_3_tx: Outbox::new(channel_3_tx, game_logic_interface::BACKLOG_LIMIT),
+            channel_4_tx: Outbox::new(channel_4_tx, game_logic_interface::BACKLOG_LIMIT),
 
             heightmap_generator,
+            heightmap_workers,
             ants,
+            houses,
+            needs,
+            behaviours,
+            goods,
+            recipes,
+            factories,
+            labour_market,
+            market,
+            movement,
+            pheromones,
+            accounts,
+            decisions,
+            clock: clock::Clock::new(),
+            recording,
+            replay: None,
+            is_running: true,
             // terrain,
             // point_lights,
             watch,
         }
     }
 
-    pub(crate) fn update(&mut self) {
+    /// Processes the requests and advances the simulation by the elapsed real time in seconds
+    pub(crate) fn update(&mut self, real_dt: f64) {
+        self.flush_channels();
+
         // update ups viewer
         self.watch.update();
         let _res = self
@@ -78,35 +245,1102 @@ impl GameLogic {
                 self.watch.get_viewer_data(),
             ));
 
+        self.process_requests();
+        self.send_terrain();
+        if !self.is_running {
+            return;
+        }
+
+        let nr_ticks = self.clock.advance(real_dt);
+        for _i in 0..nr_ticks {
+            self.next_tick();
+        }
+
+        self.send_clock_state();
+        self.send_channel_statistics();
+    }
+
+    /// Processes the requests and advances the simulation by exactly one tick, regardless of the clock
+    pub(crate) fn step(&mut self) {
+        self.flush_channels();
+
+        self.process_requests();
+        self.send_terrain();
+        if !self.is_running {
+            return;
+        }
+
+        self.next_tick();
+        self.send_clock_state();
+        self.send_channel_statistics();
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.is_running
+    }
+
+    /// True until the end of a recorded session is reached
+    pub(crate) fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Restarts the simulation with new settings, keeping the channels to the client
+    fn reset(&mut self, settings: GameLogicSettings) {
+        let Simulation {
+            heightmap_generator,
+            ants,
+            houses,
+            needs,
+            behaviours,
+            goods,
+            recipes,
+            factories,
+            labour_market,
+            market,
+            movement,
+            pheromones,
+            accounts,
+            decisions,
+            recording,
+        } = Simulation::new(&settings);
+
+        // the waiting tiles are still delivered
+        if settings.nr_terrain_workers != self.settings.nr_terrain_workers {
+            self.heightmap_workers.resize(settings.nr_terrain_workers);
+        }
+
+        self.settings = settings;
+        self.heightmap_generator = heightmap_generator;
+        self.ants = ants;
+        self.houses = houses;
+        self.needs = needs;
+        self.behaviours = behaviours;
+        self.goods = goods;
+        self.recipes = recipes;
+        self.factories = factories;
+        self.labour_market = labour_market;
+        self.market = market;
+        self.movement = movement;
+        self.pheromones = pheromones;
+        self.accounts = accounts;
+        self.decisions = decisions;
+        self.clock = clock::Clock::new();
+        self.recording = recording;
+        self.replay = None;
+        self.is_running = true;
+        self.watch = watch::Watch::new();
+    }
+
+    /// Moves the messages waiting for the client into the channels
+    fn flush_channels(&mut self) {
+        self.channel_1_tx.flush();
+        self.channel_2_tx.flush();
+        self.channel_3_tx.flush();
+        self.channel_4_tx.flush();
+    }
+
+    pub(crate) fn channel_statistics(&self) -> ChannelStatistics {
+        ChannelStatistics {
+            heavy: self.channel_1_tx.statistics(),
+            medium: self.channel_2_tx.statistics(),
+            light: self.channel_3_tx.statistics(),
+            critical: self.channel_4_tx.statistics(),
+        }
+    }
+
+    fn send_channel_statistics(&mut self) {
+        let channel_statistics = self.channel_statistics();
+        let _res = self
+            .channel_2_tx
+            .send(GameLogicMessageMedium::UpdateChannelStatistics(
+                channel_statistics,
+            ));
+    }
+
+    /// Sends the finished tiles of the terrain in the order of their completion
+    fn send_terrain(&mut self) {
+        for (id, height_map) in self.heightmap_workers.collect(REQUEST_TIME_BUDGET) {
+            let res = self
+                .channel_1_tx
+                .send(GameLogicMessageHeavy::Terrain { id, height_map });
+            match res {
+                Ok(_) => {}
+                Err(_err) => {
+                    // println!("{}", err)
+                }
+            }
+        }
+    }
+
+    fn send_clock_state(&mut self) {
+        let res = self
+            .channel_2_tx
+            .send(GameLogicMessageMedium::UpdateClock(self.clock.state()));
+        match res {
+            Ok(_) => {}
+            Err(_err) => {
+                // println!("{}", err)
+            }
+        }
+    }
+
+    fn send_sync(&mut self, id: RequestId) {
+        let ants = (0..self.ants.len())
+            .filter(|id| self.ants.is_alive(*id))
+            .map(|id| self.ants.get(id).clone())
+            .collect();
+        let factories = self.factories.iter().map(|elem| elem.state()).collect();
+
+        let res = self.channel_2_tx.send(GameLogicMessageMedium::Synced {
+            id,
+            settings: self.settings.clone(),
+            clock: self.clock.state(),
+            ants,
+            factories,
+        });
+        match res {
+            Ok(_) => {}
+            Err(_err) => {
+                // println!("{}", err)
+            }
+        }
+    }
+
+    /// Receives all pending requests and processes them in order until the time budget is used up,
+    /// the rest waits for the next tick. A cancelled request is removed before it is processed.
+    fn process_requests(&mut self) {
         self.watch.start(0, "Process Requests");
         {
-            let res = self.channel_0_rx.try_recv();
-            match res {
-                Ok(message) => match message {
-                    GameLogicMessageRequest::GetTerrain(heightmap_details) => {
-                        let heightmap = self.heightmap_generator.generate(heightmap_details);
-                        let res = self
-                            .channel_1_tx
-                            .send(GameLogicMessageHeavy::Terrain(heightmap));
-                        match res {
-                            Ok(_) => {}
-                            Err(err) => println!("{}", err),
-                        }
+            loop {
+                let res = self.channel_0_rx.try_recv();
+                match res {
+                    Ok(Request {
+                        message: GameLogicMessageRequest::Cancel(id),
+                        ..
+                    }) => {
+                        self.pending_requests.retain(|elem| elem.id != id);
+                        self.heightmap_workers.cancel(id);
+                    }
+                    Ok(Request {
+                        message: GameLogicMessageRequest::SetViewPosition(view_position),
+                        ..
+                    }) => {
+                        self.heightmap_workers.set_view_position(view_position);
+                    }
+                    Ok(request) => self.pending_requests.push_back(request),
+                    Err(mpsc::TryRecvError::Empty) => {
+                        // no message found
+                        break;
+                    }
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        // the client is gone
+                        self.is_running = false;
+                        break;
                     }
-                },
-                Err(_err) => {
-                    // no message found
+                }
+            }
+
+            let start_time = instant::Instant::now();
+            while let Some(request) = self.pending_requests.pop_front() {
+                self.handle_request(request);
+
+                if start_time.elapsed().as_secs_f64() > REQUEST_TIME_BUDGET {
+                    break;
                 }
             }
         }
         self.watch.stop(0);
+    }
+
+    fn handle_request(&mut self, request: Request) {
+        let id = request.id;
+        let res = match &request.message {
+            GameLogicMessageRequest::SaveRecording(path) => {
+                self.recording.end_tick = self.clock.tick();
+                self.recording
+                    .save(path)
+                    .map_err(|err| format!("{}: {}", path, err))
+            }
+            GameLogicMessageRequest::SaveSnapshot(path) => self
+                .save_snapshot(path)
+                .map_err(|err| format!("{}: {}", path, err)),
+            GameLogicMessageRequest::LoadSnapshot(path) => self
+                .load_snapshot(path)
+                .map_err(|err| format!("{}: {}", path, err)),
+            _ => {
+                self.recording.record(self.clock.tick(), request.clone());
+                self.apply_request(request);
+                Ok(())
+            }
+        };
+
+        match res {
+            Ok(_) => {}
+            Err(reason) => report(
+                &mut self.channel_4_tx,
+                GameLogicMessageCritical::RequestFailed { id, reason },
+            ),
+        }
+    }
+
+    fn apply_request(&mut self, request: Request) {
+        let id = request.id;
+        match request.message {
+            GameLogicMessageRequest::GetTerrain(heightmap_details) => {
+                let res = heightmap_details.validate();
+                if let Err(reason) = res {
+                    report(
+                        &mut self.channel_4_tx,
+                        GameLogicMessageCritical::InvalidRequest { id, reason },
+                    );
+                    return;
+                }
+
+                self.heightmap_workers
+                    .submit(id, heightmap_details, &self.heightmap_generator);
+            }
+            GameLogicMessageRequest::Cancel(_id) => {
+                // removed from the pending requests when it is received
+            }
+            GameLogicMessageRequest::SetViewPosition(_view_position) => {
+                // applied when it is received
+            }
+            GameLogicMessageRequest::Pause => self.clock.pause(),
+            GameLogicMessageRequest::Resume => self.clock.resume(),
+            GameLogicMessageRequest::SetTimeScale(time_scale) => {
+                self.clock.set_time_scale(time_scale)
+            }
+            GameLogicMessageRequest::Step => self.clock.step(),
+            GameLogicMessageRequest::Shutdown => self.is_running = false,
+            GameLogicMessageRequest::Reset(settings) => self.reset(settings),
+            GameLogicMessageRequest::Sync => self.send_sync(id),
+            GameLogicMessageRequest::SaveRecording(_path)
+            | GameLogicMessageRequest::SaveSnapshot(_path)
+            | GameLogicMessageRequest::LoadSnapshot(_path) => {
+                // files are only accessed by the live session, never during a replay
+            }
+        }
+    }
+
+    /// Starts to reproduce a recorded session, the game logic has to be created with the recorded settings.
+    /// The recorded requests are applied while the simulation advances.
+    pub(crate) fn replay(&mut self, recording: recording::Recording) {
+        if let Some(snapshot) = &recording.snapshot {
+            let res = self.restore(snapshot);
+            match res {
+                Ok(_) => {}
+                Err(err) => {
+                    report(
+                        &mut self.channel_4_tx,
+                        GameLogicMessageCritical::Error(format!(
+                            "snapshot of the recording: {}",
+                            err
+                        )),
+                    );
+                    return;
+                }
+            }
+        }
+
+        self.replay = Some(recording);
+    }
+
+    /// Advances the simulation by one tick, applying the recorded requests during a replay
+    fn next_tick(&mut self) {
+        let tick = self.clock.tick();
+        if tick.is_multiple_of(KEYFRAME_INTERVAL) {
+            self.check_state(tick);
+        }
+
+        let Some(recording) = self.replay.take() else {
+            self.simulate_tick();
+            return;
+        };
+
+        if let Some(expected) = recording.checksum_at(tick) {
+            let actual = self.recording.checksum_at(tick).unwrap_or_default();
+            if actual != expected {
+                report(
+                    &mut self.channel_4_tx,
+                    GameLogicMessageCritical::Desync {
+                        tick,
+                        expected,
+                        actual,
+                    },
+                );
+            }
+        }
+
+        for request in recording.requests_at(tick) {
+            self.apply_request(request.clone());
+        }
+
+        if self.is_running && tick < recording.end_tick {
+            self.simulate_tick();
+            self.replay = Some(recording);
+        }
+    }
+
+    /// Checks the invariants of the state and records its checksum, so a replay can detect a desync
+    fn check_state(&mut self, tick: u64) {
+        if !self.accounts.is_conserved() {
+            report(
+                &mut self.channel_4_tx,
+                GameLogicMessageCritical::InvariantViolated(format!(
+                    "money is not conserved: total {}, minted {}, burned {}",
+                    self.accounts.total(),
+                    self.accounts.minted(),
+                    self.accounts.burned()
+                )),
+            );
+        }
+
+        let mut writer = wire::Writer::new();
+        self.encode_state(&mut writer);
+        self.recording
+            .record_checksum(tick, wire::checksum(&writer.into_bytes()));
+    }
+
+    /// Writes the whole state of the simulation to a file
+    fn save_snapshot(&self, path: &str) -> std::io::Result<()> {
+        let mut writer = wire::Writer::new();
+        writer.write_header();
+        self.encode_state(&mut writer);
+
+        std::fs::write(path, writer.into_bytes())
+    }
+
+    /// Continues the simulation from the state in a file, a new recording starts from it
+    fn load_snapshot(&mut self, path: &str) -> std::io::Result<()> {
+        let bytes = std::fs::read(path)?;
+        wire::strip_header(&bytes)
+            .and_then(|state| self.restore(state))
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// The random stream of the decisions is drawn from while the simulation runs, so its state is kept.
+    /// The other streams are only drawn from while creating the world, the settings are enough to restore them.
+    /// Goods, recipes and the terrain are recreated from the defaults and the seed.
+    fn encode_state(&self, writer: &mut wire::Writer) {
+        self.settings.encode(writer);
+        self.clock.encode(writer);
+        self.ants.encode(writer);
+        self.houses.encode(writer);
+        self.needs.encode(writer);
+        self.behaviours.encode(writer);
+        self.pheromones.encode(writer);
+        self.factories.encode(writer);
+        self.labour_market.encode(writer);
+        self.market.encode(writer);
+        self.accounts.encode(writer);
+        writer.write_u64(self.decisions.get_seed());
+    }
+
+    /// Replaces the state, which is unchanged if the data is invalid
+    fn restore(&mut self, state: &[u8]) -> Result<(), wire::DecodeError> {
+        let mut reader = wire::Reader::new(state);
+        let settings = GameLogicSettings::decode(&mut reader)?;
+        let clock = clock::Clock::decode(&mut reader)?;
+        let ants = ants::Ants::decode(&mut reader)?;
+        let houses = houses::Houses::decode(&mut reader)?;
+        let needs = needs::Needs::decode(&self.goods, &mut reader)?;
+        let behaviours = behaviour::Behaviours::decode(&mut reader)?;
+        let pheromones = pheromones::Pheromones::decode(&mut reader)?;
+        let factories = factories::Factories::decode(&mut reader)?;
+        let labour_market = labour::LabourMarket::decode(&mut reader)?;
+        let market = market::Market::decode(&mut reader)?;
+        let accounts = accounts::Accounts::decode(&mut reader)?;
+        let decisions = fastrand::Rng::with_seed(reader.read_u64()?);
+        if reader.remaining() > 0 {
+            return Err(wire::DecodeError::TrailingBytes(reader.remaining()));
+        }
+
+        // the parts refer to each other by their ids
+        let nr_ants = ants.len();
+        let nr_houses = houses.iter().count();
+        let nr_goods = self.goods.len();
+        ants.validate(nr_goods)?;
+        houses.validate(nr_ants)?;
+        needs.validate(nr_ants, nr_houses)?;
+        behaviours.validate(nr_ants, factories.len())?;
+        factories.validate(self.recipes.iter().count(), nr_goods)?;
+        labour_market.validate(nr_ants, &factories)?;
+        market.validate(nr_goods, nr_ants, factories.len())?;
+        accounts.validate(nr_ants, factories.len(), nr_houses)?;
+
+        self.reset(settings.clone());
+        self.clock = clock;
+        self.ants = ants;
+        self.houses = houses;
+        self.needs = needs;
+        self.behaviours = behaviours;
+        self.pheromones = pheromones;
+        self.factories = factories;
+        self.labour_market = labour_market;
+        self.market = market;
+        self.accounts = accounts;
+        self.decisions = decisions;
+        self.recording.snapshot = Some(state.to_vec());
+
+        // the client replaces its state with the loaded one
+        let res = self
+            .channel_2_tx
+            .send(GameLogicMessageMedium::SnapshotLoaded(settings));
+        match res {
+            Ok(_) => {}
+            Err(_err) => {
+                // println!("{}", err)
+            }
+        }
+        for id in (0..self.ants.len()).filter(|id| self.ants.is_alive(*id)) {
+            let _res = self
+                .channel_2_tx
+                .send(GameLogicMessageMedium::AntBorn(self.ants.get(id).clone()));
+        }
+        self.factories.send_updates(&mut self.channel_2_tx);
+        self.send_clock_state();
+
+        Ok(())
+    }
+
+    /// Advances the simulation by one tick
+    fn simulate_tick(&mut self) {
+        let tick = self.clock.tick();
 
         self.watch.start(1, "Update point lights");
         {
             // point lights
-            // self.point_lights.update(&self.channel_3_tx);
-            self.ants.update(&self.channel_3_tx);
+            // self.point_lights.update(tick, &mut self.channel_3_tx);
+            self.ants.update(tick, &mut self.channel_3_tx);
         }
         self.watch.stop(1);
+
+        self.watch.start(5, "Update labour market");
+        {
+            self.labour_market.update(
+                tick,
+                &mut self.factories,
+                &self.ants,
+                &self.needs,
+                &mut self.accounts,
+                &mut self.decisions,
+            );
+        }
+        self.watch.stop(5);
+
+        self.watch.start(2, "Update factories");
+        {
+            self.factories.update(
+                tick,
+                TICK_DURATION,
+                &self.goods,
+                &self.recipes,
+                &mut self.market,
+                &self.accounts,
+            );
+        }
+        self.watch.stop(2);
+
+        self.watch.start(4, "Update needs");
+        {
+            let deaths = self
+                .needs
+                .update(
+                    TICK_DURATION as f32,
+                    &mut self.ants,
+                    &mut self.market,
+                    &self.accounts,
+                )
+                .to_vec();
+            for (id, cause) in deaths {
+                self.ants.kill(id);
+                self.labour_market.quit(id);
+
+                // the money of the ant is inherited by its household or lost
+                let account = accounts::Account::Ant(id);
+                match self.needs.get(id).home {
+                    Some(home) => {
+                        self.houses.move_out(home, id);
+
+                        let balance = self.accounts.balance(account);
+                        let res = self.accounts.transfer(
+                            tick,
+                            accounts::EntryKind::Inheritance,
+                            account,
+                            accounts::Account::Household(home),
+                            balance,
+                        );
+                        match res {
+                            Ok(_) => {}
+                            Err(err) => report(
+                                &mut self.channel_4_tx,
+                                GameLogicMessageCritical::InvariantViolated(err.to_string()),
+                            ),
+                        }
+                    }
+                    None => {
+                        self.accounts.burn(tick, account);
+                    }
+                }
+
+                let res = self
+                    .channel_2_tx
+                    .send(GameLogicMessageMedium::AntDied { id, cause });
+                match res {
+                    Ok(_) => {}
+                    Err(_err) => {
+                        // println!("{}", err)
+                    }
+                }
+            }
+
+            for factory in self.factories.emitted_agents() {
+                let pos = self.factories.get(*factory).pos;
+                let Some(id) = self.ants.spawn(pos) else {
+                    break; // no ids left for more ants
+                };
+
+                // the ant is born in its home, or at the factory without one
+                let home = self.houses.move_in(id, pos);
+                if let Some(home) = home {
+                    self.ants.set_pos(id, self.houses.get(home).pos, 0.0);
+                }
+                self.needs.born(id, home);
+                self.labour_market.born(id);
+                self.behaviours.born(id);
+
+                let res = self
+                    .channel_2_tx
+                    .send(GameLogicMessageMedium::AntBorn(self.ants.get(id).clone()));
+                match res {
+                    Ok(_) => {}
+                    Err(_err) => {
+                        // println!("{}", err)
+                    }
+                }
+            }
+        }
+        self.watch.stop(4);
+
+        self.watch
+            .start(6, "Update behaviours, trails and move ants");
+        {
+            let goals = self.behaviours.update(
+                tick,
+                TICK_DURATION as f32,
+                &mut self.ants,
+                &self.needs,
+                &self.labour_market,
+                &self.houses,
+                &mut self.factories,
+                &self.goods,
+                &self.recipes,
+            );
+            self.movement.update(
+                tick,
+                TICK_DURATION as f32,
+                &mut self.ants,
+                &goals,
+                &self.pheromones,
+            );
+            self.pheromones.update(TICK_DURATION as f32, &self.ants);
+
+            if tick.is_multiple_of(PHEROMONE_INTERVAL) {
+                let res = self.channel_1_tx.send(GameLogicMessageHeavy::Pheromones(
+                    self.pheromones.field().clone(),
+                ));
+                match res {
+                    Ok(_) => {}
+                    Err(_err) => {
+                        // println!("{}", err)
+                    }
+                }
+            }
+        }
+        self.watch.stop(6);
+
+        self.watch.start(3, "Clear market");
+        {
+            let trades = self.market.update(tick);
+            for trade in trades {
+                let res = settle_trade(
+                    trade,
+                    &mut self.factories,
+                    &mut self.ants,
+                    &mut self.accounts,
+                );
+                match res {
+                    Ok(0) => {}
+                    Ok(quantity) => {
+                        // the client sees the goods and the money that really changed hands
+                        let res = self.channel_2_tx.send(GameLogicMessageMedium::MarketFill(
+                            market::Trade {
+                                quantity,
+                                ..trade.clone()
+                            },
+                        ));
+                        match res {
+                            Ok(_) => {}
+                            Err(_err) => {
+                                // println!("{}", err)
+                            }
+                        }
+                    }
+                    Err(err) => report(
+                        &mut self.channel_4_tx,
+                        GameLogicMessageCritical::InvariantViolated(err.to_string()),
+                    ),
+                }
+            }
+
+            self.factories.send_updates(&mut self.channel_2_tx);
+        }
+        self.watch.stop(3);
+
+        self.clock.next_tick();
     }
 }
+
+/// Lower and upper corner of the positions, including the origin
+fn bounding_box(
+    positions: impl Iterator<Item = cgmath::Vector2<f32>>,
+) -> (cgmath::Vector2<f32>, cgmath::Vector2<f32>) {
+    positions.fold(
+        (
+            cgmath::Vector2::new(0.0, 0.0),
+            cgmath::Vector2::new(0.0, 0.0),
+        ),
+        |(min, max), pos| {
+            (
+                cgmath::Vector2::new(min.x.min(pos.x), min.y.min(pos.y)),
+                cgmath::Vector2::new(max.x.max(pos.x), max.y.max(pos.y)),
+            )
+        },
+    )
+}
+
+fn inventory_mut<'a>(
+    trader: market::Trader,
+    factories: &'a mut factories::Factories,
+    ants: &'a mut ants::Ants,
+) -> &'a mut Inventory {
+    match trader {
+        market::Trader::Ant(id) => ants.inventory_mut(id),
+        market::Trader::Factory(id) => &mut factories.get_mut(id).inventory,
+    }
+}
+
+/// Moves the traded goods from the seller to the buyer and the money back, returns the settled quantity
+///
+/// The buyer only receives as many goods as it is able to pay and the seller is able to deliver.
+/// The seller pays the sales tax on the revenue.
+fn settle_trade(
+    trade: &market::Trade,
+    factories: &mut factories::Factories,
+    ants: &mut ants::Ants,
+    accounts: &mut accounts::Accounts,
+) -> Result<u32, accounts::InsufficientFunds> {
+    let buyer_account = accounts::Account::from(trade.buyer);
+    let seller_account = accounts::Account::from(trade.seller);
+
+    let affordable = accounts
+        .balance(buyer_account)
+        .checked_div(trade.price)
+        .unwrap_or(accounts::Money::MAX);
+    let quantity = trade.quantity.min(affordable.min(u32::MAX as u64) as u32);
+
+    let mut goods = Inventory::new();
+
+    let seller = inventory_mut(trade.seller, factories, ants);
+    let quantity = seller.transfer(&mut goods, trade.good, quantity);
+
+    let buyer = inventory_mut(trade.buyer, factories, ants);
+    goods.transfer(buyer, trade.good, quantity);
+
+    // the quantity is limited to the affordable one, so this never fails
+    let revenue = quantity as accounts::Money * trade.price;
+    accounts.transfer(
+        trade.tick,
+        accounts::EntryKind::Trade,
+        buyer_account,
+        seller_account,
+        revenue,
+    )?;
+    let tax = revenue / accounts::SALES_TAX;
+    if tax > 0 {
+        accounts.transfer(
+            trade.tick,
+            accounts::EntryKind::Tax,
+            seller_account,
+            accounts::Account::Treasury,
+            tax,
+        )?;
+    }
+
+    Ok(quantity)
+}
+
+/// Reports a problem to the client, or logs it if the client is gone
+fn report(channel: &mut Outbox<GameLogicMessageCritical>, msg: GameLogicMessageCritical) {
+    let res = channel.send(msg);
+    match res {
+        Ok(_) => {}
+        Err(err) => log::error!("{}", err.0),
+    }
+}
+
+/// Wraps a message of a test, the id is not checked
+#[cfg(test)]
+fn request(message: GameLogicMessageRequest) -> Request {
+    Request { id: 0, message }
+}
+
+/// The ends of the channels on the side of the client
+#[cfg(test)]
+struct Channels {
+    requests: mpsc::Sender<Request>,
+    heavy: mpsc::Receiver<GameLogicMessageHeavy>,
+    medium: mpsc::Receiver<GameLogicMessageMedium>,
+    light: mpsc::Receiver<GameLogicMessageLight>,
+    critical: mpsc::Receiver<GameLogicMessageCritical>,
+}
+
+/// Settings of a small world, the terrain is generated without workers
+#[cfg(test)]
+fn test_settings(seed: u64) -> GameLogicSettings {
+    GameLogicSettings {
+        enable_multithreading: false,
+        max_nr_ants: 100,
+        seed,
+        nr_terrain_workers: 0,
+    }
+}
+
+/// Creates the game logic, returns the channels of the client.
+/// The output channels hold a few messages, the rest waits in the outboxes or is dropped.
+#[cfg(test)]
+fn create_game_logic(settings: GameLogicSettings) -> (GameLogic, Channels) {
+    let (channel_0_tx, channel_0_rx) = mpsc::channel();
+    let (channel_1_tx, channel_1_rx) = mpsc::sync_channel(16);
+    let (channel_2_tx, channel_2_rx) = mpsc::sync_channel(16);
+    let (channel_3_tx, channel_3_rx) = mpsc::sync_channel(16);
+    let (channel_4_tx, channel_4_rx) = mpsc::sync_channel(16);
+
+    let game_logic = GameLogic::new(
+        settings,
+        channel_0_rx,
+        channel_1_tx,
+        channel_2_tx,
+        channel_3_tx,
+        channel_4_tx,
+    );
+    let channels = Channels {
+        requests: channel_0_tx,
+        heavy: channel_1_rx,
+        medium: channel_2_rx,
+        light: channel_3_rx,
+        critical: channel_4_rx,
+    };
+
+    (game_logic, channels)
+}
+
+#[test]
+fn test_production_chain() {
+    let (mut game_logic, channels) = create_game_logic(test_settings(0));
+
+    // the workers gather water and wood first, the grain of the farm is bought and baked into food
+    let food = game_logic.goods.find("food").unwrap();
+    let bakery = game_logic.recipes.find("bake food").unwrap();
+    let bakery = game_logic
+        .factories
+        .iter()
+        .find(|elem| elem.recipe == bakery)
+        .unwrap()
+        .id;
+    let mut has_food = false;
+    for _i in 0..2000 {
+        game_logic.step();
+        has_food |= game_logic.factories.get(bakery).inventory.quantity(food) > 0;
+    }
+    assert!(has_food);
+
+    // the walking ants are sent to the client
+    assert!(matches!(
+        channels.light.try_recv(),
+        Ok(GameLogicMessageLight::UpdateAnts(_))
+    ));
+
+    // the base factory turns the blue resources mined from the gathered ore into agents
+    let base_factory = game_logic.factories.get(factories::BASE_FACTORY_ID);
+    assert!(base_factory.produced_agents() > 0);
+
+    // money only moved between the accounts, the sellers paid taxes
+    assert!(game_logic.accounts.is_conserved());
+    assert!(game_logic.accounts.balance(accounts::Account::Treasury) > 0);
+    assert_eq!(
+        game_logic.accounts.total(),
+        100 * INITIAL_ANT_MONEY + game_logic.factories.len() as u64 * INITIAL_FACTORY_MONEY
+    );
+}
+
+#[test]
+fn test_same_seed_same_state() {
+    let run = |seed: u64| {
+        let (mut game_logic, _channels) = create_game_logic(test_settings(seed));
+        for _i in 0..200 {
+            game_logic.step();
+        }
+
+        let factories: Vec<factories::FactoryState> = game_logic
+            .factories
+            .iter()
+            .map(|elem| elem.state())
+            .collect();
+        let balances: Vec<accounts::Money> = (0..game_logic.ants.len())
+            .map(|id| game_logic.accounts.balance(accounts::Account::Ant(id)))
+            .collect();
+        let ages: Vec<f64> = (0..game_logic.ants.len())
+            .map(|id| game_logic.needs.get(id).age)
+            .collect();
+
+        (factories, balances, ages)
+    };
+
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7).2, run(8).2);
+}
+
+#[test]
+fn test_reset_and_shutdown() {
+    let (mut game_logic, channels) = create_game_logic(test_settings(0));
+    for _i in 0..10 {
+        game_logic.step();
+    }
+    assert_eq!(game_logic.clock.tick(), 10);
+
+    let settings = GameLogicSettings {
+        max_nr_ants: 4,
+        ..test_settings(1)
+    };
+    channels
+        .requests
+        .send(request(GameLogicMessageRequest::Reset(settings)))
+        .unwrap();
+    game_logic.step();
+    assert_eq!(game_logic.clock.tick(), 1);
+    assert_eq!(game_logic.ants.len(), 4);
+
+    // the channels survive the reset
+    channels
+        .requests
+        .send(request(GameLogicMessageRequest::Shutdown))
+        .unwrap();
+    game_logic.step();
+    assert!(!game_logic.is_running());
+
+    let (mut game_logic, channels) = create_game_logic(test_settings(0));
+    drop(channels.requests);
+    game_logic.step();
+    assert!(!game_logic.is_running());
+}
+
+#[test]
+fn test_replay_reproduces_the_session() {
+    let (mut game_logic, channels) = create_game_logic(test_settings(3));
+    for i in 0..300 {
+        match i {
+            10 => channels
+                .requests
+                .send(request(GameLogicMessageRequest::SetTimeScale(4.0))),
+            150 => channels
+                .requests
+                .send(request(GameLogicMessageRequest::Pause)),
+            _ => Ok(()),
+        }
+        .unwrap();
+        game_logic.step();
+    }
+
+    let path = std::env::temp_dir().join(format!("recording_{}.mes", std::process::id()));
+    channels
+        .requests
+        .send(request(GameLogicMessageRequest::SaveRecording(
+            path.to_string_lossy().to_string(),
+        )))
+        .unwrap();
+    game_logic.process_requests();
+
+    let recording = recording::Recording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(recording.requests.len(), 2);
+    assert_eq!(recording.end_tick, 300);
+
+    let (mut replayed, _channels) = create_game_logic(recording.settings.clone());
+    replayed.replay(recording);
+    while replayed.is_replaying() {
+        replayed.step();
+    }
+
+    let state = |game_logic: &GameLogic| {
+        let factories: Vec<factories::FactoryState> = game_logic
+            .factories
+            .iter()
+            .map(|elem| elem.state())
+            .collect();
+        let balances: Vec<accounts::Money> = (0..game_logic.ants.len())
+            .map(|id| game_logic.accounts.balance(accounts::Account::Ant(id)))
+            .collect();
+
+        (game_logic.clock.state(), factories, balances)
+    };
+    assert_eq!(state(&replayed), state(&game_logic));
+}
+
+#[test]
+fn test_snapshot_restores_the_state() {
+    let (mut game_logic, channels) = create_game_logic(test_settings(5));
+    for _i in 0..200 {
+        game_logic.step();
+    }
+
+    let path = std::env::temp_dir().join(format!("snapshot_{}.mes", std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    channels
+        .requests
+        .send(request(GameLogicMessageRequest::SaveSnapshot(path.clone())))
+        .unwrap();
+    game_logic.process_requests();
+
+    // a different world continues from the saved state
+    let (mut loaded, channels) = create_game_logic(test_settings(6));
+    channels
+        .requests
+        .send(request(GameLogicMessageRequest::LoadSnapshot(path.clone())))
+        .unwrap();
+    loaded.process_requests();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.clock.tick(), 200);
+
+    // the client replaces its state
+    loaded.flush_channels();
+    assert!(matches!(
+        channels.medium.try_recv(),
+        Ok(GameLogicMessageMedium::SnapshotLoaded(_))
+    ));
+
+    for _i in 0..100 {
+        game_logic.step();
+        loaded.step();
+    }
+
+    let state = |game_logic: &GameLogic| {
+        let mut writer = wire::Writer::new();
+        game_logic.encode_state(&mut writer);
+        writer.into_bytes()
+    };
+    assert!(state(&loaded) == state(&game_logic));
+    assert!(loaded.accounts.is_conserved());
+}
+
+#[test]
+fn test_inconsistent_snapshot_is_refused() {
+    let (mut game_logic, _channels) = create_game_logic(test_settings(3));
+
+    // the ants of another world do not match the rest of the state
+    let mut writer = wire::Writer::new();
+    game_logic.settings.encode(&mut writer);
+    game_logic.clock.encode(&mut writer);
+    ants::Ants::new(4).encode(&mut writer);
+    game_logic.houses.encode(&mut writer);
+    game_logic.needs.encode(&mut writer);
+    game_logic.behaviours.encode(&mut writer);
+    game_logic.pheromones.encode(&mut writer);
+    game_logic.factories.encode(&mut writer);
+    game_logic.labour_market.encode(&mut writer);
+    game_logic.market.encode(&mut writer);
+    game_logic.accounts.encode(&mut writer);
+    writer.write_u64(game_logic.decisions.get_seed());
+
+    let res = game_logic.restore(&writer.into_bytes());
+    assert!(matches!(res, Err(wire::DecodeError::Inconsistent(_))));
+    assert_eq!(game_logic.ants.len(), 100);
+}
+
+#[test]
+fn test_replay_reports_a_desync() {
+    let (mut game_logic, _channels) = create_game_logic(test_settings(9));
+    for _i in 0..KEYFRAME_INTERVAL + 1 {
+        game_logic.step();
+    }
+
+    let mut recording = game_logic.recording.clone();
+    recording.end_tick = game_logic.clock.tick();
+    let checksum = recording
+        .checksums
+        .iter_mut()
+        .find(|elem| elem.0 == KEYFRAME_INTERVAL)
+        .unwrap();
+    checksum.1 = !checksum.1;
+
+    let (mut replayed, channels) = create_game_logic(recording.settings.clone());
+    replayed.replay(recording);
+    while replayed.is_replaying() {
+        replayed.step();
+    }
+
+    let desyncs: Vec<u64> = channels
+        .critical
+        .try_iter()
+        .filter_map(|msg| match msg {
+            GameLogicMessageCritical::Desync { tick, .. } => Some(tick),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(desyncs, vec![KEYFRAME_INTERVAL]);
+}
+
+#[test]
+fn test_responses_echo_the_request_id() {
+    let (mut game_logic, channels) = create_game_logic(GameLogicSettings {
+        max_nr_ants: 10,
+        ..test_settings(2)
+    });
+
+    let details = |depth: usize| heightmap_generator::HeightMapDetails {
+        pos_0: cgmath::Vector2::new(0, 0),
+        pos_1: cgmath::Vector2::new(1, 1),
+        point_distance: 1,
+        size_0: 4,
+        size_1: 2,
+        nr_tiles: 1,
+        depth,
+        node_index: 0,
+    };
+    let requests = [
+        (1, GameLogicMessageRequest::GetTerrain(details(0))),
+        (2, GameLogicMessageRequest::GetTerrain(details(0))),
+        (3, GameLogicMessageRequest::Cancel(1)), // received before the terrain is generated
+        (4, GameLogicMessageRequest::GetTerrain(details(99))),
+    ];
+    for (id, message) in requests {
+        channels.requests.send(Request { id, message }).unwrap();
+    }
+    for _i in 0..3 {
+        game_logic.step();
+    }
+
+    let terrain: Vec<u64> = channels
+        .heavy
+        .try_iter()
+        .filter_map(|msg| match msg {
+            GameLogicMessageHeavy::Terrain { id, .. } => Some(id),
+            GameLogicMessageHeavy::Pheromones(_) => None,
+        })
+        .collect();
+    assert_eq!(terrain, vec![2]);
+    assert!(matches!(
+        channels.critical.try_recv(),
+        Ok(GameLogicMessageCritical::InvalidRequest { id: 4, .. })
+    ));
+}
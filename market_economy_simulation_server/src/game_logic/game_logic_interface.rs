@@ -3,36 +3,216 @@ use std::sync::mpsc;
 use wgpu_renderer::performance_monitor::watch;
 
 use crate::{
-    ants,
+    ants, clock, factories,
+    game_logic::GameLogicSettings,
     heightmap_generator::{HeightMap, HeightMapDetails},
-    point_lights,
+    market, needs,
+    outbox::{self, ChannelStatistics},
+    pheromones, point_lights,
 };
 
 pub const WATCH_POINT_SIZE: usize = 7;
 
+// ticks between two updates of all entities, in between only the changes are sent
+pub const KEYFRAME_INTERVAL: u64 = 256;
+
+// nr messages the client can fall behind before the game logic keeps them in its backlog
+pub const HEAVY_CHANNEL_CAPACITY: usize = 4;
+pub const MEDIUM_CHANNEL_CAPACITY: usize = 4096;
+pub const LIGHT_CHANNEL_CAPACITY: usize = 16384;
+pub const CRITICAL_CHANNEL_CAPACITY: usize = 64;
+
+// droppable messages are discarded once this many messages wait in a backlog
+pub const BACKLOG_LIMIT: usize = 4096;
+
+// chosen by the client, the responses to a request carry its id
+pub type RequestId = u64;
+
+#[derive(Clone)]
+pub struct Request {
+    pub id: RequestId,
+    pub message: GameLogicMessageRequest,
+}
+
+#[derive(Clone)]
 pub enum GameLogicMessageRequest {
-    GetTerrain(HeightMapDetails), // Requests the terrain heightmap
+    GetTerrain(HeightMapDetails),          // Requests the terrain heightmap
+    Cancel(RequestId), // drops a request that is not processed yet, e.g. a stale terrain tile
+    SetViewPosition(cgmath::Vector2<f32>), // the terrain closest to the camera is generated first
+    Pause,             // stops the simulation clock
+    Resume,            // continues the simulation clock
+    SetTimeScale(f64), // speed of the simulation relative to the real time (0.25 to 16.0)
+    Step,              // advances the simulation by a single tick, also while paused
+    Shutdown,          // stops the simulation, the server thread exits
+    Reset(GameLogicSettings), // restarts the simulation with new settings
+    SaveRecording(String), // writes the requests since the last reset to the file for a replay
+    SaveSnapshot(String), // writes the whole state of the simulation to the file
+    LoadSnapshot(String), // continues the simulation from the state in the file
+    Sync, // sends the whole state to the client, e.g. after it joined a running session
 }
 
 pub enum GameLogicMessageHeavy {
-    Terrain(HeightMap), // The terrain heightmap data
+    Terrain {
+        id: RequestId,
+        height_map: HeightMap,
+    }, // The terrain heightmap data
+    Pheromones(pheromones::PheromoneField), // the intensities of the pheromone trails
 }
 
 pub enum GameLogicMessageMedium {
     UpdateWatchPoints(watch::WatchViewerData<WATCH_POINT_SIZE>), // all the data for a point of the performance monitor
+    UpdateClock(clock::ClockState), // the current tick, sent after the ticks of an update
+    MarketFill(market::Trade),      // a trade executed on the market
+    UpdateFactory(factories::FactoryState), // the state of a factory changed
+    AntBorn(ants::Ant),             // an ant was born
+    AntDied {
+        id: usize,
+        cause: needs::CauseOfDeath,
+    }, // an ant died
+    SnapshotLoaded(GameLogicSettings), // the state was replaced, followed by the alive ants
+    UpdateChannelStatistics(ChannelStatistics), // the delivery of the messages to the client
+    Synced {
+        id: RequestId,
+        settings: GameLogicSettings,
+        clock: clock::ClockState,
+        ants: Vec<ants::Ant>,
+        factories: Vec<factories::FactoryState>,
+    }, // the whole state, replaces the one of the client, followed by the changes
 }
 
 pub enum GameLogicMessageLight {
     UpdatePointLight(point_lights::PointLight), // updates the data of a point light
     UpdateAnt(ants::Ant),                       // updates the data of an ant
+    UpdateAnts(ants::AntBatch), // the changes of many ants, or all of them on a keyframe
+}
+
+pub enum GameLogicMessageCritical {
+    ServerPanicked(String), // the game logic stopped, contains the message of the panic
+    InvalidRequest {
+        id: RequestId,
+        reason: String,
+    }, // a request was refused
+    RequestFailed {
+        id: RequestId,
+        reason: String,
+    }, // a request could not be completed, e.g. writing a file
+    InvariantViolated(String), // the state of the simulation is inconsistent
+    Desync {
+        tick: u64,
+        expected: u64,
+        actual: u64,
+    }, // the checksum of the replayed state differs from the recorded one
+    Error(String),          // an operation failed outside of a request, e.g. starting a replay
+}
+
+impl std::fmt::Display for GameLogicMessageCritical {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameLogicMessageCritical::ServerPanicked(msg) => {
+                write!(f, "the game logic panicked: {}", msg)
+            }
+            GameLogicMessageCritical::InvalidRequest { id, reason } => {
+                write!(f, "invalid request {}: {}", id, reason)
+            }
+            GameLogicMessageCritical::RequestFailed { id, reason } => {
+                write!(f, "request {} failed: {}", id, reason)
+            }
+            GameLogicMessageCritical::InvariantViolated(msg) => {
+                write!(f, "invariant violated: {}", msg)
+            }
+            GameLogicMessageCritical::Desync {
+                tick,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "desync at tick {}: checksum {:016x} instead of {:016x}",
+                tick, actual, expected
+            ),
+            GameLogicMessageCritical::Error(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+// The terrain is requested explicitly, it is never dropped,
+// only the latest pheromone field is needed
+impl outbox::Delivery for GameLogicMessageHeavy {
+    fn key(&self) -> Option<outbox::Key> {
+        match self {
+            GameLogicMessageHeavy::Terrain { .. } => None,
+            GameLogicMessageHeavy::Pheromones(_) => Some((1, 0)),
+        }
+    }
+
+    fn is_droppable(&self) -> bool {
+        false
+    }
+}
+
+// States are replaced by newer ones, events are kept except for the trades
+impl outbox::Delivery for GameLogicMessageMedium {
+    fn key(&self) -> Option<outbox::Key> {
+        match self {
+            GameLogicMessageMedium::UpdateWatchPoints(_) => Some((0, 0)),
+            GameLogicMessageMedium::UpdateClock(_) => Some((1, 0)),
+            GameLogicMessageMedium::UpdateFactory(factory_state) => Some((3, factory_state.id)),
+            GameLogicMessageMedium::UpdateChannelStatistics(_) => Some((7, 0)),
+            GameLogicMessageMedium::MarketFill(_)
+            | GameLogicMessageMedium::AntBorn(_)
+            | GameLogicMessageMedium::AntDied { .. }
+            | GameLogicMessageMedium::SnapshotLoaded(_)
+            | GameLogicMessageMedium::Synced { .. } => None,
+        }
+    }
+
+    fn is_droppable(&self) -> bool {
+        matches!(self, GameLogicMessageMedium::MarketFill(_))
+    }
 }
 
-pub enum GameLogicMessageCritical {}
+// Only the latest state of a light or an ant is needed,
+// the waiting changes of the same ants are merged into one batch
+impl outbox::Delivery for GameLogicMessageLight {
+    fn key(&self) -> Option<outbox::Key> {
+        match self {
+            GameLogicMessageLight::UpdatePointLight(point_light) => {
+                Some((0, point_light.id as usize))
+            }
+            GameLogicMessageLight::UpdateAnt(ant) => Some((1, ant.id)),
+            GameLogicMessageLight::UpdateAnts(batch) => Some((2, batch.chunk)),
+        }
+    }
+
+    fn merge(&mut self, newer: Self) {
+        match (self, newer) {
+            (
+                GameLogicMessageLight::UpdateAnts(batch),
+                GameLogicMessageLight::UpdateAnts(newer),
+            ) => batch.merge(newer),
+            (waiting, newer) => *waiting = newer,
+        }
+    }
+
+    fn is_droppable(&self) -> bool {
+        false
+    }
+}
+
+// Every problem is reported
+impl outbox::Delivery for GameLogicMessageCritical {
+    fn key(&self) -> Option<outbox::Key> {
+        None
+    }
+
+    fn is_droppable(&self) -> bool {
+        false
+    }
+}
 
 pub trait GameLogicInterface {
     fn get_heavy_messages(&self) -> &mpsc::Receiver<GameLogicMessageHeavy>;
     fn get_medium_messages(&self) -> &mpsc::Receiver<GameLogicMessageMedium>;
     fn get_light_messages(&self) -> &mpsc::Receiver<GameLogicMessageLight>;
     fn get_critical_messages(&self) -> &mpsc::Receiver<GameLogicMessageCritical>;
-    fn send_messages(&self) -> &mpsc::Sender<GameLogicMessageRequest>;
+    fn send_messages(&self) -> &mpsc::Sender<Request>;
 }
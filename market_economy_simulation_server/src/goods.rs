@@ -0,0 +1,95 @@
+//! Catalogue of all goods known to the simulation
+//!
+
+pub type GoodId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Piece,
+    Kilogram,
+    Litre,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Perishability {
+    Durable,
+    Perishable { shelf_life: u64 }, // nr ticks until the good spoils
+}
+
+#[derive(Clone, Debug)]
+pub struct Good {
+    pub id: GoodId,
+    pub name: String,
+    pub unit: Unit,
+    pub perishability: Perishability,
+}
+
+pub struct GoodsCatalogue {
+    goods: Vec<Good>, // indexed by the good id
+}
+
+impl Default for GoodsCatalogue {
+    fn default() -> Self {
+        let mut catalogue = Self::new();
+
+        catalogue.add("red", Unit::Kilogram, Perishability::Durable);
+        catalogue.add("green", Unit::Kilogram, Perishability::Durable);
+        catalogue.add("blue", Unit::Kilogram, Perishability::Durable);
+        catalogue.add(
+            "grain",
+            Unit::Kilogram,
+            Perishability::Perishable { shelf_life: 6000 },
+        );
+        catalogue.add(
+            "food",
+            Unit::Piece,
+            Perishability::Perishable { shelf_life: 1200 },
+        );
+        catalogue.add("ore", Unit::Kilogram, Perishability::Durable);
+        catalogue.add("water", Unit::Litre, Perishability::Durable);
+        catalogue.add("wood", Unit::Kilogram, Perishability::Durable);
+
+        catalogue
+    }
+}
+
+impl GoodsCatalogue {
+    pub fn new() -> Self {
+        Self { goods: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: &str, unit: Unit, perishability: Perishability) -> GoodId {
+        let id = self.goods.len();
+        self.goods.push(Good {
+            id,
+            name: name.to_string(),
+            unit,
+            perishability,
+        });
+
+        id
+    }
+
+    pub fn get(&self, id: GoodId) -> &Good {
+        &self.goods[id]
+    }
+
+    pub fn find(&self, name: &str) -> Option<GoodId> {
+        self.goods
+            .iter()
+            .find(|elem| elem.name == name)
+            .map(|elem| elem.id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Good> {
+        self.goods.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.goods.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.goods.is_empty()
+    }
+}
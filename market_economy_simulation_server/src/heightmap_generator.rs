@@ -2,21 +2,18 @@
 
 use noise::NoiseFn;
 
+pub const MAX_DEPTH: usize = 12; // deepest level of the quad tree a tile can be requested for
+pub const MAX_TILE_SIZE: usize = 1027; // max nr points along one side of a tile
+
 #[allow(unused)]
 pub struct HeightMapGenerator {
     perlin: noise::Perlin,
 }
 
-impl Default for HeightMapGenerator {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 #[allow(unused)]
 impl HeightMapGenerator {
-    pub fn new() -> Self {
-        let perlin: noise::Perlin = noise::Perlin::new(1);
+    pub fn new(seed: u32) -> Self {
+        let perlin: noise::Perlin = noise::Perlin::new(seed);
 
         Self { perlin }
     }
@@ -31,45 +28,12 @@ impl HeightMapGenerator {
         let size = size_x * size_y;
 
         let mut heights = Vec::with_capacity(size);
-        for y in 0..size_y {
-            for x in 0..size_x {
-                let mut height = (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 128.0,
-                    (p_y + y as isize * distance as isize) as f64 / 128.0,
-                ]) * 20.0)
-                    .max(0.0);
-
-                height += (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 64.0,
-                    (p_y + y as isize * distance as isize) as f64 / 64.0,
-                ]) * 20.0)
-                    .max(0.0);
-
-                height += (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 32.0,
-                    (p_y + y as isize * distance as isize) as f64 / 32.0,
-                ]) * 20.0)
-                    .max(0.0);
-
-                height += (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 16.0,
-                    (p_y + y as isize * distance as isize) as f64 / 16.0,
-                ]) * 8.0)
-                    .max(0.0);
-
-                height += (self.perlin.get([
-                    (p_x + x as isize * distance as isize) as f64 / 8.0,
-                    (p_y + y as isize * distance as isize) as f64 / 8.0,
-                ]) * 2.0)
-                    .max(0.0);
-
-                // create canyon
-                let a = Self::depth_to_distance(7, 8);
-                height *= Self::canyon(
-                    (p_x + x as isize * distance as isize - a as isize / 2) as f32 / 20.0,
-                ) as f64;
-
-                // heights.push(height as f32);
+        for _y in 0..size_y {
+            for _x in 0..size_x {
+                // heights.push(self.height(
+                //     (p_x + _x as isize * distance as isize) as f64,
+                //     (p_y + _y as isize * distance as isize) as f64,
+                // ) as f32);
                 heights.push(0.0);
             }
         }
@@ -77,6 +41,21 @@ impl HeightMapGenerator {
         HeightMap { heights, details }
     }
 
+    /// Height of the terrain at the world position
+    pub fn height(&self, x: f64, y: f64) -> f64 {
+        let mut height = (self.perlin.get([x / 128.0, y / 128.0]) * 20.0).max(0.0);
+        height += (self.perlin.get([x / 64.0, y / 64.0]) * 20.0).max(0.0);
+        height += (self.perlin.get([x / 32.0, y / 32.0]) * 20.0).max(0.0);
+        height += (self.perlin.get([x / 16.0, y / 16.0]) * 8.0).max(0.0);
+        height += (self.perlin.get([x / 8.0, y / 8.0]) * 2.0).max(0.0);
+
+        // create canyon
+        let a = Self::depth_to_distance(7, 8);
+        height *= Self::canyon((x - a as f64 / 2.0) as f32 / 20.0) as f64;
+
+        height
+    }
+
     fn canyon(x: f32) -> f32 {
         1.0 - 1.0 / (1.0 + x * x * x * x * x * x)
     }
@@ -108,3 +87,36 @@ pub struct HeightMapDetails {
     pub depth: usize,      // Depth of the Node in the quad tree
     pub node_index: usize, // Index of the Node in the quad tree
 }
+
+impl HeightMapDetails {
+    /// Checks the description of a requested tile, returns the reason if it can not be generated
+    pub fn validate(&self) -> Result<(), String> {
+        let max_nr_nodes = (4usize.pow(MAX_DEPTH as u32) - 1) / 3;
+
+        if self.point_distance == 0 {
+            Err("the point distance of the terrain is zero".to_string())
+        } else if self.size_0 < 3 || self.size_0 > MAX_TILE_SIZE {
+            Err(format!(
+                "tile size {} of the terrain is out of range",
+                self.size_0
+            ))
+        } else if self.size_1 != self.size_0 - 2 || self.nr_tiles != self.size_0 - 3 {
+            Err(format!(
+                "tile sizes {}, {} and {} of the terrain do not match",
+                self.size_0, self.size_1, self.nr_tiles
+            ))
+        } else if self.depth >= MAX_DEPTH {
+            Err(format!(
+                "depth {} of the terrain is out of range",
+                self.depth
+            ))
+        } else if self.node_index >= max_nr_nodes {
+            Err(format!(
+                "node {} of the terrain is out of range",
+                self.node_index
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
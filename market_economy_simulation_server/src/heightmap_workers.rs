@@ -0,0 +1,283 @@
+//! Generates the tiles of the heightmap on a pool of worker threads
+//!
+//! The game logic keeps simulating while a burst of requested tiles is generated.
+//! The waiting tiles are generated by priority, coarse tiles first and then the ones
+//! closest to the view. The finished tiles are collected in the order of their completion.
+//! Without threads (wasm32 or no workers) the tiles are generated inline while collecting.
+
+use std::sync::{Arc, Condvar, Mutex, mpsc};
+use std::thread;
+
+use crate::game_logic::game_logic_interface::RequestId;
+use crate::heightmap_generator::{HeightMap, HeightMapDetails, HeightMapGenerator};
+
+struct Job {
+    id: RequestId,
+    details: HeightMapDetails,
+    generator: Arc<HeightMapGenerator>, // the tile is generated with the world it was requested for
+}
+
+struct Queue {
+    jobs: Vec<Job>,
+    view_position: cgmath::Vector2<f32>,
+    is_closed: bool, // the workers exit
+}
+
+impl Queue {
+    /// Removes the waiting job with the highest priority, equal ones in the order of submission
+    fn pop(&mut self) -> Option<Job> {
+        let index = (0..self.jobs.len()).min_by(|a, b| {
+            let a = &self.jobs[*a].details;
+            let b = &self.jobs[*b].details;
+            a.depth.cmp(&b.depth).then(
+                distance_squared(a, self.view_position)
+                    .total_cmp(&distance_squared(b, self.view_position)),
+            )
+        })?;
+
+        Some(self.jobs.remove(index))
+    }
+}
+
+fn distance_squared(details: &HeightMapDetails, view_position: cgmath::Vector2<f32>) -> f32 {
+    let half_size = (details.point_distance * details.size_0) as f32 / 2.0;
+    let dx = details.pos_0.x as f32 + half_size - view_position.x;
+    let dy = details.pos_0.y as f32 + half_size - view_position.y;
+
+    dx * dx + dy * dy
+}
+
+pub struct HeightMapWorkers {
+    nr_threads: usize,
+    threads: Vec<thread::JoinHandle<()>>, // started with the first job
+
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    finished_tx: mpsc::Sender<(RequestId, HeightMap)>,
+    finished_rx: mpsc::Receiver<(RequestId, HeightMap)>,
+}
+
+impl HeightMapWorkers {
+    pub fn new(nr_threads: usize) -> Self {
+        let nr_threads = available_threads(nr_threads);
+
+        let queue = Queue {
+            jobs: Vec::new(),
+            view_position: cgmath::Vector2::new(0.0, 0.0),
+            is_closed: false,
+        };
+        let (finished_tx, finished_rx) = mpsc::channel();
+
+        Self {
+            nr_threads,
+            threads: Vec::new(),
+            queue: Arc::new((Mutex::new(queue), Condvar::new())),
+            finished_tx,
+            finished_rx,
+        }
+    }
+
+    pub fn submit(
+        &mut self,
+        id: RequestId,
+        details: HeightMapDetails,
+        generator: &Arc<HeightMapGenerator>,
+    ) {
+        if self.threads.len() < self.nr_threads {
+            self.start_threads();
+        }
+
+        let (lock, condvar) = &*self.queue;
+        lock.lock().unwrap().jobs.push(Job {
+            id,
+            details,
+            generator: generator.clone(),
+        });
+        condvar.notify_one();
+    }
+
+    /// Drops a waiting job, a tile that is already being generated is still delivered
+    pub fn cancel(&mut self, id: RequestId) {
+        let (lock, _condvar) = &*self.queue;
+        lock.lock().unwrap().jobs.retain(|elem| elem.id != id);
+    }
+
+    /// The waiting tiles closest to this position are generated first
+    pub fn set_view_position(&mut self, view_position: cgmath::Vector2<f32>) {
+        let (lock, _condvar) = &*self.queue;
+        lock.lock().unwrap().view_position = view_position;
+    }
+
+    /// Returns the finished tiles in the order of completion.
+    /// Without threads, the waiting tiles are generated here until the time budget in seconds is used up.
+    pub fn collect(&mut self, time_budget: f64) -> Vec<(RequestId, HeightMap)> {
+        if self.nr_threads == 0 {
+            let start_time = instant::Instant::now();
+            while start_time.elapsed().as_secs_f64() < time_budget {
+                let (lock, _condvar) = &*self.queue;
+                let job = lock.lock().unwrap().pop();
+                let Some(job) = job else {
+                    break;
+                };
+
+                let height_map = job.generator.generate(job.details);
+                let _res = self.finished_tx.send((job.id, height_map));
+            }
+        }
+
+        self.finished_rx.try_iter().collect()
+    }
+
+    /// Changes the number of threads, the waiting tiles are still delivered
+    pub fn resize(&mut self, nr_threads: usize) {
+        self.stop_threads();
+        self.nr_threads = available_threads(nr_threads);
+
+        let (lock, _condvar) = &*self.queue;
+        let has_jobs = !lock.lock().unwrap().jobs.is_empty();
+        if has_jobs {
+            self.start_threads();
+        }
+    }
+
+    fn start_threads(&mut self) {
+        for _i in self.threads.len()..self.nr_threads {
+            let queue = self.queue.clone();
+            let finished_tx = self.finished_tx.clone();
+
+            self.threads
+                .push(thread::spawn(move || work(&queue, &finished_tx)));
+        }
+    }
+
+    /// Waits for the threads to finish their current tile, the waiting tiles are kept
+    fn stop_threads(&mut self) {
+        let (lock, condvar) = &*self.queue;
+        lock.lock().unwrap().is_closed = true;
+        condvar.notify_all();
+
+        for thread in self.threads.drain(..) {
+            let res = thread.join();
+            match res {
+                Ok(_) => {}
+                Err(_err) => log::error!("a heightmap worker panicked"),
+            }
+        }
+
+        lock.lock().unwrap().is_closed = false;
+    }
+}
+
+impl Drop for HeightMapWorkers {
+    fn drop(&mut self) {
+        self.stop_threads();
+    }
+}
+
+/// No threads on the web
+fn available_threads(nr_threads: usize) -> usize {
+    if cfg!(target_arch = "wasm32") {
+        0
+    } else {
+        nr_threads
+    }
+}
+
+/// Generates the waiting tiles until the pool is stopped
+fn work(queue: &(Mutex<Queue>, Condvar), finished_tx: &mpsc::Sender<(RequestId, HeightMap)>) {
+    let (lock, condvar) = queue;
+    loop {
+        let job = {
+            let mut queue = lock.lock().unwrap();
+            loop {
+                if queue.is_closed {
+                    return;
+                }
+                if let Some(job) = queue.pop() {
+                    break job;
+                }
+                queue = condvar.wait(queue).unwrap();
+            }
+        };
+
+        let height_map = job.generator.generate(job.details);
+        let res = finished_tx.send((job.id, height_map));
+        if res.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+fn details(depth: usize, x: isize) -> HeightMapDetails {
+    HeightMapDetails {
+        pos_0: cgmath::Vector2::new(x, 0),
+        pos_1: cgmath::Vector2::new(x + 1, 1),
+        point_distance: 1,
+        size_0: 4,
+        size_1: 2,
+        nr_tiles: 1,
+        depth,
+        node_index: 0,
+    }
+}
+
+#[test]
+fn test_inline_generation_follows_the_priority() {
+    let generator = Arc::new(HeightMapGenerator::new(1));
+    let mut workers = HeightMapWorkers::new(0);
+    workers.set_view_position(cgmath::Vector2::new(100.0, 0.0));
+
+    workers.submit(0, details(2, 0), &generator);
+    workers.submit(1, details(1, 0), &generator);
+    workers.submit(2, details(1, 100), &generator);
+    workers.submit(3, details(0, 0), &generator);
+    workers.cancel(0);
+
+    let ids: Vec<RequestId> = workers
+        .collect(f64::INFINITY)
+        .into_iter()
+        .map(|elem| elem.0)
+        .collect();
+    assert_eq!(ids, vec![3, 2, 1]);
+}
+
+#[test]
+fn test_workers_deliver_all_tiles() {
+    let generator = Arc::new(HeightMapGenerator::new(1));
+    let mut workers = HeightMapWorkers::new(2);
+    for id in 0..8 {
+        workers.submit(id, details(1, id as isize * 10), &generator);
+    }
+
+    let mut ids = Vec::new();
+    for _i in 0..1000 {
+        ids.extend(workers.collect(0.0).into_iter().map(|elem| elem.0));
+        if ids.len() == 8 {
+            break;
+        }
+        thread::sleep(std::time::Duration::from_millis(1));
+    }
+    ids.sort();
+    assert_eq!(ids, (0..8).collect::<Vec<RequestId>>());
+}
+
+#[test]
+fn test_resizing_keeps_the_waiting_tiles() {
+    let generator = Arc::new(HeightMapGenerator::new(1));
+    let mut workers = HeightMapWorkers::new(0);
+    for id in 0..4 {
+        workers.submit(id, details(1, id as isize * 10), &generator);
+    }
+
+    workers.resize(2);
+    let mut ids = Vec::new();
+    for _i in 0..1000 {
+        ids.extend(workers.collect(0.0).into_iter().map(|elem| elem.0));
+        if ids.len() == 4 {
+            break;
+        }
+        thread::sleep(std::time::Duration::from_millis(1));
+    }
+    ids.sort();
+    assert_eq!(ids, (0..4).collect::<Vec<RequestId>>());
+}
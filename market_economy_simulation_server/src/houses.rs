@@ -0,0 +1,147 @@
+//! Houses the ants are living in
+//!
+
+use crate::{
+    spatial_grid::SpatialGrid,
+    wire::{self, Decode, Encode},
+};
+
+const GRID_CELL_SIZE: f32 = 32.0;
+
+pub struct House {
+    pub id: usize,
+    pub pos: cgmath::Vector2<f32>,
+    pub capacity: u32,
+
+    residents: Vec<usize>, // ids of the ants living in the house
+}
+
+impl House {
+    pub fn residents(&self) -> u32 {
+        self.residents.len() as u32
+    }
+
+    pub fn has_space(&self) -> bool {
+        self.residents() < self.capacity
+    }
+}
+
+pub struct Houses {
+    houses: Vec<House>, // indexed by the house id
+    grid: SpatialGrid,  // positions of the houses
+}
+
+impl Default for Houses {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Houses {
+    pub fn new() -> Self {
+        Self {
+            houses: Vec::new(),
+            grid: SpatialGrid::new(GRID_CELL_SIZE),
+        }
+    }
+
+    /// Creates enough houses for the given number of ants
+    pub fn create_default(nr_ants: usize) -> Self {
+        let capacity = 16;
+        let nr_houses = nr_ants.div_ceil(capacity as usize);
+
+        let mut houses = Self::new();
+        for i in 0..nr_houses {
+            let pos = cgmath::Vector2::new(i as f32 * 20.0, -20.0);
+            houses.add(pos, capacity);
+        }
+
+        houses
+    }
+
+    pub fn add(&mut self, pos: cgmath::Vector2<f32>, capacity: u32) -> usize {
+        let id = self.houses.len();
+        self.houses.push(House {
+            id,
+            pos,
+            capacity,
+            residents: Vec::new(),
+        });
+        self.grid.insert(id, pos);
+
+        id
+    }
+
+    pub fn get(&self, id: usize) -> &House {
+        &self.houses[id]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &House> {
+        self.houses.iter()
+    }
+
+    /// Moves the ant into the closest house with space left, returns the id of the house
+    pub fn move_in(&mut self, ant: usize, pos: cgmath::Vector2<f32>) -> Option<usize> {
+        let id = self
+            .grid
+            .nearest_where(pos, |id| self.houses[id].has_space())?;
+        self.houses[id].residents.push(ant);
+
+        Some(id)
+    }
+
+    pub fn move_out(&mut self, house: usize, ant: usize) {
+        self.houses[house].residents.retain(|elem| *elem != ant);
+    }
+
+    /// Checks that the houses are indexed by their id and only the given ants live in them
+    pub fn validate(&self, nr_ants: usize) -> Result<(), wire::DecodeError> {
+        let is_valid = |(id, house): (usize, &House)| {
+            house.id == id && house.residents.iter().all(|ant| *ant < nr_ants)
+        };
+        if !self.houses.iter().enumerate().all(is_valid) {
+            return Err(wire::DecodeError::Inconsistent("houses"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Encode for House {
+    fn encode(&self, writer: &mut wire::Writer) {
+        writer.write_usize(self.id);
+        self.pos.encode(writer);
+        writer.write_u32(self.capacity);
+        self.residents.encode(writer);
+    }
+}
+
+impl Decode for House {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            id: reader.read_usize()?,
+            pos: cgmath::Vector2::decode(reader)?,
+            capacity: reader.read_u32()?,
+            residents: Vec::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Houses {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.houses.encode(writer);
+    }
+}
+
+impl Decode for Houses {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        let houses: Vec<House> = Vec::decode(reader)?;
+
+        let mut grid = SpatialGrid::new(GRID_CELL_SIZE);
+        for (id, house) in houses.iter().enumerate() {
+            grid.insert(id, house.pos);
+        }
+
+        Ok(Self { houses, grid })
+    }
+}
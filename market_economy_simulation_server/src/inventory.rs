@@ -0,0 +1,229 @@
+//! Goods stored by a single entity
+//!
+//! Perishable goods are stored in batches, which are removed when they expire.
+//! Goods are always taken from the oldest batch first.
+
+use std::collections::VecDeque;
+
+use crate::goods::{GoodId, GoodsCatalogue, Perishability};
+use crate::wire::{self, Decode, Encode};
+
+#[derive(Clone, Debug)]
+struct Batch {
+    quantity: u32,
+    expires: Option<u64>, // tick at which the batch spoils
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Inventory {
+    stocks: Vec<VecDeque<Batch>>, // indexed by the good id
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self { stocks: Vec::new() }
+    }
+
+    pub fn quantity(&self, good: GoodId) -> u32 {
+        match self.stocks.get(good) {
+            Some(batches) => batches.iter().map(|elem| elem.quantity).sum(),
+            None => 0,
+        }
+    }
+
+    /// Iterates over all goods in stock and their quantities
+    pub fn iter(&self) -> impl Iterator<Item = (GoodId, u32)> + '_ {
+        (0..self.stocks.len())
+            .map(|good| (good, self.quantity(good)))
+            .filter(|(_good, quantity)| *quantity > 0)
+    }
+
+    pub fn contains(&self, goods: &[(GoodId, u32)]) -> bool {
+        goods
+            .iter()
+            .all(|(good, quantity)| self.quantity(*good) >= *quantity)
+    }
+
+    /// Checks that only known goods are stored
+    pub fn validate(&self, nr_goods: usize) -> Result<(), wire::DecodeError> {
+        if self.stocks.len() > nr_goods {
+            return Err(wire::DecodeError::Inconsistent("goods in the inventory"));
+        }
+
+        Ok(())
+    }
+
+    /// Adds goods produced at the given tick
+    pub fn add(&mut self, catalogue: &GoodsCatalogue, good: GoodId, quantity: u32, tick: u64) {
+        let expires = match catalogue.get(good).perishability {
+            Perishability::Durable => None,
+            Perishability::Perishable { shelf_life } => Some(tick + shelf_life),
+        };
+
+        self.add_batch(good, quantity, expires);
+    }
+
+    /// Keeps the batches ordered by their expiry, the durable ones last
+    fn add_batch(&mut self, good: GoodId, quantity: u32, expires: Option<u64>) {
+        if quantity == 0 {
+            return;
+        }
+
+        if good >= self.stocks.len() {
+            self.stocks.resize(good + 1, VecDeque::new());
+        }
+
+        let expiry = |expires: Option<u64>| expires.unwrap_or(u64::MAX);
+        let batches = &mut self.stocks[good];
+        let pos = batches.partition_point(|batch| expiry(batch.expires) <= expiry(expires));
+        match pos.checked_sub(1).map(|prev| &mut batches[prev]) {
+            Some(prev) if prev.expires == expires => prev.quantity += quantity,
+            _ => batches.insert(pos, Batch { quantity, expires }),
+        }
+    }
+
+    /// Removes up to the given quantity, returns the quantity actually removed
+    pub fn take(&mut self, good: GoodId, quantity: u32) -> u32 {
+        let Some(batches) = self.stocks.get_mut(good) else {
+            return 0;
+        };
+
+        let mut taken = 0;
+        while taken < quantity {
+            let Some(first) = batches.front_mut() else {
+                break;
+            };
+
+            let amount = first.quantity.min(quantity - taken);
+            first.quantity -= amount;
+            taken += amount;
+
+            if first.quantity == 0 {
+                batches.pop_front();
+            }
+        }
+
+        taken
+    }
+
+    /// Moves up to the given quantity into another inventory, keeping the expiry dates
+    pub fn transfer(&mut self, other: &mut Inventory, good: GoodId, quantity: u32) -> u32 {
+        let Some(batches) = self.stocks.get_mut(good) else {
+            return 0;
+        };
+
+        let mut moved = 0;
+        while moved < quantity {
+            let Some(first) = batches.front_mut() else {
+                break;
+            };
+
+            let amount = first.quantity.min(quantity - moved);
+            let expires = first.expires;
+            first.quantity -= amount;
+            moved += amount;
+
+            if first.quantity == 0 {
+                batches.pop_front();
+            }
+
+            other.add_batch(good, amount, expires);
+        }
+
+        moved
+    }
+
+    /// Removes all batches which expired, returns the spoiled quantity
+    pub fn spoil(&mut self, tick: u64) -> u32 {
+        let mut spoiled = 0;
+        for batches in &mut self.stocks {
+            batches.retain(|elem| match elem.expires {
+                Some(expires) if expires <= tick => {
+                    spoiled += elem.quantity;
+                    false
+                }
+                _ => true,
+            });
+        }
+
+        spoiled
+    }
+}
+
+impl Encode for Batch {
+    fn encode(&self, writer: &mut wire::Writer) {
+        writer.write_u32(self.quantity);
+        self.expires.encode(writer);
+    }
+}
+
+impl Decode for Batch {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            quantity: reader.read_u32()?,
+            expires: Option::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Inventory {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.stocks.encode(writer);
+    }
+}
+
+impl Decode for Inventory {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            stocks: Vec::decode(reader)?,
+        })
+    }
+}
+
+#[test]
+fn test_take_oldest_batch_first() {
+    let catalogue = GoodsCatalogue::default();
+    let food = catalogue.find("food").unwrap();
+
+    let mut inventory = Inventory::new();
+    inventory.add(&catalogue, food, 2, 0);
+    inventory.add(&catalogue, food, 3, 100);
+    assert_eq!(inventory.quantity(food), 5);
+
+    assert_eq!(inventory.take(food, 3), 3);
+    assert_eq!(inventory.quantity(food), 2);
+
+    // only the batch of tick 0 would have spoiled, but it is already used up
+    assert_eq!(inventory.spoil(1200), 0);
+    assert_eq!(inventory.spoil(1300), 2);
+    assert_eq!(inventory.quantity(food), 0);
+}
+
+#[test]
+fn test_unknown_goods_are_refused() {
+    let mut inventory = Inventory::new();
+    inventory.add_batch(3, 1, None);
+    assert!(inventory.validate(4).is_ok());
+    assert!(matches!(
+        inventory.validate(3),
+        Err(wire::DecodeError::Inconsistent(_))
+    ));
+}
+
+#[test]
+fn test_transferred_batches_keep_the_oldest_first() {
+    let catalogue = GoodsCatalogue::default();
+    let food = catalogue.find("food").unwrap();
+
+    let mut seller = Inventory::new();
+    seller.add(&catalogue, food, 2, 0);
+    let mut buyer = Inventory::new();
+    buyer.add(&catalogue, food, 3, 100);
+
+    assert_eq!(seller.transfer(&mut buyer, food, 2), 2);
+    assert_eq!(buyer.take(food, 2), 2);
+
+    // the older batch from the seller was taken, the own one is left
+    assert_eq!(buyer.spoil(1200), 0);
+    assert_eq!(buyer.quantity(food), 3);
+}
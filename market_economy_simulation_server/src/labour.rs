@@ -0,0 +1,320 @@
+//! Labour market matching the ants with the jobs of the factories
+//!
+//! Every factory posts a wage for its open jobs. Unemployed ants take the
+//! best paid open job, employed ants quit when another factory pays clearly more.
+//! Factories raise their wage while jobs stay open and slowly lower it when fully staffed.
+//! Ants are paid their wage for every tick they are working, resting ants are not working.
+//! Ants quit if their factory cannot pay them anymore.
+
+use crate::{
+    accounts::{Account, Accounts, EntryKind},
+    ants::Ants,
+    factories::Factories,
+    market::Price,
+    needs::Needs,
+    recipes::Recipes,
+    wire::{self, Decode, Encode},
+};
+
+const INITIAL_WAGE: Price = 10; // per tick worked
+const WAGE_INCREASE_TICKS: u64 = 4; // nr ticks between wage increases of factories with open jobs
+const WAGE_DECREASE_TICKS: u64 = 16; // nr ticks between wage decreases of fully staffed factories
+const QUIT_THRESHOLD: Price = 8; // ants quit if another wage exceeds theirs by 1/QUIT_THRESHOLD
+
+struct Vacancies {
+    wage: Price,
+    required: u32,
+    employees: Vec<usize>, // ids of the employed ants
+}
+
+impl Vacancies {
+    fn openings(&self) -> u32 {
+        self.required.saturating_sub(self.employees.len() as u32)
+    }
+}
+
+pub struct LabourMarket {
+    vacancies: Vec<Vacancies>,     // indexed by the factory id
+    employers: Vec<Option<usize>>, // indexed by the ant id
+}
+
+impl LabourMarket {
+    pub fn new(factories: &Factories, recipes: &Recipes, nr_ants: usize) -> Self {
+        let vacancies = factories
+            .iter()
+            .map(|factory| Vacancies {
+                wage: INITIAL_WAGE,
+                required: recipes.get(factory.recipe).required_workers,
+                employees: Vec::new(),
+            })
+            .collect();
+
+        Self {
+            vacancies,
+            employers: vec![None; nr_ants],
+        }
+    }
+
+    /// Checks that every ant and factory has an entry, that the employments refer to them
+    /// and that no factory has more workers than employees
+    pub fn validate(&self, nr_ants: usize, factories: &Factories) -> Result<(), wire::DecodeError> {
+        let nr_factories = factories.len();
+        if self.employers.len() != nr_ants || self.vacancies.len() != nr_factories {
+            return Err(wire::DecodeError::Inconsistent("size of the labour market"));
+        }
+        let is_employer = |employer: &Option<usize>| employer.is_none_or(|id| id < nr_factories);
+        let is_employee =
+            |vacancies: &Vacancies| vacancies.employees.iter().all(|id| *id < nr_ants);
+        if !self.employers.iter().all(is_employer) || !self.vacancies.iter().all(is_employee) {
+            return Err(wire::DecodeError::Inconsistent("employments"));
+        }
+        let is_staffed = |(factory, vacancies): (&crate::factories::Factory, &Vacancies)| {
+            factory.workers as usize <= vacancies.employees.len()
+        };
+        if !factories.iter().zip(&self.vacancies).all(is_staffed) {
+            return Err(wire::DecodeError::Inconsistent("workers of the factories"));
+        }
+
+        Ok(())
+    }
+
+    /// Factory employing the ant
+    pub fn employer(&self, ant: usize) -> Option<usize> {
+        self.employers[ant]
+    }
+
+    pub fn wage(&self, factory: usize) -> Price {
+        self.vacancies[factory].wage
+    }
+
+    pub fn employees(&self, factory: usize) -> &[usize] {
+        &self.vacancies[factory].employees
+    }
+
+    /// Makes space for a newborn ant, which is unemployed
+    pub fn born(&mut self, ant: usize) {
+        if ant >= self.employers.len() {
+            self.employers.resize(ant + 1, None);
+        }
+    }
+
+    pub fn quit(&mut self, ant: usize) {
+        if let Some(factory) = self.employers[ant].take() {
+            self.vacancies[factory]
+                .employees
+                .retain(|elem| *elem != ant);
+        }
+    }
+
+    fn hire(&mut self, ant: usize, factory: usize) {
+        self.quit(ant);
+        self.vacancies[factory].employees.push(ant);
+        self.employers[ant] = Some(factory);
+    }
+
+    /// Factory with the highest wage and open jobs, the ant picks one of the ties at random
+    fn best_offer(&self, rng: &mut fastrand::Rng) -> Option<(usize, Price)> {
+        let mut best = None;
+        let mut nr_ties = 0;
+        for (id, elem) in self.vacancies.iter().enumerate() {
+            if elem.openings() == 0 {
+                continue;
+            }
+
+            match best {
+                Some((_best_id, best_wage)) if best_wage > elem.wage => {}
+                Some((_best_id, best_wage)) if best_wage == elem.wage => {
+                    // every tie is picked with the same chance
+                    nr_ties += 1;
+                    if rng.usize(..nr_ties) == 0 {
+                        best = Some((id, elem.wage));
+                    }
+                }
+                _ => {
+                    best = Some((id, elem.wage));
+                    nr_ties = 1;
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Adapts the wages, matches the ants with the jobs and pays the working ants
+    pub fn update(
+        &mut self,
+        tick: u64,
+        factories: &mut Factories,
+        ants: &Ants,
+        needs: &Needs,
+        accounts: &mut Accounts,
+        rng: &mut fastrand::Rng,
+    ) {
+        for vacancies in &mut self.vacancies {
+            if vacancies.openings() > 0 {
+                if tick.is_multiple_of(WAGE_INCREASE_TICKS) {
+                    vacancies.wage += 1;
+                }
+            } else if tick.is_multiple_of(WAGE_DECREASE_TICKS) {
+                vacancies.wage = vacancies.wage.saturating_sub(1).max(1);
+            }
+        }
+
+        for ant in 0..self.employers.len() {
+            if !ants.is_alive(ant) {
+                continue;
+            }
+
+            let Some((factory, wage)) = self.best_offer(rng) else {
+                break; // no open jobs left
+            };
+
+            let is_better = match self.employers[ant] {
+                Some(employer) => {
+                    let current = self.vacancies[employer].wage;
+                    wage > current + current / QUIT_THRESHOLD
+                }
+                None => true,
+            };
+
+            if is_better {
+                self.hire(ant, factory);
+            }
+        }
+
+        for (id, vacancies) in self.vacancies.iter_mut().enumerate() {
+            let mut workers = 0;
+            vacancies.employees.retain(|ant| {
+                if needs.get(*ant).is_resting {
+                    return true;
+                }
+
+                let res = accounts.transfer(
+                    tick,
+                    EntryKind::Wage,
+                    Account::Factory(id),
+                    Account::Ant(*ant),
+                    vacancies.wage,
+                );
+                match res {
+                    Ok(_) => {
+                        workers += 1;
+                        true
+                    }
+                    Err(_err) => {
+                        self.employers[*ant] = None;
+                        false
+                    }
+                }
+            });
+
+            let factory = factories.get_mut(id);
+            factory.workers = workers;
+            factory.wage = vacancies.wage;
+        }
+    }
+}
+
+impl Encode for Vacancies {
+    fn encode(&self, writer: &mut wire::Writer) {
+        writer.write_u64(self.wage);
+        writer.write_u32(self.required);
+        self.employees.encode(writer);
+    }
+}
+
+impl Decode for Vacancies {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            wage: reader.read_u64()?,
+            required: reader.read_u32()?,
+            employees: Vec::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for LabourMarket {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.vacancies.encode(writer);
+        self.employers.encode(writer);
+    }
+}
+
+impl Decode for LabourMarket {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            vacancies: Vec::decode(reader)?,
+            employers: Vec::decode(reader)?,
+        })
+    }
+}
+
+#[test]
+fn test_ants_take_the_best_paid_job() {
+    use crate::{goods::GoodsCatalogue, recipes::DEFAULT_RECIPES};
+
+    let catalogue = GoodsCatalogue::default();
+    let recipes = Recipes::new(&catalogue, DEFAULT_RECIPES);
+    let mut factories = Factories::create_default(&catalogue, &recipes);
+
+    let ants = Ants::new(1);
+    let needs = Needs::new(&catalogue, &[None], &mut fastrand::Rng::with_seed(0));
+
+    let mut accounts = Accounts::new();
+    accounts.mint(0, Account::Factory(2), 1000);
+    accounts.mint(0, Account::Factory(3), 1000);
+
+    let mut rng = fastrand::Rng::with_seed(0);
+    let mut labour_market = LabourMarket::new(&factories, &recipes, ants.len());
+    labour_market.vacancies[2].wage = 50;
+
+    labour_market.update(1, &mut factories, &ants, &needs, &mut accounts, &mut rng);
+    assert_eq!(labour_market.employer(0), Some(2));
+    assert_eq!(factories.get(2).workers, 1);
+
+    // a slightly better wage is not worth quitting
+    labour_market.vacancies[3].wage = 52;
+    labour_market.update(1, &mut factories, &ants, &needs, &mut accounts, &mut rng);
+    assert_eq!(labour_market.employer(0), Some(2));
+
+    labour_market.vacancies[3].wage = 100;
+    labour_market.update(1, &mut factories, &ants, &needs, &mut accounts, &mut rng);
+    assert_eq!(labour_market.employer(0), Some(3));
+    assert_eq!(factories.get(2).workers, 0);
+    assert_eq!(factories.get(3).workers, 1);
+    // the wages with open jobs only rise every WAGE_INCREASE_TICKS
+    assert_eq!(accounts.balance(Account::Ant(0)), 50 + 50 + 100);
+    labour_market.update(
+        WAGE_INCREASE_TICKS,
+        &mut factories,
+        &ants,
+        &needs,
+        &mut accounts,
+        &mut rng,
+    );
+    assert_eq!(labour_market.wage(2), 51);
+
+    // an unpaid ant quits
+    accounts.burn(2, Account::Factory(3));
+    labour_market.update(2, &mut factories, &ants, &needs, &mut accounts, &mut rng);
+    assert_eq!(labour_market.employer(0), None);
+    assert_eq!(factories.get(3).workers, 0);
+    assert!(accounts.is_conserved());
+}
+
+#[test]
+fn test_workers_without_employees_are_refused() {
+    use crate::{goods::GoodsCatalogue, recipes::DEFAULT_RECIPES};
+
+    let catalogue = GoodsCatalogue::default();
+    let recipes = Recipes::new(&catalogue, DEFAULT_RECIPES);
+    let mut factories = Factories::create_default(&catalogue, &recipes);
+    let labour_market = LabourMarket::new(&factories, &recipes, 1);
+    assert!(labour_market.validate(1, &factories).is_ok());
+
+    factories.get_mut(2).workers = 1;
+    assert!(matches!(
+        labour_market.validate(1, &factories),
+        Err(wire::DecodeError::Inconsistent(_))
+    ));
+}
@@ -2,26 +2,49 @@
 //! Intended to be easily ported to be used in a multiplayer session,
 //! if this feature may ever be implemented
 
+pub mod accounts;
 pub mod ants;
+pub mod behaviour;
+pub mod clock;
+pub mod factories;
 pub mod game_logic;
+pub mod goods;
 pub mod heightmap_generator;
+pub mod heightmap_workers;
+pub mod houses;
+pub mod inventory;
+pub mod labour;
+pub mod market;
+pub mod movement;
+pub mod needs;
+pub mod network;
+pub mod outbox;
+pub mod pheromones;
 pub mod point_lights;
+pub mod random;
+pub mod recipes;
+pub mod recording;
+pub mod spatial_grid;
 pub mod terrain;
+pub mod wire;
 
 use std::sync::mpsc;
 use std::thread::{self};
 use std::time::Duration;
 
 use game_logic::game_logic_interface::{
-    GameLogicInterface, GameLogicMessageCritical, GameLogicMessageHeavy, GameLogicMessageLight,
-    GameLogicMessageMedium, GameLogicMessageRequest,
+    CRITICAL_CHANNEL_CAPACITY, GameLogicInterface, GameLogicMessageCritical, GameLogicMessageHeavy,
+    GameLogicMessageLight, GameLogicMessageMedium, GameLogicMessageRequest, HEAVY_CHANNEL_CAPACITY,
+    LIGHT_CHANNEL_CAPACITY, MEDIUM_CHANNEL_CAPACITY, Request,
 };
 use game_logic::{GameLogic, GameLogicSettings};
 
 pub struct GameLogicSingleThreaded {
     game_logic: Box<GameLogic>,
+    has_panicked: bool,
+    panic_tx: mpsc::SyncSender<GameLogicMessageCritical>, // reports a panic of the game logic
 
-    channel_0_tx: mpsc::Sender<GameLogicMessageRequest>,
+    channel_0_tx: mpsc::Sender<Request>,
     channel_1_rx: mpsc::Receiver<GameLogicMessageHeavy>,
     channel_2_rx: mpsc::Receiver<GameLogicMessageMedium>,
     channel_3_rx: mpsc::Receiver<GameLogicMessageLight>,
@@ -31,10 +54,11 @@ pub struct GameLogicSingleThreaded {
 impl GameLogicSingleThreaded {
     pub fn new(settings: GameLogicSettings) -> Self {
         let (channel_0_tx, channel_0_rx) = mpsc::channel();
-        let (channel_1_tx, channel_1_rx) = mpsc::channel();
-        let (channel_2_tx, channel_2_rx) = mpsc::channel();
-        let (channel_3_tx, channel_3_rx) = mpsc::channel();
-        let (channel_4_tx, channel_4_rx) = mpsc::channel();
+        let (channel_1_tx, channel_1_rx) = mpsc::sync_channel(HEAVY_CHANNEL_CAPACITY);
+        let (channel_2_tx, channel_2_rx) = mpsc::sync_channel(MEDIUM_CHANNEL_CAPACITY);
+        let (channel_3_tx, channel_3_rx) = mpsc::sync_channel(LIGHT_CHANNEL_CAPACITY);
+        let (channel_4_tx, channel_4_rx) = mpsc::sync_channel(CRITICAL_CHANNEL_CAPACITY);
+        let panic_tx = channel_4_tx.clone();
 
         let game_logic = Box::new(GameLogic::new(
             settings,
@@ -47,6 +71,8 @@ impl GameLogicSingleThreaded {
 
         Self {
             game_logic,
+            has_panicked: false,
+            panic_tx,
             channel_0_tx,
             channel_1_rx,
             channel_2_rx,
@@ -55,8 +81,44 @@ impl GameLogicSingleThreaded {
         }
     }
 
-    fn update(&mut self) {
-        self.game_logic.update();
+    /// Advances the simulation by the elapsed real time
+    pub fn update(&mut self, dt: instant::Duration) {
+        if !self.has_panicked {
+            let game_logic = &mut self.game_logic;
+            self.has_panicked =
+                !catch_panic(&self.panic_tx, || game_logic.update(dt.as_secs_f64()));
+        }
+    }
+
+    /// Advances the simulation by exactly one tick, regardless of the clock
+    pub fn step(&mut self) {
+        if !self.has_panicked {
+            let game_logic = &mut self.game_logic;
+            self.has_panicked = !catch_panic(&self.panic_tx, || game_logic.step());
+        }
+    }
+
+    /// False after a shutdown request or if the game logic panicked
+    pub fn is_running(&self) -> bool {
+        !self.has_panicked && self.game_logic.is_running()
+    }
+
+    /// True until the end of a replayed session is reached
+    pub fn is_replaying(&self) -> bool {
+        !self.has_panicked && self.game_logic.is_replaying()
+    }
+
+    /// Delivery of the messages so far, e.g. the nr of dropped trades
+    pub fn channel_statistics(&self) -> outbox::ChannelStatistics {
+        self.game_logic.channel_statistics()
+    }
+
+    /// Creates the game logic with the recorded settings and reproduces the recorded session
+    pub fn replay(recording: recording::Recording) -> Self {
+        let mut game_logic = Self::new(recording.settings.clone());
+        game_logic.game_logic.replay(recording);
+
+        game_logic
     }
 }
 
@@ -79,15 +141,15 @@ impl GameLogicInterface for GameLogicSingleThreaded {
         &self.channel_4_rx
     }
 
-    fn send_messages(&self) -> &mpsc::Sender<GameLogicMessageRequest> {
+    fn send_messages(&self) -> &mpsc::Sender<Request> {
         &self.channel_0_tx
     }
 }
 
 pub struct GameLogicMultiThreaded {
-    _game_logic: thread::JoinHandle<()>,
+    game_logic: Option<thread::JoinHandle<()>>,
 
-    channel_0_tx: mpsc::Sender<GameLogicMessageRequest>,
+    channel_0_tx: mpsc::Sender<Request>,
     channel_1_rx: mpsc::Receiver<GameLogicMessageHeavy>,
     channel_2_rx: mpsc::Receiver<GameLogicMessageMedium>,
     channel_3_rx: mpsc::Receiver<GameLogicMessageLight>,
@@ -97,10 +159,11 @@ pub struct GameLogicMultiThreaded {
 impl GameLogicMultiThreaded {
     pub fn new(settings: GameLogicSettings) -> Self {
         let (channel_0_tx, channel_0_rx) = mpsc::channel();
-        let (channel_1_tx, channel_1_rx) = mpsc::channel();
-        let (channel_2_tx, channel_2_rx) = mpsc::channel();
-        let (channel_3_tx, channel_3_rx) = mpsc::channel();
-        let (channel_4_tx, channel_4_rx) = mpsc::channel();
+        let (channel_1_tx, channel_1_rx) = mpsc::sync_channel(HEAVY_CHANNEL_CAPACITY);
+        let (channel_2_tx, channel_2_rx) = mpsc::sync_channel(MEDIUM_CHANNEL_CAPACITY);
+        let (channel_3_tx, channel_3_rx) = mpsc::sync_channel(LIGHT_CHANNEL_CAPACITY);
+        let (channel_4_tx, channel_4_rx) = mpsc::sync_channel(CRITICAL_CHANNEL_CAPACITY);
+        let panic_tx = channel_4_tx.clone();
 
         let game_logic = thread::spawn(move || {
             let mut game_logic = GameLogic::new(
@@ -112,14 +175,19 @@ impl GameLogicMultiThreaded {
                 channel_4_tx,
             );
 
-            loop {
+            let interval = Duration::from_secs_f64(clock::TICK_DURATION);
+            let mut last_time = instant::Instant::now();
+            while game_logic.is_running() {
                 let start_time = instant::Instant::now();
-                game_logic.update();
+                let dt = (start_time - last_time).as_secs_f64();
+                if !catch_panic(&panic_tx, || game_logic.update(dt)) {
+                    break;
+                }
+                last_time = start_time;
                 let stop_time = instant::Instant::now();
 
                 let time_passed = stop_time - start_time;
 
-                let interval = Duration::from_millis(16);
                 if time_passed < interval {
                     thread::sleep(interval - time_passed);
                 }
@@ -127,7 +195,7 @@ impl GameLogicMultiThreaded {
         });
 
         Self {
-            _game_logic: game_logic,
+            game_logic: Some(game_logic),
             channel_0_tx,
             channel_1_rx,
             channel_2_rx,
@@ -137,6 +205,32 @@ impl GameLogicMultiThreaded {
     }
 }
 
+impl GameLogicMultiThreaded {
+    /// False after a shutdown request or if the thread panicked
+    pub fn is_running(&self) -> bool {
+        self.game_logic
+            .as_ref()
+            .is_some_and(|elem| !elem.is_finished())
+    }
+}
+
+impl Drop for GameLogicMultiThreaded {
+    fn drop(&mut self) {
+        let _res = self.channel_0_tx.send(Request {
+            id: 0,
+            message: GameLogicMessageRequest::Shutdown,
+        });
+
+        if let Some(game_logic) = self.game_logic.take() {
+            let res = game_logic.join();
+            match res {
+                Ok(_) => {}
+                Err(_err) => log::error!("the game logic thread panicked"),
+            }
+        }
+    }
+}
+
 impl GameLogicInterface for GameLogicMultiThreaded {
     fn get_heavy_messages(&self) -> &mpsc::Receiver<GameLogicMessageHeavy> {
         &self.channel_1_rx
@@ -154,15 +248,38 @@ impl GameLogicInterface for GameLogicMultiThreaded {
         &self.channel_4_rx
     }
 
-    fn send_messages(&self) -> &mpsc::Sender<GameLogicMessageRequest> {
+    fn send_messages(&self) -> &mpsc::Sender<Request> {
         &self.channel_0_tx
     }
 }
 
+/// Runs a part of the game logic, a panic is reported to the client as a critical message.
+/// Returns false if it panicked, the state of the game logic is unusable then.
+fn catch_panic(channel: &mpsc::SyncSender<GameLogicMessageCritical>, f: impl FnOnce()) -> bool {
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    match res {
+        Ok(_) => true,
+        Err(payload) => {
+            let msg = match (
+                payload.downcast_ref::<&str>(),
+                payload.downcast_ref::<String>(),
+            ) {
+                (Some(msg), _) => msg.to_string(),
+                (None, Some(msg)) => msg.clone(),
+                (None, None) => "unknown cause".to_string(),
+            };
+            let _res = channel.try_send(GameLogicMessageCritical::ServerPanicked(msg));
+
+            false
+        }
+    }
+}
+
 enum GameLogicExecution {
     SingleThreaded(GameLogicSingleThreaded),
     #[allow(dead_code)] // unused in wasm
     Multithreaded(GameLogicMultiThreaded),
+    Remote(network::GameLogicNetworkClient),
 }
 
 pub struct GameLogicServer {
@@ -190,14 +307,40 @@ impl GameLogicServer {
         Self { server }
     }
 
-    pub fn update(&mut self) {
+    /// Connects to a game logic running in a server process
+    pub fn connect(addr: &str) -> std::io::Result<Self> {
+        let client = network::GameLogicNetworkClient::connect(addr)?;
+
+        Ok(Self {
+            server: GameLogicExecution::Remote(client),
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        match &self.server {
+            GameLogicExecution::SingleThreaded(game_logic_single_threaded) => {
+                game_logic_single_threaded.is_running()
+            }
+            GameLogicExecution::Multithreaded(game_logic_multi_threaded) => {
+                game_logic_multi_threaded.is_running()
+            }
+            GameLogicExecution::Remote(game_logic_network_client) => {
+                game_logic_network_client.is_connected()
+            }
+        }
+    }
+
+    pub fn update(&mut self, dt: instant::Duration) {
         match &mut self.server {
             GameLogicExecution::SingleThreaded(game_logic_single_threaded) => {
-                game_logic_single_threaded.update();
+                game_logic_single_threaded.update(dt);
             }
             GameLogicExecution::Multithreaded(_game_logic_multi_threaded) => {
                 // update is done in another thread
             }
+            GameLogicExecution::Remote(_game_logic_network_client) => {
+                // update is done in the server process
+            }
         }
     }
 }
@@ -211,6 +354,9 @@ impl GameLogicInterface for GameLogicServer {
             GameLogicExecution::Multithreaded(game_logic_multi_threaded) => {
                 game_logic_multi_threaded.get_heavy_messages()
             }
+            GameLogicExecution::Remote(game_logic_network_client) => {
+                game_logic_network_client.get_heavy_messages()
+            }
         }
     }
 
@@ -222,6 +368,9 @@ impl GameLogicInterface for GameLogicServer {
             GameLogicExecution::Multithreaded(game_logic_multi_threaded) => {
                 game_logic_multi_threaded.get_medium_messages()
             }
+            GameLogicExecution::Remote(game_logic_network_client) => {
+                game_logic_network_client.get_medium_messages()
+            }
         }
     }
 
@@ -233,6 +382,9 @@ impl GameLogicInterface for GameLogicServer {
             GameLogicExecution::Multithreaded(game_logic_multi_threaded) => {
                 game_logic_multi_threaded.get_light_messages()
             }
+            GameLogicExecution::Remote(game_logic_network_client) => {
+                game_logic_network_client.get_light_messages()
+            }
         }
     }
 
@@ -244,10 +396,13 @@ impl GameLogicInterface for GameLogicServer {
             GameLogicExecution::Multithreaded(game_logic_multi_threaded) => {
                 game_logic_multi_threaded.get_critical_messages()
             }
+            GameLogicExecution::Remote(game_logic_network_client) => {
+                game_logic_network_client.get_critical_messages()
+            }
         }
     }
 
-    fn send_messages(&self) -> &mpsc::Sender<GameLogicMessageRequest> {
+    fn send_messages(&self) -> &mpsc::Sender<Request> {
         match &self.server {
             GameLogicExecution::SingleThreaded(game_logic_single_threaded) => {
                 game_logic_single_threaded.send_messages()
@@ -255,6 +410,9 @@ impl GameLogicInterface for GameLogicServer {
             GameLogicExecution::Multithreaded(game_logic_multi_threaded) => {
                 game_logic_multi_threaded.send_messages()
             }
+            GameLogicExecution::Remote(game_logic_network_client) => {
+                game_logic_network_client.send_messages()
+            }
         }
     }
 }
@@ -0,0 +1,470 @@
+//! Order books of all goods traded on the market
+//!
+//! Traders post bids and asks, which are matched once per tick.
+//! Orders are matched by price-time priority, the trade is executed
+//! at the price of the older (resting) order.
+
+use std::collections::VecDeque;
+
+use crate::goods::GoodId;
+use crate::wire::{self, Decode, Encode};
+
+pub type Price = u64; // price per unit in the smallest currency unit
+pub type OrderId = u64;
+
+const PRICE_HISTORY_SIZE: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Bid, // wants to buy
+    Ask, // wants to sell
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trader {
+    Ant(usize),
+    Factory(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Order {
+    pub id: OrderId,
+    pub trader: Trader,
+    pub good: GoodId,
+    pub side: Side,
+    pub price: Price,
+    pub quantity: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Trade {
+    pub tick: u64,
+    pub good: GoodId,
+    pub price: Price,
+    pub quantity: u32,
+
+    pub buyer: Trader,
+    pub seller: Trader,
+    pub bid: OrderId,
+    pub ask: OrderId,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PricePoint {
+    pub tick: u64,
+    pub last_trade: Option<Price>,
+    pub mid: Option<Price>,
+}
+
+/// All open orders of a single good
+pub struct OrderBook {
+    good: GoodId,
+
+    bids: Vec<Order>, // sorted by price descending, then by age
+    asks: Vec<Order>, // sorted by price ascending, then by age
+
+    last_trade: Option<Price>,
+    history: VecDeque<PricePoint>,
+}
+
+impl OrderBook {
+    pub fn new(good: GoodId) -> Self {
+        Self {
+            good,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            last_trade: None,
+            history: VecDeque::with_capacity(PRICE_HISTORY_SIZE),
+        }
+    }
+
+    fn insert(&mut self, order: Order) {
+        // order ids are increasing, so inserting after all orders with
+        // an equal price keeps the time priority
+        match order.side {
+            Side::Bid => {
+                let index = self.bids.partition_point(|elem| elem.price >= order.price);
+                self.bids.insert(index, order);
+            }
+            Side::Ask => {
+                let index = self.asks.partition_point(|elem| elem.price <= order.price);
+                self.asks.insert(index, order);
+            }
+        }
+    }
+
+    fn cancel(&mut self, id: OrderId) -> Option<Order> {
+        if let Some(index) = self.bids.iter().position(|elem| elem.id == id) {
+            return Some(self.bids.remove(index));
+        }
+        if let Some(index) = self.asks.iter().position(|elem| elem.id == id) {
+            return Some(self.asks.remove(index));
+        }
+
+        None
+    }
+
+    /// Matches crossing orders until the book is no longer crossed
+    fn match_orders(&mut self, tick: u64, trades: &mut Vec<Trade>) {
+        while let (Some(bid), Some(ask)) = (self.bids.first_mut(), self.asks.first_mut()) {
+            if bid.price < ask.price {
+                break;
+            }
+
+            let price = if bid.id < ask.id {
+                bid.price
+            } else {
+                ask.price
+            };
+            let quantity = bid.quantity.min(ask.quantity);
+
+            bid.quantity -= quantity;
+            ask.quantity -= quantity;
+
+            trades.push(Trade {
+                tick,
+                good: self.good,
+                price,
+                quantity,
+                buyer: bid.trader,
+                seller: ask.trader,
+                bid: bid.id,
+                ask: ask.id,
+            });
+
+            let bid_filled = bid.quantity == 0;
+            let ask_filled = ask.quantity == 0;
+
+            if bid_filled {
+                self.bids.remove(0);
+            }
+            if ask_filled {
+                self.asks.remove(0);
+            }
+
+            self.last_trade = Some(price);
+        }
+    }
+
+    fn record_price(&mut self, tick: u64) {
+        if self.history.len() == PRICE_HISTORY_SIZE {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(PricePoint {
+            tick,
+            last_trade: self.last_trade,
+            mid: self.mid_price(),
+        });
+    }
+
+    pub fn best_bid(&self) -> Option<Price> {
+        self.bids.first().map(|elem| elem.price)
+    }
+
+    pub fn best_ask(&self) -> Option<Price> {
+        self.asks.first().map(|elem| elem.price)
+    }
+
+    pub fn mid_price(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2),
+            _ => None,
+        }
+    }
+
+    pub fn last_trade(&self) -> Option<Price> {
+        self.last_trade
+    }
+
+    pub fn history(&self) -> &VecDeque<PricePoint> {
+        &self.history
+    }
+}
+
+/// Orders of a single trader, which are re-posted every tick
+///
+/// The reservation prices are adapted to whether the orders of the last tick were filled.
+#[derive(Clone, Debug)]
+pub struct Quotes {
+    orders: Vec<(GoodId, Side, OrderId)>, // orders posted in the last tick
+    prices: Vec<Price>,                   // reservation price indexed by the good id
+}
+
+impl Quotes {
+    pub fn new(nr_goods: usize, initial_price: Price) -> Self {
+        Self {
+            orders: Vec::new(),
+            prices: vec![initial_price; nr_goods],
+        }
+    }
+
+    pub fn price(&self, good: GoodId) -> Price {
+        self.prices[good]
+    }
+
+    /// Checks that there is a price for every good and the orders are for known goods
+    pub fn validate(&self, nr_goods: usize) -> Result<(), wire::DecodeError> {
+        if self.prices.len() != nr_goods {
+            return Err(wire::DecodeError::Inconsistent("number of quoted prices"));
+        }
+        if self
+            .orders
+            .iter()
+            .any(|(good, _side, _id)| *good >= nr_goods)
+        {
+            return Err(wire::DecodeError::Inconsistent(
+                "goods of the quoted orders",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Lowers the reservation price to the given maximum, e.g. the available money
+    pub fn limit_price(&mut self, good: GoodId, max_price: Price) {
+        let price = &mut self.prices[good];
+        *price = (*price).min(max_price).max(1);
+    }
+
+    /// Cancels the remaining orders of the last tick and adapts the prices
+    pub fn update_prices(&mut self, market: &mut Market) {
+        for (good, side, id) in self.orders.drain(..) {
+            let is_filled = market.cancel_order(good, id).is_none();
+            let price = &mut self.prices[good];
+
+            *price = match (side, is_filled) {
+                (Side::Bid, true) | (Side::Ask, false) => (*price - *price / 16 - 1).max(1),
+                (Side::Bid, false) | (Side::Ask, true) => *price + *price / 16 + 1,
+            };
+        }
+    }
+
+    /// Posts an order at the reservation price
+    pub fn post(
+        &mut self,
+        market: &mut Market,
+        trader: Trader,
+        good: GoodId,
+        side: Side,
+        quantity: u32,
+    ) {
+        if let Some(id) = market.post_order(trader, good, side, self.prices[good], quantity) {
+            self.orders.push((good, side, id));
+        }
+    }
+
+    /// Cancels all open orders without adapting the prices
+    pub fn cancel(&mut self, market: &mut Market) {
+        for (good, _side, id) in self.orders.drain(..) {
+            market.cancel_order(good, id);
+        }
+    }
+}
+
+pub struct Market {
+    order_books: Vec<OrderBook>, // indexed by the good id
+
+    next_order_id: OrderId,
+
+    trades: Vec<Trade>,
+}
+
+impl Market {
+    pub fn new(nr_goods: usize) -> Self {
+        let order_books = (0..nr_goods).map(OrderBook::new).collect();
+
+        Self {
+            order_books,
+            next_order_id: 0,
+            trades: Vec::new(),
+        }
+    }
+
+    /// Adds an order to the order book, it is matched during the next update
+    pub fn post_order(
+        &mut self,
+        trader: Trader,
+        good: GoodId,
+        side: Side,
+        price: Price,
+        quantity: u32,
+    ) -> Option<OrderId> {
+        if good >= self.order_books.len() || quantity == 0 {
+            return None;
+        }
+
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+
+        self.order_books[good].insert(Order {
+            id,
+            trader,
+            good,
+            side,
+            price,
+            quantity,
+        });
+
+        Some(id)
+    }
+
+    /// Removes the remaining quantity of an order from the order book
+    pub fn cancel_order(&mut self, good: GoodId, id: OrderId) -> Option<Order> {
+        self.order_books.get_mut(good)?.cancel(id)
+    }
+
+    pub fn nr_goods(&self) -> usize {
+        self.order_books.len()
+    }
+
+    /// Checks that there is an order book for every good and that the traders exist
+    pub fn validate(
+        &self,
+        nr_goods: usize,
+        nr_ants: usize,
+        nr_factories: usize,
+    ) -> Result<(), wire::DecodeError> {
+        if self.order_books.len() != nr_goods {
+            return Err(wire::DecodeError::Inconsistent("number of order books"));
+        }
+
+        let is_valid = |order: &Order| match order.trader {
+            Trader::Ant(id) => id < nr_ants,
+            Trader::Factory(id) => id < nr_factories,
+        };
+        let mut orders = self
+            .order_books
+            .iter()
+            .flat_map(|order_book| order_book.bids.iter().chain(&order_book.asks));
+        if !orders.all(is_valid) {
+            return Err(wire::DecodeError::Inconsistent("traders of the orders"));
+        }
+
+        Ok(())
+    }
+
+    pub fn order_book(&self, good: GoodId) -> &OrderBook {
+        &self.order_books[good]
+    }
+
+    /// Clears all crossing orders, the trades are settled and reported by the caller
+    pub fn update(&mut self, tick: u64) -> &[Trade] {
+        self.trades.clear();
+
+        for order_book in &mut self.order_books {
+            order_book.match_orders(tick, &mut self.trades);
+            order_book.record_price(tick);
+        }
+
+        &self.trades
+    }
+}
+
+impl Encode for OrderBook {
+    fn encode(&self, writer: &mut wire::Writer) {
+        writer.write_usize(self.good);
+        self.bids.encode(writer);
+        self.asks.encode(writer);
+        self.last_trade.encode(writer);
+        self.history.encode(writer);
+    }
+}
+
+impl Decode for OrderBook {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            good: reader.read_usize()?,
+            bids: Vec::decode(reader)?,
+            asks: Vec::decode(reader)?,
+            last_trade: Option::decode(reader)?,
+            history: VecDeque::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Quotes {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.orders.encode(writer);
+        self.prices.encode(writer);
+    }
+}
+
+impl Decode for Quotes {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            orders: Vec::decode(reader)?,
+            prices: Vec::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Market {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.order_books.encode(writer);
+        writer.write_u64(self.next_order_id);
+    }
+}
+
+impl Decode for Market {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self {
+            order_books: Vec::decode(reader)?,
+            next_order_id: reader.read_u64()?,
+            trades: Vec::new(),
+        })
+    }
+}
+
+#[test]
+fn test_crossing_orders_trade_at_resting_price() {
+    let mut market = Market::new(1);
+
+    market.post_order(Trader::Factory(0), 0, Side::Ask, 10, 5);
+    market.post_order(Trader::Ant(0), 0, Side::Bid, 12, 3);
+
+    let trades = market.update(0);
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].price, 10);
+    assert_eq!(trades[0].quantity, 3);
+    assert_eq!(trades[0].buyer, Trader::Ant(0));
+    assert_eq!(trades[0].seller, Trader::Factory(0));
+
+    let order_book = market.order_book(0);
+    assert_eq!(order_book.best_bid(), None);
+    assert_eq!(order_book.best_ask(), Some(10));
+    assert_eq!(order_book.last_trade(), Some(10));
+}
+
+#[test]
+fn test_price_time_priority() {
+    let mut market = Market::new(1);
+
+    market.post_order(Trader::Ant(0), 0, Side::Bid, 9, 1);
+    market.post_order(Trader::Ant(1), 0, Side::Bid, 11, 1);
+    market.post_order(Trader::Ant(2), 0, Side::Bid, 11, 1);
+    market.post_order(Trader::Factory(0), 0, Side::Ask, 8, 2);
+
+    let trades = market.update(0);
+    assert_eq!(trades.len(), 2);
+    assert_eq!(trades[0].buyer, Trader::Ant(1));
+    assert_eq!(trades[1].buyer, Trader::Ant(2));
+    assert_eq!(trades[0].price, 11);
+
+    let order_book = market.order_book(0);
+    assert_eq!(order_book.best_bid(), Some(9));
+    assert_eq!(order_book.mid_price(), None);
+    assert_eq!(order_book.history().len(), 1);
+}
+
+#[test]
+fn test_orders_of_unknown_goods_are_refused() {
+    let mut quotes = Quotes::new(2, 10);
+    assert!(quotes.validate(2).is_ok());
+
+    quotes.orders.push((2, Side::Bid, 0));
+    assert!(matches!(
+        quotes.validate(2),
+        Err(wire::DecodeError::Inconsistent(_))
+    ));
+}
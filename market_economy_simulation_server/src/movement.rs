@@ -0,0 +1,341 @@
+//! Moves the ants toward their goals over the terrain
+//!
+//! The area of the world is divided into a grid of cells. Walking between two cells costs
+//! their distance, raised by the slope of the terrain, too steep slopes cannot be walked.
+//! For every goal a flow field is calculated once, pointing from every cell to the next cell
+//! on the cheapest path. The ants follow the flow field, keep their distance to each other
+//! and turn toward their heading. Foraging ants are also drawn along the pheromone trails,
+//! as long as a trail does not lead away from their path.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use cgmath::InnerSpace;
+
+use crate::ants::Ants;
+use crate::behaviour::Task;
+use crate::heightmap_generator::HeightMapGenerator;
+use crate::pheromones::Pheromones;
+
+const CELL_SIZE: f32 = 4.0;
+const SLOPE_COST: f32 = 8.0; // extra cost of walking one unit up or down, relative to one unit on the flat
+const MAX_SLOPE: f32 = 1.0; // height difference per distance, steeper cells are not connected
+const MAX_FLOW_FIELDS: usize = 64; // the least recently used flow field is dropped above this
+
+const WALK_SPEED: f32 = 8.0; // units per second
+const TURN_SPEED: f32 = 6.0; // radians per second
+const ARRIVAL_DISTANCE: f32 = 1.0; // ants closer to their goal stop
+const SEPARATION_DISTANCE: f32 = 1.5; // ants closer to each other push apart
+const TRAIL_WEIGHT: f32 = 0.5; // pull of a pheromone trail, relative to the pull of the flow field
+const MIN_TRAIL_GRADIENT: f32 = 1e-3; // weaker rises of the intensity are not noticed
+
+const NO_CELL: u32 = u32::MAX;
+
+/// Cells of the walkable area, with the cost of walking to each neighbour
+struct NavigationGrid {
+    origin: cgmath::Vector2<f32>, // lower corner of the cell (0/0)
+    size_x: usize,
+    size_y: usize,
+    heights: Vec<f32>, // at the middle of the cells
+}
+
+impl NavigationGrid {
+    fn new(
+        generator: &HeightMapGenerator,
+        origin: cgmath::Vector2<f32>,
+        size_x: usize,
+        size_y: usize,
+    ) -> Self {
+        let mut heights = Vec::with_capacity(size_x * size_y);
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let middle =
+                    origin + cgmath::Vector2::new(x as f32 + 0.5, y as f32 + 0.5) * CELL_SIZE;
+                heights.push(generator.height(middle.x as f64, middle.y as f64) as f32);
+            }
+        }
+
+        Self {
+            origin,
+            size_x,
+            size_y,
+            heights,
+        }
+    }
+
+    fn cell(&self, pos: cgmath::Vector2<f32>) -> usize {
+        let local = (pos - self.origin) / CELL_SIZE;
+        let x = (local.x.max(0.0) as usize).min(self.size_x - 1);
+        let y = (local.y.max(0.0) as usize).min(self.size_y - 1);
+
+        y * self.size_x + x
+    }
+
+    fn middle(&self, cell: usize) -> cgmath::Vector2<f32> {
+        let x = (cell % self.size_x) as f32 + 0.5;
+        let y = (cell / self.size_x) as f32 + 0.5;
+
+        self.origin + cgmath::Vector2::new(x, y) * CELL_SIZE
+    }
+
+    fn clamp(&self, pos: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        let max_x = self.origin.x + self.size_x as f32 * CELL_SIZE;
+        let max_y = self.origin.y + self.size_y as f32 * CELL_SIZE;
+
+        cgmath::Vector2::new(
+            pos.x.clamp(self.origin.x, max_x),
+            pos.y.clamp(self.origin.y, max_y),
+        )
+    }
+
+    /// Walkable neighbours of the cell with the cost to walk there
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+        const OFFSETS: [(isize, isize, u32); 8] = [
+            (-1, 0, 10),
+            (1, 0, 10),
+            (0, -1, 10),
+            (0, 1, 10),
+            (-1, -1, 14),
+            (1, -1, 14),
+            (-1, 1, 14),
+            (1, 1, 14),
+        ];
+
+        let x = (cell % self.size_x) as isize;
+        let y = (cell / self.size_x) as isize;
+
+        OFFSETS.iter().filter_map(move |(dx, dy, distance)| {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= self.size_x as isize || ny >= self.size_y as isize {
+                return None;
+            }
+            let neighbour = ny as usize * self.size_x + nx as usize;
+
+            let rise = (self.heights[neighbour] - self.heights[cell]).abs();
+            let run = *distance as f32 / 10.0 * CELL_SIZE;
+            if rise / run > MAX_SLOPE {
+                return None;
+            }
+
+            let cost = *distance as f32 * (1.0 + SLOPE_COST * rise / run);
+            Some((neighbour, cost as u32))
+        })
+    }
+
+    /// Dijkstra from the goal, every cell points to its next cell on the cheapest path
+    fn flow_field(&self, goal: usize) -> Vec<u32> {
+        let mut costs = vec![u32::MAX; self.heights.len()];
+        let mut next = vec![NO_CELL; self.heights.len()];
+        let mut queue = BinaryHeap::new();
+
+        costs[goal] = 0;
+        queue.push(Reverse((0, goal)));
+        while let Some(Reverse((cost, cell))) = queue.pop() {
+            if cost > costs[cell] {
+                continue;
+            }
+
+            // the costs are symmetric, so the path back to the goal costs the same
+            for (neighbour, step) in self.neighbours(cell) {
+                let new_cost = cost + step;
+                if new_cost < costs[neighbour] {
+                    costs[neighbour] = new_cost;
+                    next[neighbour] = cell as u32;
+                    queue.push(Reverse((new_cost, neighbour)));
+                }
+            }
+        }
+
+        next
+    }
+}
+
+pub struct Movement {
+    grid: NavigationGrid,
+    flow_fields: BTreeMap<usize, (Vec<u32>, u64)>, // by the goal cell, with the tick it was last used
+}
+
+impl Movement {
+    /// Covers the area between the corners
+    pub fn new(
+        generator: &HeightMapGenerator,
+        min: cgmath::Vector2<f32>,
+        max: cgmath::Vector2<f32>,
+    ) -> Self {
+        let size_x = (((max.x - min.x) / CELL_SIZE).ceil() as usize).max(1);
+        let size_y = (((max.y - min.y) / CELL_SIZE).ceil() as usize).max(1);
+        let grid = NavigationGrid::new(generator, min, size_x, size_y);
+
+        Self {
+            grid,
+            flow_fields: BTreeMap::new(),
+        }
+    }
+
+    /// Moves the alive ants toward their goals, indexed by the ant id
+    pub fn update(
+        &mut self,
+        tick: u64,
+        dt: f32,
+        ants: &mut Ants,
+        goals: &[Option<cgmath::Vector2<f32>>],
+        pheromones: &Pheromones,
+    ) {
+        let mut moves = Vec::new();
+        for (id, goal) in goals.iter().enumerate() {
+            let Some(goal) = goal else {
+                continue;
+            };
+            if !ants.is_alive(id) {
+                continue;
+            }
+
+            let pos = ants.get(id).pos;
+            if (goal - pos).magnitude() < ARRIVAL_DISTANCE {
+                continue;
+            }
+
+            let target = self.waypoint(tick, pos, *goal);
+            let direction = (target - pos).normalize();
+            let mut velocity = direction + separation(id, pos, ants);
+            if ants.get(id).task == Task::Forage {
+                let pull = trail(pos, pheromones);
+                if pull.dot(direction) > 0.0 {
+                    velocity += pull;
+                }
+            }
+            if velocity.magnitude2() == 0.0 {
+                continue;
+            }
+            let velocity = velocity.normalize() * WALK_SPEED;
+
+            let new_pos = self.grid.clamp(pos + velocity * dt);
+            let rot_z = turn(
+                ants.get(id).rot_z,
+                velocity.y.atan2(velocity.x),
+                TURN_SPEED * dt,
+            );
+            moves.push((id, new_pos, rot_z));
+        }
+
+        for (id, pos, rot_z) in moves {
+            ants.set_pos(id, pos, rot_z);
+        }
+
+        self.drop_unused_flow_fields();
+    }
+
+    /// The point to walk to next, one step along the path from the cell of the ant or the goal itself.
+    /// Following the direction between the middles of the cells keeps an ant at the border of two
+    /// cells from swinging back and forth between their next cells.
+    fn waypoint(
+        &mut self,
+        tick: u64,
+        pos: cgmath::Vector2<f32>,
+        goal: cgmath::Vector2<f32>,
+    ) -> cgmath::Vector2<f32> {
+        let goal_cell = self.grid.cell(goal);
+        let cell = self.grid.cell(pos);
+        if cell == goal_cell {
+            return goal;
+        }
+
+        let grid = &self.grid;
+        let (flow_field, last_used) = self
+            .flow_fields
+            .entry(goal_cell)
+            .or_insert_with(|| (grid.flow_field(goal_cell), tick));
+        *last_used = tick;
+
+        match flow_field[cell] {
+            NO_CELL => goal, // unreachable, walks straight
+            next => pos + (self.grid.middle(next as usize) - self.grid.middle(cell)),
+        }
+    }
+
+    fn drop_unused_flow_fields(&mut self) {
+        while self.flow_fields.len() > MAX_FLOW_FIELDS {
+            let oldest = self
+                .flow_fields
+                .iter()
+                .min_by_key(|(_goal, (_flow_field, last_used))| *last_used)
+                .map(|(goal, _elem)| *goal);
+            if let Some(goal) = oldest {
+                self.flow_fields.remove(&goal);
+            }
+        }
+    }
+}
+
+/// Pushes the ant away from the ants around it
+fn separation(id: usize, pos: cgmath::Vector2<f32>, ants: &Ants) -> cgmath::Vector2<f32> {
+    let mut push = cgmath::Vector2::new(0.0, 0.0);
+    ants.visit_radius(pos, SEPARATION_DISTANCE, |other, other_pos| {
+        let offset = pos - other_pos;
+        let distance = offset.magnitude();
+        if other != id && distance > 0.0 && distance < SEPARATION_DISTANCE {
+            push += offset / distance * (1.0 - distance / SEPARATION_DISTANCE);
+        }
+    });
+
+    push
+}
+
+/// Pulls the ant up the gradient of the pheromones
+fn trail(pos: cgmath::Vector2<f32>, pheromones: &Pheromones) -> cgmath::Vector2<f32> {
+    let gradient = pheromones.gradient(pos);
+    if gradient.magnitude() < MIN_TRAIL_GRADIENT {
+        return cgmath::Vector2::new(0.0, 0.0);
+    }
+
+    gradient.normalize() * TRAIL_WEIGHT
+}
+
+/// Turns the angle toward the target angle by at most the step, in radians within (-π, π]
+fn turn(angle: f32, target: f32, step: f32) -> f32 {
+    let difference = (target - angle + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+        - std::f32::consts::PI;
+    let turned = angle + difference.clamp(-step, step);
+
+    std::f32::consts::PI - (std::f32::consts::PI - turned).rem_euclid(std::f32::consts::TAU)
+}
+
+#[test]
+fn test_ants_walk_around_a_ridge() {
+    let generator = HeightMapGenerator::new(3);
+    let (min, max) = (
+        cgmath::Vector2::new(0.0, 0.0),
+        cgmath::Vector2::new(40.0, 40.0),
+    );
+    let mut movement = Movement::new(&generator, min, max);
+    let pheromones = Pheromones::new(min, max);
+
+    // a wall across the middle with a gap at the top
+    for y in 0..movement.grid.size_y - 1 {
+        let cell = y * movement.grid.size_x + 5;
+        movement.grid.heights[cell] = 100.0;
+    }
+
+    let mut ants = Ants::new(1);
+    ants.set_pos(0, cgmath::Vector2::new(2.0, 2.0), 0.0);
+    let goal = cgmath::Vector2::new(38.0, 2.0);
+
+    let mut max_y: f32 = 0.0;
+    for tick in 0..2000 {
+        movement.update(tick, 0.016, &mut ants, &[Some(goal)], &pheromones);
+        max_y = max_y.max(ants.get(0).pos.y);
+    }
+
+    assert!((ants.get(0).pos - goal).magnitude() < ARRIVAL_DISTANCE);
+    assert!(max_y > 32.0); // passed the gap
+}
+
+#[test]
+fn test_turn_takes_the_short_way() {
+    let angle = turn(3.0, -3.0, 0.1);
+    assert!(angle > 3.0);
+
+    // turning past π continues from -π
+    let angle = turn(3.1, -3.1, 0.1);
+    assert!(angle > -std::f32::consts::PI && angle < -3.0);
+}
@@ -0,0 +1,299 @@
+//! Needs of the ants, which decay over time
+//!
+//! Hungry ants eat food from their inventory or bid for food on the market,
+//! tired ants walk home and only recover once they rest there, ants without a home
+//! rest where they are, which is slower. Starving and exhausted ants lose health
+//! and die when it is used up, the same happens when they get too old.
+
+use crate::{
+    accounts::{Account, Accounts},
+    ants::Ants,
+    behaviour::Task,
+    goods::{GoodId, GoodsCatalogue},
+    market::{self, Quotes, Side, Trader},
+    wire::{self, Decode, Encode},
+};
+
+const MAX_VALUE: f32 = 100.0;
+
+const FOOD_DECAY: f32 = 2.0; // per second
+const FOOD_PER_MEAL: f32 = 50.0;
+const HUNGRY: f32 = 50.0;
+
+const SLEEP_DECAY: f32 = 1.0; // per second
+const SLEEP_RECOVERY_HOME: f32 = 10.0; // per second
+const SLEEP_RECOVERY_OUTSIDE: f32 = 4.0; // per second
+const TIRED: f32 = 20.0;
+
+const STARVATION_DAMAGE: f32 = 5.0; // per second
+const HEALTH_RECOVERY: f32 = 1.0; // per second
+
+const LIFESPAN: f64 = 600.0; // seconds
+
+const INITIAL_FOOD_PRICE: market::Price = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CauseOfDeath {
+    Starvation,
+    Exhaustion,
+    Age,
+}
+
+#[derive(Clone, Debug)]
+pub struct LiveStates {
+    pub age: f64, // seconds
+    pub health: f32,
+    pub sleep: f32,
+    pub food: f32,
+
+    pub home: Option<usize>, // id of the house
+    pub is_resting: bool,
+}
+
+impl LiveStates {
+    pub fn new(home: Option<usize>) -> Self {
+        Self {
+            age: 0.0,
+            health: MAX_VALUE,
+            sleep: MAX_VALUE,
+            food: MAX_VALUE,
+            home,
+            is_resting: false,
+        }
+    }
+
+    /// Advances the needs by the elapsed simulation time, returns if the ant died.
+    /// A resting ant only recovers at its resting place, it gets more tired on the way there.
+    fn update(&mut self, dt: f32, is_at_rest: bool) -> Option<CauseOfDeath> {
+        self.age += dt as f64;
+        self.food = (self.food - FOOD_DECAY * dt).max(0.0);
+
+        if self.is_resting && is_at_rest {
+            let recovery = match self.home {
+                Some(_) => SLEEP_RECOVERY_HOME,
+                None => SLEEP_RECOVERY_OUTSIDE,
+            };
+            self.sleep = (self.sleep + recovery * dt).min(MAX_VALUE);
+            self.is_resting = self.sleep < MAX_VALUE;
+        } else {
+            self.sleep = (self.sleep - SLEEP_DECAY * dt).max(0.0);
+            self.is_resting |= self.sleep < TIRED;
+        }
+
+        if self.food <= 0.0 || self.sleep <= 0.0 {
+            self.health -= STARVATION_DAMAGE * dt;
+        } else {
+            self.health = (self.health + HEALTH_RECOVERY * dt).min(MAX_VALUE);
+        }
+
+        if self.health <= 0.0 && self.food <= 0.0 {
+            Some(CauseOfDeath::Starvation)
+        } else if self.health <= 0.0 {
+            Some(CauseOfDeath::Exhaustion)
+        } else if self.age >= LIFESPAN {
+            Some(CauseOfDeath::Age)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_hungry(&self) -> bool {
+        self.food < HUNGRY
+    }
+}
+
+pub struct Needs {
+    food: GoodId,
+    nr_goods: usize, // in the catalogue
+
+    live_states: Vec<LiveStates>, // indexed by the ant id
+    quotes: Vec<Quotes>,          // indexed by the ant id
+
+    deaths: Vec<(usize, CauseOfDeath)>, // ants which died during the last update
+}
+
+impl Needs {
+    pub fn new(
+        catalogue: &GoodsCatalogue,
+        homes: &[Option<usize>],
+        rng: &mut fastrand::Rng,
+    ) -> Self {
+        let food = catalogue
+            .find("food")
+            .expect("missing food in the catalogue");
+
+        // spread the ages, so the ants do not all die at the same time
+        let live_states = homes
+            .iter()
+            .map(|home| LiveStates {
+                age: rng.f64() * LIFESPAN * 0.8,
+                ..LiveStates::new(*home)
+            })
+            .collect();
+
+        let quotes = vec![Quotes::new(catalogue.len(), INITIAL_FOOD_PRICE); homes.len()];
+
+        Self {
+            food,
+            nr_goods: catalogue.len(),
+            live_states,
+            quotes,
+            deaths: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, id: usize) -> &LiveStates {
+        &self.live_states[id]
+    }
+
+    /// Resets the needs of a newborn ant
+    pub fn born(&mut self, id: usize, home: Option<usize>) {
+        if id >= self.live_states.len() {
+            self.live_states.resize(id + 1, LiveStates::new(None));
+            self.quotes
+                .resize(id + 1, Quotes::new(self.nr_goods, INITIAL_FOOD_PRICE));
+        }
+        self.live_states[id] = LiveStates::new(home);
+    }
+
+    /// Checks that there are needs for every ant, that their homes exist and their quotes fit the goods
+    pub fn validate(&self, nr_ants: usize, nr_houses: usize) -> Result<(), wire::DecodeError> {
+        if self.live_states.len() != nr_ants || self.quotes.len() != nr_ants {
+            return Err(wire::DecodeError::Inconsistent("number of needs"));
+        }
+        let has_home = |live_states: &LiveStates| live_states.home.is_none_or(|id| id < nr_houses);
+        if !self.live_states.iter().all(has_home) {
+            return Err(wire::DecodeError::Inconsistent("homes of the ants"));
+        }
+        for quotes in &self.quotes {
+            quotes.validate(self.nr_goods)?;
+        }
+
+        Ok(())
+    }
+
+    /// Advances the needs of all living ants, returns the ants which died
+    pub fn update(
+        &mut self,
+        dt: f32,
+        ants: &mut Ants,
+        market: &mut market::Market,
+        accounts: &Accounts,
+    ) -> &[(usize, CauseOfDeath)] {
+        self.deaths.clear();
+
+        for (id, live_states) in self.live_states.iter_mut().enumerate() {
+            if !ants.is_alive(id) {
+                continue;
+            }
+
+            let quotes = &mut self.quotes[id];
+            quotes.update_prices(market);
+
+            let is_at_rest = ants.get(id).task == Task::Rest;
+            if let Some(cause) = live_states.update(dt, is_at_rest) {
+                quotes.cancel(market);
+                self.deaths.push((id, cause));
+                continue;
+            }
+
+            if live_states.is_hungry() {
+                if ants.inventory_mut(id).take(self.food, 1) == 1 {
+                    live_states.food = (live_states.food + FOOD_PER_MEAL).min(MAX_VALUE);
+                } else {
+                    let balance = accounts.balance(Account::Ant(id));
+                    if balance > 0 {
+                        quotes.limit_price(self.food, balance);
+                        quotes.post(market, Trader::Ant(id), self.food, Side::Bid, 1);
+                    }
+                }
+            }
+        }
+
+        &self.deaths
+    }
+}
+
+impl Encode for Needs {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.live_states.encode(writer);
+        self.quotes.encode(writer);
+    }
+}
+
+impl Needs {
+    /// The goods are not encoded, they are taken from the catalogue
+    pub fn decode(
+        catalogue: &GoodsCatalogue,
+        reader: &mut wire::Reader,
+    ) -> Result<Self, wire::DecodeError> {
+        let food = catalogue
+            .find("food")
+            .ok_or(wire::DecodeError::Inconsistent("food in the catalogue"))?;
+
+        Ok(Self {
+            food,
+            nr_goods: catalogue.len(),
+            live_states: Vec::decode(reader)?,
+            quotes: Vec::decode(reader)?,
+            deaths: Vec::new(),
+        })
+    }
+}
+
+#[test]
+fn test_starving_ant_dies() {
+    let mut live_states = LiveStates::new(None);
+
+    let mut cause = None;
+    for _i in 0..1000 {
+        cause = live_states.update(1.0, false);
+        if cause.is_some() {
+            break;
+        }
+    }
+
+    assert_eq!(cause, Some(CauseOfDeath::Starvation));
+    assert!(live_states.age < LIFESPAN);
+}
+
+#[test]
+fn test_tired_ants_only_recover_at_rest() {
+    let mut live_states = LiveStates {
+        sleep: TIRED / 2.0,
+        is_resting: true,
+        ..LiveStates::new(Some(0))
+    };
+
+    // walking home
+    live_states.update(1.0, false);
+    assert!(live_states.sleep < TIRED / 2.0);
+
+    let sleep = live_states.sleep;
+    live_states.update(1.0, true);
+    assert!(live_states.sleep > sleep);
+
+    // an ant which never reaches its home dies of exhaustion
+    let mut cause = None;
+    for _i in 0..1000 {
+        live_states.food = MAX_VALUE;
+        cause = live_states.update(1.0, false);
+        if cause.is_some() {
+            break;
+        }
+    }
+    assert_eq!(cause, Some(CauseOfDeath::Exhaustion));
+}
+
+#[test]
+fn test_quotes_of_other_goods_are_refused() {
+    let catalogue = GoodsCatalogue::default();
+    let mut needs = Needs::new(&catalogue, &[None, None], &mut fastrand::Rng::with_seed(0));
+    assert!(needs.validate(2, 0).is_ok());
+
+    needs.quotes[1] = Quotes::new(catalogue.len() - 1, INITIAL_FOOD_PRICE);
+    assert!(matches!(
+        needs.validate(2, 0),
+        Err(wire::DecodeError::Inconsistent(_))
+    ));
+}
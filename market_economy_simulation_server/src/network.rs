@@ -0,0 +1,737 @@
+//! TCP transport of the game logic interface
+//!
+//! The server forwards the requests of all connected clients to one game logic
+//! and sends every message of the game logic to all of them, except the responses
+//! to a request, which only go to the client that sent it.
+//! The clients choose their request ids independently, the server replaces them by
+//! unique ids and restores them in the responses.
+//! A new client first receives the whole state, a client that falls too far behind
+//! is disconnected.
+//! Files and the session itself are only controlled by the server process,
+//! such requests of the clients are refused.
+//! A connection starts with the header of the wire encoding in both directions,
+//! followed by frames of `[length u32][channel u8][message]`, the length counts the
+//! channel and the message bytes.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, mpsc};
+use std::thread;
+
+use crate::game_logic::game_logic_interface::{
+    GameLogicInterface, GameLogicMessageCritical, GameLogicMessageHeavy, GameLogicMessageLight,
+    GameLogicMessageMedium, GameLogicMessageRequest, Request, RequestId,
+};
+use crate::wire::{self, Decode};
+
+const CHANNEL_REQUEST: u8 = 0;
+const CHANNEL_HEAVY: u8 = 1;
+const CHANNEL_MEDIUM: u8 = 2;
+const CHANNEL_LIGHT: u8 = 3;
+const CHANNEL_CRITICAL: u8 = 4;
+
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024; // rejects corrupt lengths before allocating
+const MAX_REQUEST_FRAME_SIZE: usize = 64 * 1024; // the largest requests carry a path
+const CLIENT_QUEUE_CAPACITY: usize = 4096; // frames waiting for a client before it is disconnected
+
+type Frame = Arc<Vec<u8>>;
+
+fn frame(channel: u8, message: &impl wire::Encode) -> Frame {
+    let payload = wire::encode(message);
+
+    let mut frame = Vec::with_capacity(payload.len() + 5);
+    frame.extend_from_slice(&(payload.len() as u32 + 1).to_le_bytes());
+    frame.push(channel);
+    frame.extend_from_slice(&payload);
+
+    Arc::new(frame)
+}
+
+fn read_frame(stream: &mut impl Read, max_size: usize) -> io::Result<(u8, Vec<u8>)> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len == 0 || len > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid frame length {}", len),
+        ));
+    }
+
+    let mut channel = [0; 1];
+    stream.read_exact(&mut channel)?;
+
+    let mut payload = vec![0; len - 1];
+    stream.read_exact(&mut payload)?;
+
+    Ok((channel[0], payload))
+}
+
+fn decode<T: Decode>(payload: &[u8]) -> io::Result<T> {
+    wire::decode(payload).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Exchanges the headers, fails if the peer speaks another protocol version
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    stream.write_all(&wire::header())?;
+
+    let mut header = [0; wire::HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    wire::check_header(&header).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// True for the requests accessing files of the server or ending the session of all clients
+fn is_refused(message: &GameLogicMessageRequest) -> bool {
+    matches!(
+        message,
+        GameLogicMessageRequest::SaveRecording(_)
+            | GameLogicMessageRequest::SaveSnapshot(_)
+            | GameLogicMessageRequest::LoadSnapshot(_)
+            | GameLogicMessageRequest::Shutdown
+            | GameLogicMessageRequest::Reset(_)
+    )
+}
+
+/// The connection thread of a client
+struct Client {
+    id: usize,
+    frames: mpsc::SyncSender<Frame>,
+    requests: mpsc::Receiver<Request>,
+}
+
+/// A forwarded request that is answered, the response goes back to the client that sent it
+struct Route {
+    client: usize, // id of the client
+    id: RequestId, // chosen by the client, or by the server for the sync of a new client
+}
+
+/// Accepts clients and connects them to a game logic
+pub struct GameLogicNetworkServer {
+    listener: TcpListener,
+    requests: mpsc::Sender<Request>,
+
+    clients: Vec<Client>,
+    next_client_id: usize,
+
+    next_request_id: RequestId,
+    routes: BTreeMap<RequestId, Route>, // by the forwarded id, until the request is answered
+}
+
+impl GameLogicNetworkServer {
+    /// The requests of the clients are sent to `requests`
+    pub fn bind(addr: impl ToSocketAddrs, requests: mpsc::Sender<Request>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            requests,
+            clients: Vec::new(),
+            next_client_id: 0,
+            next_request_id: 0,
+            routes: BTreeMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Number of clients that were connected at the last forward
+    pub fn nr_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Accepts new clients, passes their requests to the game logic
+    /// and sends all pending messages of the game logic to the clients
+    pub fn forward(&mut self, game_logic: &dyn GameLogicInterface) {
+        self.accept();
+        self.receive_requests();
+
+        // with the id of the client a response is addressed to, None is sent to all clients
+        let mut frames = Vec::new();
+        for msg in game_logic.get_heavy_messages().try_iter() {
+            match msg {
+                GameLogicMessageHeavy::Terrain { id, height_map } => {
+                    // a cancelled request or one of a client that left is not answered
+                    let Some(route) = self.routes.remove(&id) else {
+                        continue;
+                    };
+                    let msg = GameLogicMessageHeavy::Terrain {
+                        id: route.id,
+                        height_map,
+                    };
+                    frames.push((Some(route.client), frame(CHANNEL_HEAVY, &msg)));
+                }
+                msg => frames.push((None, frame(CHANNEL_HEAVY, &msg))),
+            }
+        }
+        for msg in game_logic.get_medium_messages().try_iter() {
+            // the watch points measure this process and can not be transferred
+            if let GameLogicMessageMedium::UpdateWatchPoints(_watch_viewer_data) = msg {
+                continue;
+            }
+            match msg {
+                GameLogicMessageMedium::Synced {
+                    id,
+                    settings,
+                    clock,
+                    ants,
+                    factories,
+                } => {
+                    let Some(route) = self.routes.remove(&id) else {
+                        continue;
+                    };
+                    let msg = GameLogicMessageMedium::Synced {
+                        id: route.id,
+                        settings,
+                        clock,
+                        ants,
+                        factories,
+                    };
+                    frames.push((Some(route.client), frame(CHANNEL_MEDIUM, &msg)));
+                }
+                msg => frames.push((None, frame(CHANNEL_MEDIUM, &msg))),
+            }
+        }
+        for msg in game_logic.get_light_messages().try_iter() {
+            frames.push((None, frame(CHANNEL_LIGHT, &msg)));
+        }
+        for msg in game_logic.get_critical_messages().try_iter() {
+            match msg {
+                GameLogicMessageCritical::InvalidRequest { id, reason } => {
+                    let Some(route) = self.routes.remove(&id) else {
+                        continue;
+                    };
+                    let msg = GameLogicMessageCritical::InvalidRequest {
+                        id: route.id,
+                        reason,
+                    };
+                    frames.push((Some(route.client), frame(CHANNEL_CRITICAL, &msg)));
+                }
+                GameLogicMessageCritical::RequestFailed { id, reason } => {
+                    let Some(route) = self.routes.remove(&id) else {
+                        continue;
+                    };
+                    let msg = GameLogicMessageCritical::RequestFailed {
+                        id: route.id,
+                        reason,
+                    };
+                    frames.push((Some(route.client), frame(CHANNEL_CRITICAL, &msg)));
+                }
+                msg => frames.push((None, frame(CHANNEL_CRITICAL, &msg))),
+            }
+        }
+
+        // a client is removed as soon as its connection thread exited or its queue is full,
+        // the connection thread ends once the queue is dropped
+        self.clients.retain(|client| {
+            frames
+                .iter()
+                .filter(|(to, _frame)| to.is_none_or(|to| to == client.id))
+                .all(|(_to, frame)| client.frames.try_send(frame.clone()).is_ok())
+        });
+        self.cancel_orphaned_requests();
+    }
+
+    /// Replaces the id chosen by the client and passes the request to the game logic
+    fn forward_request(&mut self, message: GameLogicMessageRequest) -> RequestId {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        let _res = self.requests.send(Request { id, message });
+
+        id
+    }
+
+    fn receive_requests(&mut self) {
+        let mut requests = Vec::new();
+        for client in &self.clients {
+            for request in client.requests.try_iter() {
+                if is_refused(&request.message) {
+                    let refusal = GameLogicMessageCritical::InvalidRequest {
+                        id: request.id,
+                        reason: "only the server process accesses files and ends the session"
+                            .to_string(),
+                    };
+                    let _res = client.frames.try_send(frame(CHANNEL_CRITICAL, &refusal));
+                    continue;
+                }
+
+                requests.push((client.id, request));
+            }
+        }
+
+        for (client, request) in requests {
+            let message = match request.message {
+                GameLogicMessageRequest::Cancel(id) => {
+                    // a client only cancels its own requests
+                    let forwarded = self
+                        .routes
+                        .iter()
+                        .find(|(_forwarded, route)| route.client == client && route.id == id)
+                        .map(|(forwarded, _route)| *forwarded);
+                    let Some(forwarded) = forwarded else {
+                        continue; // already answered
+                    };
+                    self.routes.remove(&forwarded);
+                    GameLogicMessageRequest::Cancel(forwarded)
+                }
+                message => message,
+            };
+
+            let is_answered = matches!(message, GameLogicMessageRequest::GetTerrain(_));
+            let forwarded = self.forward_request(message);
+            if is_answered {
+                self.routes.insert(
+                    forwarded,
+                    Route {
+                        client,
+                        id: request.id,
+                    },
+                );
+            }
+        }
+    }
+
+    /// The terrain is not generated for clients that left
+    fn cancel_orphaned_requests(&mut self) {
+        let orphaned: Vec<RequestId> = self
+            .routes
+            .iter()
+            .filter(|(_forwarded, route)| {
+                !self.clients.iter().any(|client| client.id == route.client)
+            })
+            .map(|(forwarded, _route)| *forwarded)
+            .collect();
+
+        for forwarded in orphaned {
+            self.routes.remove(&forwarded);
+            self.forward_request(GameLogicMessageRequest::Cancel(forwarded));
+        }
+    }
+
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _addr)) => {
+                    let (frames_tx, frames_rx) = mpsc::sync_channel(CLIENT_QUEUE_CAPACITY);
+                    let (requests_tx, requests_rx) = mpsc::channel();
+                    thread::spawn(move || {
+                        let res = serve_client(stream, requests_tx, frames_rx);
+                        match res {
+                            Ok(_) => {}
+                            Err(err) => log::warn!("client disconnected: {}", err),
+                        }
+                    });
+                    self.clients.push(Client {
+                        id: self.next_client_id,
+                        frames: frames_tx,
+                        requests: requests_rx,
+                    });
+                    self.next_client_id += 1;
+
+                    // the changes sent to the client build on the whole state
+                    let id = self.forward_request(GameLogicMessageRequest::Sync);
+                    self.routes.insert(
+                        id,
+                        Route {
+                            client: self.next_client_id - 1,
+                            id,
+                        },
+                    );
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    log::error!("accepting a client failed: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Writes the frames to the client, while another thread reads its requests
+fn serve_client(
+    mut stream: TcpStream,
+    requests: mpsc::Sender<Request>,
+    frames: mpsc::Receiver<Frame>,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    handshake(&mut stream)?;
+
+    let mut reader = stream.try_clone()?;
+    thread::spawn(move || {
+        loop {
+            let request = match read_frame(&mut reader, MAX_REQUEST_FRAME_SIZE) {
+                Ok((CHANNEL_REQUEST, payload)) => decode::<Request>(&payload),
+                Ok((channel, _payload)) => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected channel {}", channel),
+                )),
+                Err(err) => Err(err),
+            };
+
+            let res = match request {
+                Ok(request) => requests.send(request),
+                Err(_err) => break, // disconnected or broken client
+            };
+            if res.is_err() {
+                break;
+            }
+        }
+        let _res = reader.shutdown(Shutdown::Both);
+    });
+
+    // ends when the server is dropped or the client disconnects
+    for frame in frames.iter() {
+        let res = stream.write_all(&frame);
+        if res.is_err() {
+            break;
+        }
+    }
+    let _res = stream.shutdown(Shutdown::Both);
+
+    Ok(())
+}
+
+/// Game logic running in another process, connected over TCP
+pub struct GameLogicNetworkClient {
+    stream: TcpStream,
+    receiver: thread::JoinHandle<()>,
+
+    channel_0_tx: mpsc::Sender<Request>,
+    channel_1_rx: mpsc::Receiver<GameLogicMessageHeavy>,
+    channel_2_rx: mpsc::Receiver<GameLogicMessageMedium>,
+    channel_3_rx: mpsc::Receiver<GameLogicMessageLight>,
+    channel_4_rx: mpsc::Receiver<GameLogicMessageCritical>,
+}
+
+impl GameLogicNetworkClient {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        handshake(&mut stream)?;
+
+        let (channel_0_tx, channel_0_rx) = mpsc::channel::<Request>();
+        let (channel_1_tx, channel_1_rx) = mpsc::channel();
+        let (channel_2_tx, channel_2_rx) = mpsc::channel();
+        let (channel_3_tx, channel_3_rx) = mpsc::channel();
+        let (channel_4_tx, channel_4_rx) = mpsc::channel();
+
+        let mut writer = stream.try_clone()?;
+        thread::spawn(move || {
+            for request in channel_0_rx.iter() {
+                let res = writer.write_all(&frame(CHANNEL_REQUEST, &request));
+                if res.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut reader = stream.try_clone()?;
+        let receiver = thread::spawn(move || {
+            let res = receive_messages(
+                &mut reader,
+                channel_1_tx,
+                channel_2_tx,
+                channel_3_tx,
+                channel_4_tx,
+            );
+            match res {
+                Ok(_) => {}
+                Err(err) => log::error!("receiving from the server failed: {}", err),
+            }
+        });
+
+        Ok(Self {
+            stream,
+            receiver,
+            channel_0_tx,
+            channel_1_rx,
+            channel_2_rx,
+            channel_3_rx,
+            channel_4_rx,
+        })
+    }
+
+    /// False after the server closed the connection
+    pub fn is_connected(&self) -> bool {
+        !self.receiver.is_finished()
+    }
+}
+
+/// Reads the frames of the server until the connection is closed
+fn receive_messages(
+    reader: &mut TcpStream,
+    channel_1_tx: mpsc::Sender<GameLogicMessageHeavy>,
+    channel_2_tx: mpsc::Sender<GameLogicMessageMedium>,
+    channel_3_tx: mpsc::Sender<GameLogicMessageLight>,
+    channel_4_tx: mpsc::Sender<GameLogicMessageCritical>,
+) -> io::Result<()> {
+    loop {
+        let (channel, payload) = match read_frame(reader, MAX_FRAME_SIZE) {
+            Ok(frame) => frame,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        // the receivers are only gone if the client was dropped
+        let res = match channel {
+            CHANNEL_HEAVY => channel_1_tx.send(decode(&payload)?).is_ok(),
+            CHANNEL_MEDIUM => channel_2_tx.send(decode(&payload)?).is_ok(),
+            CHANNEL_LIGHT => channel_3_tx.send(decode(&payload)?).is_ok(),
+            CHANNEL_CRITICAL => channel_4_tx.send(decode(&payload)?).is_ok(),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected channel {}", channel),
+                ));
+            }
+        };
+        if !res {
+            return Ok(());
+        }
+    }
+}
+
+impl Drop for GameLogicNetworkClient {
+    fn drop(&mut self) {
+        // ends the reader thread, the writer thread ends with the request channel
+        let _res = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+impl GameLogicInterface for GameLogicNetworkClient {
+    fn get_heavy_messages(&self) -> &mpsc::Receiver<GameLogicMessageHeavy> {
+        &self.channel_1_rx
+    }
+
+    fn get_medium_messages(&self) -> &mpsc::Receiver<GameLogicMessageMedium> {
+        &self.channel_2_rx
+    }
+
+    fn get_light_messages(&self) -> &mpsc::Receiver<GameLogicMessageLight> {
+        &self.channel_3_rx
+    }
+
+    fn get_critical_messages(&self) -> &mpsc::Receiver<GameLogicMessageCritical> {
+        &self.channel_4_rx
+    }
+
+    fn send_messages(&self) -> &mpsc::Sender<Request> {
+        &self.channel_0_tx
+    }
+}
+
+/// Creates a game logic with a server on localhost and connects the clients to it
+#[cfg(test)]
+fn create_session(
+    nr_clients: usize,
+) -> (
+    crate::GameLogicSingleThreaded,
+    GameLogicNetworkServer,
+    Vec<GameLogicNetworkClient>,
+) {
+    use crate::GameLogicSingleThreaded;
+    use crate::game_logic::GameLogicSettings;
+
+    let game_logic = GameLogicSingleThreaded::new(GameLogicSettings {
+        enable_multithreading: false,
+        max_nr_ants: 10,
+        seed: 1,
+        nr_terrain_workers: 0,
+    });
+    let mut server =
+        GameLogicNetworkServer::bind("127.0.0.1:0", game_logic.send_messages().clone()).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    // the handshake completes once the server accepted the connection
+    let connections: Vec<_> = (0..nr_clients)
+        .map(|_i| thread::spawn(move || GameLogicNetworkClient::connect(addr)))
+        .collect();
+    while !connections.iter().all(|elem| elem.is_finished()) {
+        server.forward(&game_logic);
+        thread::sleep(instant::Duration::from_millis(1));
+    }
+    let clients: Vec<_> = connections
+        .into_iter()
+        .map(|elem| elem.join().unwrap().unwrap())
+        .collect();
+
+    (game_logic, server, clients)
+}
+
+#[test]
+fn test_clients_over_localhost() {
+    let (mut game_logic, mut server, clients) = create_session(2);
+    assert_eq!(server.nr_clients(), 2);
+
+    // a request of one client changes the state seen by the other client
+    clients[0]
+        .send_messages()
+        .send(Request {
+            id: 1,
+            message: GameLogicMessageRequest::Pause,
+        })
+        .unwrap();
+
+    let mut is_paused = false;
+    for _i in 0..1000 {
+        game_logic.update(instant::Duration::from_millis(1));
+        server.forward(&game_logic);
+
+        for msg in clients[1].get_medium_messages().try_iter() {
+            if let GameLogicMessageMedium::UpdateClock(clock_state) = msg {
+                is_paused |= clock_state.is_paused;
+            }
+        }
+        if is_paused {
+            break;
+        }
+        thread::sleep(instant::Duration::from_millis(1));
+    }
+    assert!(is_paused);
+}
+
+#[test]
+fn test_file_and_admin_requests_are_refused() {
+    let (mut game_logic, mut server, clients) = create_session(1);
+
+    let path = std::env::temp_dir().join("network_refused_snapshot.mes");
+    let _res = std::fs::remove_file(&path);
+    let requests = [
+        GameLogicMessageRequest::SaveSnapshot(path.to_string_lossy().to_string()),
+        GameLogicMessageRequest::Shutdown,
+    ];
+    for (id, message) in requests.into_iter().enumerate() {
+        clients[0]
+            .send_messages()
+            .send(Request {
+                id: id as u64,
+                message,
+            })
+            .unwrap();
+    }
+
+    let mut refused = Vec::new();
+    for _i in 0..1000 {
+        game_logic.update(instant::Duration::from_millis(1));
+        server.forward(&game_logic);
+
+        for msg in clients[0].get_critical_messages().try_iter() {
+            if let GameLogicMessageCritical::InvalidRequest { id, .. } = msg {
+                refused.push(id);
+            }
+        }
+        if refused.len() == 2 {
+            break;
+        }
+        thread::sleep(instant::Duration::from_millis(1));
+    }
+    assert_eq!(refused, vec![0, 1]);
+    assert!(game_logic.is_running());
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_responses_only_reach_the_requesting_client() {
+    let (mut game_logic, mut server, clients) = create_session(2);
+
+    let details = crate::heightmap_generator::HeightMapDetails {
+        pos_0: cgmath::Vector2::new(0, 0),
+        pos_1: cgmath::Vector2::new(1, 1),
+        point_distance: 1,
+        size_0: 4,
+        size_1: 2,
+        nr_tiles: 1,
+        depth: 0,
+        node_index: 0,
+    };
+    // both clients use the same id, the first one cancels its request
+    let requests = [
+        (0, GameLogicMessageRequest::GetTerrain(details.clone())),
+        (0, GameLogicMessageRequest::Cancel(1)),
+        (1, GameLogicMessageRequest::GetTerrain(details)),
+    ];
+    for (client, message) in requests {
+        clients[client]
+            .send_messages()
+            .send(Request { id: 1, message })
+            .unwrap();
+        // keeps the order of the requests of both clients
+        let nr_forwarded = server.next_request_id;
+        while server.next_request_id == nr_forwarded {
+            server.forward(&game_logic);
+            thread::sleep(instant::Duration::from_millis(1));
+        }
+    }
+
+    let mut responses = Vec::new();
+    for _i in 0..1000 {
+        game_logic.update(instant::Duration::from_millis(1));
+        server.forward(&game_logic);
+
+        for (client, elem) in clients.iter().enumerate() {
+            for msg in elem.get_heavy_messages().try_iter() {
+                if let GameLogicMessageHeavy::Terrain { id, .. } = msg {
+                    responses.push((client, id));
+                }
+            }
+        }
+        if !responses.is_empty() {
+            break;
+        }
+        thread::sleep(instant::Duration::from_millis(1));
+    }
+    assert_eq!(responses, vec![(1, 1)]);
+    assert!(server.routes.is_empty());
+}
+
+#[test]
+fn test_new_client_receives_the_state() {
+    let (mut game_logic, mut server, clients) = create_session(1);
+
+    let mut synced = None;
+    for _i in 0..1000 {
+        game_logic.update(instant::Duration::from_millis(1));
+        server.forward(&game_logic);
+
+        for msg in clients[0].get_medium_messages().try_iter() {
+            if let GameLogicMessageMedium::Synced {
+                settings,
+                ants,
+                factories,
+                ..
+            } = msg
+            {
+                synced = Some((settings.seed, ants.len(), factories.len()));
+            }
+        }
+        if synced.is_some() {
+            break;
+        }
+        thread::sleep(instant::Duration::from_millis(1));
+    }
+    let (seed, nr_ants, nr_factories) = synced.unwrap();
+    assert_eq!(seed, 1);
+    assert!(nr_ants > 0);
+    assert!(nr_factories > 0);
+}
+
+#[test]
+fn test_large_requests_are_rejected() {
+    let request = frame(
+        CHANNEL_REQUEST,
+        &Request {
+            id: 0,
+            message: GameLogicMessageRequest::SaveSnapshot("a".repeat(MAX_REQUEST_FRAME_SIZE)),
+        },
+    );
+
+    assert!(read_frame(&mut request.as_slice(), MAX_FRAME_SIZE).is_ok());
+    let res = read_frame(&mut request.as_slice(), MAX_REQUEST_FRAME_SIZE);
+    assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,227 @@
+//! Bounded delivery of the messages to the client
+//!
+//! Every message class is sent over a bounded channel. While the channel is full,
+//! the messages wait in a backlog in their original order. A waiting message is
+//! replaced by or merged with a newer one with the same key, e.g. the changes of the same ants.
+//! Droppable messages are discarded once the backlog is full, all others are kept.
+//! Messages are never replaced across an event that is kept, so the order is preserved.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::mpsc;
+
+pub type Key = (u8, usize); // kind of the message and id of the entity
+
+pub trait Delivery {
+    /// Messages with the same key replace each other while waiting in the backlog
+    fn key(&self) -> Option<Key>;
+
+    /// The message may be discarded if the backlog is full
+    fn is_droppable(&self) -> bool;
+
+    /// Combines a waiting message with a newer one with the same key
+    fn merge(&mut self, newer: Self)
+    where
+        Self: Sized,
+    {
+        *self = newer;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutboxStatistics {
+    pub sent: u64,
+    pub coalesced: u64, // replaced by or merged with a newer message with the same key
+    pub dropped: u64,
+    pub backlog: u64, // nr messages currently waiting
+}
+
+/// Statistics of all the channels to the client
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChannelStatistics {
+    pub heavy: OutboxStatistics,
+    pub medium: OutboxStatistics,
+    pub light: OutboxStatistics,
+    pub critical: OutboxStatistics,
+}
+
+pub struct Outbox<T> {
+    sender: mpsc::SyncSender<T>,
+
+    backlog: VecDeque<T>,
+    backlog_limit: usize, // droppable messages are discarded above this size
+    front: u64,           // sequence number of the first message in the backlog
+    keys: BTreeMap<Key, u64>,
+
+    statistics: OutboxStatistics,
+}
+
+impl<T: Delivery> Outbox<T> {
+    pub fn new(sender: mpsc::SyncSender<T>, backlog_limit: usize) -> Self {
+        Self {
+            sender,
+            backlog: VecDeque::new(),
+            backlog_limit,
+            front: 0,
+            keys: BTreeMap::new(),
+            statistics: OutboxStatistics::default(),
+        }
+    }
+
+    pub fn statistics(&self) -> OutboxStatistics {
+        OutboxStatistics {
+            backlog: self.backlog.len() as u64,
+            ..self.statistics
+        }
+    }
+
+    /// Fails only if the client is gone
+    pub fn send(&mut self, msg: T) -> Result<(), mpsc::SendError<T>> {
+        if !self.backlog.is_empty() {
+            self.push(msg);
+            return Ok(());
+        }
+
+        match self.sender.try_send(msg) {
+            Ok(_) => {
+                self.statistics.sent += 1;
+                Ok(())
+            }
+            Err(mpsc::TrySendError::Full(msg)) => {
+                self.push(msg);
+                Ok(())
+            }
+            Err(mpsc::TrySendError::Disconnected(msg)) => Err(mpsc::SendError(msg)),
+        }
+    }
+
+    /// Moves the waiting messages into the channel, as far as there is space
+    pub fn flush(&mut self) {
+        while let Some(msg) = self.backlog.pop_front() {
+            let key = msg.key();
+            if let Some(key) = key
+                && self.keys.get(&key) == Some(&self.front)
+            {
+                self.keys.remove(&key);
+            }
+
+            match self.sender.try_send(msg) {
+                Ok(_) => {
+                    self.statistics.sent += 1;
+                    self.front += 1;
+                }
+                Err(mpsc::TrySendError::Full(msg)) => {
+                    if let Some(key) = key {
+                        self.keys.insert(key, self.front);
+                    }
+                    self.backlog.push_front(msg);
+                    break;
+                }
+                Err(mpsc::TrySendError::Disconnected(_msg)) => {
+                    self.backlog.clear();
+                    self.keys.clear();
+                    break;
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, msg: T) {
+        let key = msg.key();
+
+        if let Some(key) = key
+            && let Some(seq) = self.keys.get(&key)
+        {
+            self.backlog[(seq - self.front) as usize].merge(msg);
+            self.statistics.coalesced += 1;
+            return;
+        }
+
+        if self.backlog.len() >= self.backlog_limit && msg.is_droppable() {
+            self.statistics.dropped += 1;
+            return;
+        }
+
+        match key {
+            Some(key) => {
+                self.keys
+                    .insert(key, self.front + self.backlog.len() as u64);
+            }
+            None => {
+                // a newer state must not overtake an event, e.g. a loaded snapshot
+                if !msg.is_droppable() {
+                    self.keys.clear();
+                }
+            }
+        }
+        self.backlog.push_back(msg);
+    }
+}
+
+#[cfg(test)]
+impl Delivery for (Option<usize>, u32) {
+    fn key(&self) -> Option<Key> {
+        self.0.map(|id| (0, id))
+    }
+
+    fn is_droppable(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+#[test]
+fn test_backlog_coalesces_and_drops() {
+    let (tx, rx) = mpsc::sync_channel(1);
+    let mut outbox = Outbox::new(tx, 2);
+
+    outbox.send((Some(0), 0)).unwrap(); // fills the channel
+    outbox.send((Some(1), 1)).unwrap();
+    outbox.send((None, 2)).unwrap();
+    outbox.send((Some(1), 3)).unwrap(); // replaces the waiting update of entity 1
+    outbox.send((None, 4)).unwrap(); // dropped, the backlog is full
+
+    let statistics = outbox.statistics();
+    assert_eq!(statistics.coalesced, 1);
+    assert_eq!(statistics.dropped, 1);
+    assert_eq!(statistics.backlog, 2);
+
+    let mut received = Vec::new();
+    for _i in 0..3 {
+        received.extend(rx.try_iter().map(|elem| elem.1));
+        outbox.flush();
+    }
+    assert_eq!(received, vec![0, 3, 2]);
+    assert_eq!(outbox.statistics().sent, 3);
+}
+
+#[test]
+fn test_keyframe_replaces_a_waiting_delta() {
+    use crate::{ants::AntBatch, game_logic::game_logic_interface::GameLogicMessageLight};
+
+    let (tx, rx) = mpsc::sync_channel(1);
+    let mut outbox = Outbox::new(tx, 16);
+    let batch = |is_keyframe: bool, moved: Vec<usize>| {
+        GameLogicMessageLight::UpdateAnts(AntBatch {
+            is_keyframe,
+            positions: vec![cgmath::Vector2::new(0.0, 0.0); moved.len()],
+            rotations: vec![0.0; moved.len()],
+            moved,
+            ..AntBatch::default()
+        })
+    };
+
+    outbox.send(batch(false, vec![0])).unwrap(); // fills the channel
+    outbox.send(batch(false, vec![1, 2])).unwrap();
+    outbox.send(batch(true, vec![2, 3])).unwrap(); // ant 1 died in between
+
+    let mut received = Vec::new();
+    for _i in 0..2 {
+        received.extend(rx.try_iter());
+        outbox.flush();
+    }
+    let Some(GameLogicMessageLight::UpdateAnts(keyframe)) = received.last() else {
+        panic!("missing keyframe");
+    };
+    assert!(keyframe.is_keyframe);
+    assert_eq!(keyframe.moved, vec![2, 3]);
+    assert_eq!(received.len(), 2);
+}
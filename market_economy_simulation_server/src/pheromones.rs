@@ -0,0 +1,222 @@
+//! Pheromone trails laid by the ants
+//!
+//! The field holds one intensity per cell, the corners of the cells lie on the points of the
+//! terrain tiles. Gathering ants mark the field of their factory, carrying ants lay a trail
+//! back to the factory. Every tick the pheromones evaporate and diffuse into the neighbouring
+//! cells, so the intensity rises along the trails toward the places the ants gather at.
+
+use crate::{
+    ants::Ants,
+    behaviour::Task,
+    wire::{self, Decode, Encode},
+};
+
+pub const CELL_SIZE: usize = 2; // world units, a power of two like the distance of the terrain points
+
+const GATHER_DEPOSIT: f32 = 4.0; // intensity per second laid by a gathering ant
+const TRAIL_DEPOSIT: f32 = 1.0; // intensity per second laid by a carrying ant
+const EVAPORATION_RATE: f32 = 0.05; // share of the intensity lost per second
+const DIFFUSION_RATE: f32 = 0.4; // share of the intensity spreading to the neighbours per second, at most 1/dt
+const MIN_INTENSITY: f32 = 1e-4; // weaker intensities are cleared
+
+/// The intensities of the cells, sent to the client to draw the trails on the terrain
+#[derive(Clone, Debug, PartialEq)]
+pub struct PheromoneField {
+    pub origin: cgmath::Vector2<isize>, // lower corner of the cell (0/0)
+    pub cell_size: usize,
+    pub size_x: usize,
+    pub size_y: usize,
+    pub intensities: Vec<f32>, // row by row, starting at the origin
+}
+
+impl PheromoneField {
+    fn cell(&self, pos: cgmath::Vector2<f32>) -> Option<(usize, usize)> {
+        let x = (pos.x - self.origin.x as f32) / self.cell_size as f32;
+        let y = (pos.y - self.origin.y as f32) / self.cell_size as f32;
+        if x < 0.0 || y < 0.0 || x >= self.size_x as f32 || y >= self.size_y as f32 {
+            return None;
+        }
+
+        Some((x as usize, y as usize))
+    }
+
+    /// Intensity of the cell, cells outside of the field have the one of the closest border cell
+    fn get(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, self.size_x as isize - 1) as usize;
+        let y = y.clamp(0, self.size_y as isize - 1) as usize;
+
+        self.intensities[y * self.size_x + x]
+    }
+}
+
+pub struct Pheromones {
+    field: PheromoneField,
+    diffused: Vec<f32>, // intensities of the next tick
+}
+
+impl Pheromones {
+    /// Covers the area between the corners
+    pub fn new(min: cgmath::Vector2<f32>, max: cgmath::Vector2<f32>) -> Self {
+        let cell_size = CELL_SIZE as f32;
+        let origin = cgmath::Vector2::new(
+            ((min.x / cell_size).floor() * cell_size) as isize,
+            ((min.y / cell_size).floor() * cell_size) as isize,
+        );
+        let size_x = (((max.x - origin.x as f32) / cell_size).ceil() as usize).max(1);
+        let size_y = (((max.y - origin.y as f32) / cell_size).ceil() as usize).max(1);
+
+        Self::from_field(PheromoneField {
+            origin,
+            cell_size: CELL_SIZE,
+            size_x,
+            size_y,
+            intensities: vec![0.0; size_x * size_y],
+        })
+    }
+
+    fn from_field(field: PheromoneField) -> Self {
+        let diffused = vec![0.0; field.intensities.len()];
+
+        Self { field, diffused }
+    }
+
+    pub fn field(&self) -> &PheromoneField {
+        &self.field
+    }
+
+    /// Intensity at the position, zero outside of the field
+    pub fn intensity(&self, pos: cgmath::Vector2<f32>) -> f32 {
+        match self.field.cell(pos) {
+            Some((x, y)) => self.field.intensities[y * self.field.size_x + x],
+            None => 0.0,
+        }
+    }
+
+    /// Direction of the steepest rise of the intensity per world unit, zero outside of the field
+    pub fn gradient(&self, pos: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        let Some((x, y)) = self.field.cell(pos) else {
+            return cgmath::Vector2::new(0.0, 0.0);
+        };
+        let (x, y) = (x as isize, y as isize);
+        let distance = 2.0 * self.field.cell_size as f32;
+
+        cgmath::Vector2::new(
+            (self.field.get(x + 1, y) - self.field.get(x - 1, y)) / distance,
+            (self.field.get(x, y + 1) - self.field.get(x, y - 1)) / distance,
+        )
+    }
+
+    /// Adds the intensity at the position, nothing is laid outside of the field
+    pub fn deposit(&mut self, pos: cgmath::Vector2<f32>, amount: f32) {
+        if let Some((x, y)) = self.field.cell(pos) {
+            self.field.intensities[y * self.field.size_x + x] += amount;
+        }
+    }
+
+    /// Lays the pheromones of the gathering and carrying ants, evaporates and diffuses the field
+    pub fn update(&mut self, dt: f32, ants: &Ants) {
+        for id in (0..ants.len()).filter(|id| ants.is_alive(*id)) {
+            let ant = ants.get(id);
+            match ant.task {
+                Task::Gather => self.deposit(ant.pos, GATHER_DEPOSIT * dt),
+                Task::Carry => self.deposit(ant.pos, TRAIL_DEPOSIT * dt),
+                _ => {}
+            }
+        }
+
+        self.evaporate(dt);
+        self.diffuse(dt);
+    }
+
+    fn evaporate(&mut self, dt: f32) {
+        let factor = (1.0 - EVAPORATION_RATE * dt).max(0.0);
+        for intensity in &mut self.field.intensities {
+            *intensity *= factor;
+            if *intensity < MIN_INTENSITY {
+                *intensity = 0.0;
+            }
+        }
+    }
+
+    /// Every cell passes a share of its intensity to its four neighbours,
+    /// the border reflects it back, so no pheromones are lost
+    fn diffuse(&mut self, dt: f32) {
+        let share = (DIFFUSION_RATE * dt).min(1.0) / 4.0;
+        let field = &self.field;
+        for y in 0..field.size_y as isize {
+            for x in 0..field.size_x as isize {
+                let middle = field.get(x, y);
+                let neighbours = field.get(x - 1, y)
+                    + field.get(x + 1, y)
+                    + field.get(x, y - 1)
+                    + field.get(x, y + 1);
+
+                self.diffused[y as usize * field.size_x + x as usize] =
+                    middle + share * (neighbours - 4.0 * middle);
+            }
+        }
+
+        std::mem::swap(&mut self.field.intensities, &mut self.diffused);
+    }
+}
+
+impl Encode for Pheromones {
+    fn encode(&self, writer: &mut wire::Writer) {
+        self.field.encode(writer);
+    }
+}
+
+impl Decode for Pheromones {
+    fn decode(reader: &mut wire::Reader) -> Result<Self, wire::DecodeError> {
+        Ok(Self::from_field(PheromoneField::decode(reader)?))
+    }
+}
+
+#[cfg(test)]
+fn total(pheromones: &Pheromones) -> f32 {
+    pheromones.field.intensities.iter().sum()
+}
+
+#[test]
+fn test_pheromones_diffuse_and_evaporate() {
+    let mut pheromones = Pheromones::new(
+        cgmath::Vector2::new(-20.0, -20.0),
+        cgmath::Vector2::new(20.0, 20.0),
+    );
+    let source = cgmath::Vector2::new(1.0, 1.0);
+    pheromones.deposit(source, 10.0);
+
+    // diffusion alone keeps the pheromones
+    for _i in 0..100 {
+        pheromones.diffuse(0.016);
+    }
+    assert!((total(&pheromones) - 10.0).abs() < 1e-3);
+    assert!(pheromones.intensity(source) < 10.0);
+    assert!(pheromones.intensity(source + cgmath::Vector2::new(2.0, 0.0)) > 0.0);
+
+    let before = total(&pheromones);
+    pheromones.evaporate(1.0);
+    assert!(total(&pheromones) < before);
+}
+
+#[test]
+fn test_gradient_points_to_the_source() {
+    let mut pheromones = Pheromones::new(
+        cgmath::Vector2::new(-20.0, -20.0),
+        cgmath::Vector2::new(20.0, 20.0),
+    );
+    let source = cgmath::Vector2::new(5.0, -3.0);
+    for _i in 0..500 {
+        pheromones.deposit(source, 0.1);
+        pheromones.diffuse(0.016);
+    }
+
+    let west = pheromones.gradient(source + cgmath::Vector2::new(-6.0, 0.0));
+    assert!(west.x > 0.0);
+    let north = pheromones.gradient(source + cgmath::Vector2::new(0.0, 6.0));
+    assert!(north.y < 0.0);
+    assert_eq!(
+        pheromones.gradient(cgmath::Vector2::new(100.0, 0.0)),
+        cgmath::Vector2::new(0.0, 0.0)
+    );
+}
@@ -1,11 +1,13 @@
-use std::sync::mpsc;
-
-use crate::{game_logic::game_logic_interface::GameLogicMessageLight, terrain::Terrain};
+use crate::{
+    game_logic::game_logic_interface::{GameLogicMessageLight, KEYFRAME_INTERVAL},
+    outbox::Outbox,
+    terrain::Terrain,
+};
 
 pub struct PointLights {
     lights: Vec<PointLight>,
 
-    requires_update: bool,
+    changed: Vec<bool>, // indexed by the light id, since the last update
 }
 
 impl PointLights {
@@ -42,18 +44,34 @@ impl PointLights {
             }
         }
 
-        Self {
-            lights,
-            requires_update: true,
+        let changed = vec![true; lights.len()];
+
+        Self { lights, changed }
+    }
+
+    pub fn set_position(&mut self, id: usize, position: cgmath::Vector3<f32>) {
+        if self.lights[id].position != position {
+            self.lights[id].position = position;
+            self.changed[id] = true;
+        }
+    }
+
+    pub fn set_color(&mut self, id: usize, color: cgmath::Vector3<f32>) {
+        if self.lights[id].color != color {
+            self.lights[id].color = color;
+            self.changed[id] = true;
         }
     }
 
-    pub fn update(&mut self, channel: &mpsc::Sender<GameLogicMessageLight>) {
-        if self.requires_update {
-            for elem in &mut self.lights {
-                elem.position.x += 0.02;
-                // elem.color.x = (elem.color.x + 0.001) % 1.0;
+    /// Sends the changed lights, or all of them on a keyframe
+    pub fn update(&mut self, tick: u64, channel: &mut Outbox<GameLogicMessageLight>) {
+        let is_keyframe = tick.is_multiple_of(KEYFRAME_INTERVAL);
+
+        for (elem, changed) in self.lights.iter_mut().zip(&mut self.changed) {
+            // elem.position.x += 0.02;
+            // elem.color.x = (elem.color.x + 0.001) % 1.0;
 
+            if is_keyframe || *changed {
                 let res = channel.send(GameLogicMessageLight::UpdatePointLight(elem.clone()));
                 match res {
                     Ok(_) => {}
@@ -62,6 +80,7 @@ impl PointLights {
                     }
                 }
             }
+            *changed = false;
         }
     }
 }
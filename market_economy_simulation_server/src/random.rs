@@ -0,0 +1,47 @@
+//! Deterministic random numbers derived from the simulation seed
+//!
+//! Every subsystem draws from its own stream, so adding random draws to one
+//! subsystem does not change the random numbers of the others.
+//! Two runs with the same seed and the same inputs produce identical states.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Terrain,
+    Resources,
+    Needs,
+    Decisions, // choices of the ants while the simulation runs
+}
+
+/// Random number generator of one subsystem
+pub fn rng(seed: u64, stream: Stream) -> fastrand::Rng {
+    fastrand::Rng::with_seed(stream_seed(seed, stream))
+}
+
+/// Seed for the noise functions of one subsystem
+pub fn noise_seed(seed: u64, stream: Stream) -> u32 {
+    stream_seed(seed, stream) as u32
+}
+
+/// Mixes the seed with the stream, so the streams are independent (splitmix64)
+fn stream_seed(seed: u64, stream: Stream) -> u64 {
+    let mut z = seed
+        .wrapping_add((stream as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+        .wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[test]
+fn test_streams_are_reproducible() {
+    let mut rng_0 = rng(42, Stream::Needs);
+    let mut rng_1 = rng(42, Stream::Needs);
+    let mut rng_2 = rng(42, Stream::Resources);
+
+    let values_0: Vec<u64> = (0..8).map(|_i| rng_0.u64(..)).collect();
+    let values_1: Vec<u64> = (0..8).map(|_i| rng_1.u64(..)).collect();
+    let values_2: Vec<u64> = (0..8).map(|_i| rng_2.u64(..)).collect();
+
+    assert_eq!(values_0, values_1);
+    assert_ne!(values_0, values_2);
+}
@@ -0,0 +1,151 @@
+//! Production recipes executed by the factories
+//!
+//! The workers of a factory gather raw goods on its field and deliver them,
+//! all other inputs are bought on the market.
+
+use crate::goods::{GoodId, GoodsCatalogue};
+
+pub type RecipeId = usize;
+
+#[derive(Clone, Debug)]
+pub struct Recipe {
+    pub id: RecipeId,
+    pub name: String,
+
+    pub inputs: Vec<(GoodId, u32)>, // consumed when the production starts
+    pub outputs: Vec<(GoodId, u32)>, // produced when the production is finished
+    pub gathered: Vec<(GoodId, u32)>, // carried to the factory by a worker on every trip to the field
+    pub agents: u32,                  // nr agents emitted when the production is finished
+    pub duration: f64,                // simulation time of a production cycle in seconds
+    pub required_workers: u32,        // nr workers needed to run the production
+}
+
+impl Recipe {
+    /// True if the workers bring the good, instead of buying it
+    pub fn is_gathered(&self, good: GoodId) -> bool {
+        self.gathered.iter().any(|(elem, _quantity)| *elem == good)
+    }
+}
+
+/// Description of a recipe, referring to the goods by name
+pub struct RecipeDescription<'a> {
+    pub name: &'a str,
+    pub inputs: &'a [(&'a str, u32)],
+    pub outputs: &'a [(&'a str, u32)],
+    pub gathered: &'a [(&'a str, u32)],
+    pub agents: u32,
+    pub duration: f64,
+    pub required_workers: u32,
+}
+
+pub const DEFAULT_RECIPES: &[RecipeDescription] = &[
+    RecipeDescription {
+        name: "breed ant",
+        inputs: &[("blue", 1)],
+        outputs: &[],
+        gathered: &[],
+        agents: 1,
+        duration: 5.0,
+        required_workers: 0,
+    },
+    RecipeDescription {
+        name: "mine red",
+        inputs: &[("ore", 1)],
+        outputs: &[("red", 1)],
+        gathered: &[("ore", 4)],
+        agents: 0,
+        duration: 1.0,
+        required_workers: 1,
+    },
+    RecipeDescription {
+        name: "mine green",
+        inputs: &[("ore", 1)],
+        outputs: &[("green", 1)],
+        gathered: &[("ore", 4)],
+        agents: 0,
+        duration: 1.0,
+        required_workers: 1,
+    },
+    RecipeDescription {
+        name: "mine blue",
+        inputs: &[("ore", 1)],
+        outputs: &[("blue", 1)],
+        gathered: &[("ore", 4)],
+        agents: 0,
+        duration: 1.0,
+        required_workers: 1,
+    },
+    RecipeDescription {
+        name: "grow grain",
+        inputs: &[("water", 2)],
+        outputs: &[("grain", 4)],
+        gathered: &[("water", 4)],
+        agents: 0,
+        duration: 2.0,
+        required_workers: 2,
+    },
+    RecipeDescription {
+        name: "bake food",
+        inputs: &[("grain", 2), ("wood", 1)],
+        outputs: &[("food", 3)],
+        gathered: &[("wood", 2)],
+        agents: 0,
+        duration: 1.0,
+        required_workers: 1,
+    },
+];
+
+pub struct Recipes {
+    recipes: Vec<Recipe>, // indexed by the recipe id
+}
+
+impl Recipes {
+    /// Creates the recipes from their descriptions
+    ///
+    /// Panics if a recipe refers to a good missing in the catalogue
+    pub fn new(catalogue: &GoodsCatalogue, descriptions: &[RecipeDescription]) -> Self {
+        let find = |goods: &[(&str, u32)]| -> Vec<(GoodId, u32)> {
+            goods
+                .iter()
+                .map(|(name, quantity)| {
+                    let good = catalogue
+                        .find(name)
+                        .unwrap_or_else(|| panic!("unknown good {}", name));
+                    (good, *quantity)
+                })
+                .collect()
+        };
+
+        let recipes = descriptions
+            .iter()
+            .enumerate()
+            .map(|(id, elem)| Recipe {
+                id,
+                name: elem.name.to_string(),
+                inputs: find(elem.inputs),
+                outputs: find(elem.outputs),
+                gathered: find(elem.gathered),
+                agents: elem.agents,
+                duration: elem.duration,
+                required_workers: elem.required_workers,
+            })
+            .collect();
+
+        Self { recipes }
+    }
+
+    pub fn get(&self, id: RecipeId) -> &Recipe {
+        &self.recipes[id]
+    }
+
+    pub fn find(&self, name: &str) -> Option<RecipeId> {
+        self.recipes
+            .iter()
+            .find(|elem| elem.name == name)
+            .map(|elem| elem.id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Recipe> {
+        self.recipes.iter()
+    }
+}
@@ -0,0 +1,71 @@
+//! Recording of the requests applied to the game logic
+//!
+//! The simulation is deterministic, so the settings and the requests together with
+//! the tick they were applied at are enough to reproduce a session exactly.
+//! A recording starts with the creation, the last reset or the last loaded snapshot
+//! of the game logic.
+
+use std::path::Path;
+
+use crate::game_logic::{GameLogicSettings, game_logic_interface::Request};
+use crate::wire;
+
+#[derive(Clone)]
+pub struct RecordedRequest {
+    pub tick: u64, // the request was applied before simulating this tick
+    pub request: Request,
+}
+
+#[derive(Clone)]
+pub struct Recording {
+    pub settings: GameLogicSettings,
+    pub snapshot: Option<Vec<u8>>, // state the session started from, if it was loaded
+    pub requests: Vec<RecordedRequest>,
+    pub checksums: Vec<(u64, u64)>, // tick and checksum of the state at the keyframes
+    pub end_tick: u64,              // the tick of the simulation when the recording was saved
+}
+
+impl Recording {
+    pub fn new(settings: GameLogicSettings) -> Self {
+        Self {
+            settings,
+            snapshot: None,
+            requests: Vec::new(),
+            checksums: Vec::new(),
+            end_tick: 0,
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, request: Request) {
+        self.requests.push(RecordedRequest { tick, request });
+    }
+
+    /// Requests applied at the tick, in the original order
+    pub fn requests_at(&self, tick: u64) -> impl Iterator<Item = &Request> {
+        self.requests
+            .iter()
+            .filter(move |elem| elem.tick == tick)
+            .map(|elem| &elem.request)
+    }
+
+    pub fn record_checksum(&mut self, tick: u64, checksum: u64) {
+        self.checksums.push((tick, checksum));
+    }
+
+    pub fn checksum_at(&self, tick: u64) -> Option<u64> {
+        self.checksums
+            .iter()
+            .find(|elem| elem.0 == tick)
+            .map(|elem| elem.1)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, wire::encode_with_header(self))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        wire::decode_with_header(&bytes)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
@@ -0,0 +1,337 @@
+//! Uniform grid to find the entities close to a position
+//!
+//! Every entity is kept in the cell containing its position, so a query only looks
+//! at the entities in the cells overlapping the searched area.
+//! The cells are visited in order and keep their ids sorted, so neither the results
+//! nor the order of a visit depend on the order of the insertions.
+
+use std::collections::BTreeMap;
+
+use cgmath::InnerSpace;
+
+type Cell = (i32, i32);
+
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: BTreeMap<Cell, Vec<usize>>, // sorted ids of the entities in the cell
+    positions: Vec<Option<cgmath::Vector2<f32>>>, // indexed by the id, None if not in the grid
+    len: usize,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: BTreeMap::new(),
+            positions: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn cell(&self, pos: cgmath::Vector2<f32>) -> Cell {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Adds the entity or moves it to the new position
+    pub fn insert(&mut self, id: usize, pos: cgmath::Vector2<f32>) {
+        if id >= self.positions.len() {
+            self.positions.resize(id + 1, None);
+        }
+
+        let cell = self.cell(pos);
+        match self.positions[id] {
+            Some(old) if self.cell(old) == cell => {}
+            Some(_old) => {
+                self.remove_from_cell(id);
+                self.add_to_cell(id, cell);
+            }
+            None => {
+                self.add_to_cell(id, cell);
+                self.len += 1;
+            }
+        }
+        self.positions[id] = Some(pos);
+    }
+
+    fn add_to_cell(&mut self, id: usize, cell: Cell) {
+        let ids = self.cells.entry(cell).or_default();
+        if let Err(index) = ids.binary_search(&id) {
+            ids.insert(index, id);
+        }
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        if self.position(id).is_some() {
+            self.remove_from_cell(id);
+            self.positions[id] = None;
+            self.len -= 1;
+        }
+    }
+
+    fn remove_from_cell(&mut self, id: usize) {
+        let Some(pos) = self.positions[id] else {
+            return;
+        };
+
+        let cell = self.cell(pos);
+        if let Some(ids) = self.cells.get_mut(&cell) {
+            ids.retain(|elem| *elem != id);
+            if ids.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    pub fn position(&self, id: usize) -> Option<cgmath::Vector2<f32>> {
+        self.positions.get(id).copied().flatten()
+    }
+
+    /// Entities inside the rectangle between the corners, sorted by their id
+    pub fn in_rect(&self, min: cgmath::Vector2<f32>, max: cgmath::Vector2<f32>) -> Vec<usize> {
+        let mut res = Vec::new();
+        self.visit_rect(min, max, |id, pos| {
+            if pos.x >= min.x && pos.y >= min.y && pos.x <= max.x && pos.y <= max.y {
+                res.push(id);
+            }
+        });
+        res.sort_unstable();
+
+        res
+    }
+
+    /// Entities within the radius around the center, sorted by their id
+    pub fn in_radius(&self, center: cgmath::Vector2<f32>, radius: f32) -> Vec<usize> {
+        let mut res = Vec::new();
+        self.visit_radius(center, radius, |id, _pos| res.push(id));
+        res.sort_unstable();
+
+        res
+    }
+
+    /// Calls the function for all entities within the radius around the center, without allocating
+    pub fn visit_radius(
+        &self,
+        center: cgmath::Vector2<f32>,
+        radius: f32,
+        mut f: impl FnMut(usize, cgmath::Vector2<f32>),
+    ) {
+        let extent = cgmath::Vector2::new(radius, radius);
+        self.visit_rect(center - extent, center + extent, |id, pos| {
+            if (pos - center).magnitude2() <= radius * radius {
+                f(id, pos);
+            }
+        });
+    }
+
+    /// Calls the function for all entities in the cells overlapping the rectangle, cell by cell
+    fn visit_rect(
+        &self,
+        min: cgmath::Vector2<f32>,
+        max: cgmath::Vector2<f32>,
+        mut f: impl FnMut(usize, cgmath::Vector2<f32>),
+    ) {
+        let (min_x, min_y) = self.cell(min);
+        let (max_x, max_y) = self.cell(max);
+        let nr_cells = (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1);
+
+        // a large rectangle visits the occupied cells instead
+        if nr_cells > self.cells.len() as i64 {
+            for (cell, ids) in &self.cells {
+                if (min_x..=max_x).contains(&cell.0) && (min_y..=max_y).contains(&cell.1) {
+                    for id in ids {
+                        f(*id, self.positions[*id].unwrap());
+                    }
+                }
+            }
+            return;
+        }
+
+        // the same order as the keys of the occupied cells
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                for id in self.cells.get(&(x, y)).into_iter().flatten() {
+                    f(*id, self.positions[*id].unwrap());
+                }
+            }
+        }
+    }
+
+    /// The k entities closest to the position, the closest first, equally far ones by their id
+    pub fn k_nearest(&self, pos: cgmath::Vector2<f32>, k: usize) -> Vec<usize> {
+        self.nearest(pos, k, |_id| true)
+    }
+
+    /// The entity closest to the position for which the filter is true
+    pub fn nearest_where(
+        &self,
+        pos: cgmath::Vector2<f32>,
+        filter: impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        self.nearest(pos, 1, filter).first().copied()
+    }
+
+    /// Searches rings of cells around the position, until no entity outside of them can be closer
+    fn nearest(
+        &self,
+        pos: cgmath::Vector2<f32>,
+        k: usize,
+        filter: impl Fn(usize) -> bool,
+    ) -> Vec<usize> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let (center_x, center_y) = self.cell(pos);
+        let mut candidates: Vec<(f32, usize)> = Vec::new(); // squared distance and id
+        let mut nr_visited = 0;
+        let mut ring = 0;
+        while nr_visited < self.len {
+            for cell in ring_cells(center_x, center_y, ring) {
+                for id in self.cells.get(&cell).into_iter().flatten() {
+                    nr_visited += 1;
+                    if filter(*id) {
+                        let distance = (self.positions[*id].unwrap() - pos).magnitude2();
+                        candidates.push((distance, *id));
+                    }
+                }
+            }
+            sort_by_distance(&mut candidates);
+
+            // the cells outside of the searched rings are at least this far away
+            let reach = ring as f32 * self.cell_size;
+            if candidates.len() >= k && candidates[k - 1].0 <= reach * reach {
+                break;
+            }
+            ring += 1;
+        }
+
+        candidates.truncate(k);
+        candidates.into_iter().map(|(_distance, id)| id).collect()
+    }
+}
+
+fn sort_by_distance(candidates: &mut [(f32, usize)]) {
+    candidates.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+}
+
+/// Cells with the distance of the ring to the center cell in x or y
+fn ring_cells(center_x: i32, center_y: i32, ring: i32) -> Vec<Cell> {
+    if ring == 0 {
+        return vec![(center_x, center_y)];
+    }
+
+    let mut cells = Vec::with_capacity(8 * ring as usize);
+    for x in center_x - ring..=center_x + ring {
+        cells.push((x, center_y - ring));
+        cells.push((x, center_y + ring));
+    }
+    for y in center_y - ring + 1..center_y + ring {
+        cells.push((center_x - ring, y));
+        cells.push((center_x + ring, y));
+    }
+
+    cells
+}
+
+#[cfg(test)]
+fn scattered_grid() -> (SpatialGrid, Vec<cgmath::Vector2<f32>>) {
+    let positions: Vec<cgmath::Vector2<f32>> = (0..200)
+        .map(|i| {
+            let i = i as f32;
+            cgmath::Vector2::new((i * 37.3) % 97.0 - 40.0, (i * 11.9) % 53.0 - 20.0)
+        })
+        .collect();
+
+    let mut grid = SpatialGrid::new(8.0);
+    for (id, pos) in positions.iter().enumerate() {
+        grid.insert(id, *pos);
+    }
+
+    (grid, positions)
+}
+
+#[test]
+fn test_queries_match_a_linear_scan() {
+    let (grid, positions) = scattered_grid();
+    let center = cgmath::Vector2::new(3.0, -4.0);
+
+    let in_radius: Vec<usize> = (0..positions.len())
+        .filter(|id| (positions[*id] - center).magnitude() <= 15.0)
+        .collect();
+    assert_eq!(grid.in_radius(center, 15.0), in_radius);
+
+    let (min, max) = (
+        cgmath::Vector2::new(-10.0, -5.0),
+        cgmath::Vector2::new(20.0, 30.0),
+    );
+    let in_rect: Vec<usize> = (0..positions.len())
+        .filter(|id| {
+            let pos = positions[*id];
+            pos.x >= min.x && pos.y >= min.y && pos.x <= max.x && pos.y <= max.y
+        })
+        .collect();
+    assert_eq!(grid.in_rect(min, max), in_rect);
+
+    let mut by_distance: Vec<(f32, usize)> = (0..positions.len())
+        .map(|id| ((positions[id] - center).magnitude2(), id))
+        .collect();
+    sort_by_distance(&mut by_distance);
+    let nearest: Vec<usize> = by_distance.iter().take(10).map(|elem| elem.1).collect();
+    assert_eq!(grid.k_nearest(center, 10), nearest);
+    assert_eq!(grid.k_nearest(center, 1000).len(), positions.len());
+}
+
+#[test]
+fn test_moved_and_removed_entities_are_found_at_their_new_place() {
+    let (mut grid, _positions) = scattered_grid();
+
+    grid.insert(5, cgmath::Vector2::new(500.0, 500.0));
+    grid.remove(6);
+    grid.remove(6);
+    assert_eq!(grid.len(), 199);
+
+    let far_away = cgmath::Vector2::new(490.0, 490.0);
+    assert_eq!(grid.k_nearest(far_away, 1), vec![5]);
+    assert!(
+        grid.nearest_where(far_away, |id| id != 5 && id != 6)
+            .is_some()
+    );
+    assert!(
+        !grid
+            .in_rect(far_away, far_away + cgmath::Vector2::new(20.0, 20.0))
+            .contains(&6)
+    );
+    assert_eq!(grid.position(6), None);
+}
+
+#[test]
+fn test_visits_do_not_depend_on_the_insertions() {
+    let (grid, positions) = scattered_grid();
+    let mut reversed = SpatialGrid::new(8.0);
+    for (id, pos) in positions.iter().enumerate().rev() {
+        reversed.insert(id, *pos);
+    }
+
+    let center = cgmath::Vector2::new(3.0, -4.0);
+    let visit = |grid: &SpatialGrid| {
+        let mut ids = Vec::new();
+        grid.visit_radius(center, 15.0, |id, _pos| ids.push(id));
+        ids
+    };
+    assert_eq!(visit(&grid), visit(&reversed));
+
+    let mut ids = visit(&grid);
+    ids.sort();
+    assert_eq!(ids, grid.in_radius(center, 15.0));
+}
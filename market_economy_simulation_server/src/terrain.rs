@@ -8,7 +8,7 @@ pub struct Terrain {
 }
 
 impl Terrain {
-    pub fn new(size_x: usize, size_y: usize, distance: f32) -> Self {
+    pub fn new(size_x: usize, size_y: usize, distance: f32, seed: u32) -> Self {
         let _amplitude = 10.0;
         let _scale = 0.1;
 
@@ -16,7 +16,7 @@ impl Terrain {
         let mut heights: Vec<f32> = Vec::new();
         heights.resize(size_x * size_y, 0.0);
 
-        let perlin = noise::Perlin::new(1);
+        let perlin = noise::Perlin::new(seed);
 
         for y in 0..size_y {
             for x in 0..size_x {
@@ -0,0 +1,1642 @@
+//! Versioned binary encoding of the messages between the game logic and its clients
+//!
+//! All numbers are little endian, `usize` and `isize` are widened to 64 bit.
+//! Enums are written as a one byte tag followed by the fields of the variant.
+//! Connections and files start with the header, so an incompatible version is rejected
+//! instead of being misread.
+//! Types with private state, e.g. for the snapshots, implement the traits in their own module.
+
+use std::collections::VecDeque;
+
+use cgmath::{Vector2, Vector3};
+
+use crate::{
+    accounts::{Account, Entry, EntryKind},
+    ants::{Ant, AntBatch},
+    behaviour::Task,
+    clock::ClockState,
+    factories::FactoryState,
+    game_logic::{
+        GameLogicSettings,
+        game_logic_interface::{
+            GameLogicMessageCritical, GameLogicMessageHeavy, GameLogicMessageLight,
+            GameLogicMessageMedium, GameLogicMessageRequest, Request,
+        },
+    },
+    heightmap_generator::{HeightMap, HeightMapDetails},
+    market::{Order, PricePoint, Side, Trade, Trader},
+    needs::{CauseOfDeath, LiveStates},
+    outbox::{ChannelStatistics, OutboxStatistics},
+    pheromones::PheromoneField,
+    point_lights::{Attenuation, PointLight},
+    recording::{RecordedRequest, Recording},
+};
+
+pub const MAGIC: [u8; 4] = *b"MESS";
+pub const VERSION: u16 = 20; // increase on every change of the encoding
+
+pub const HEADER_SIZE: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    WrongMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEnd,
+    TrailingBytes(usize),
+    InvalidTag { name: &'static str, tag: u8 },
+    LocalOnly(&'static str), // the message is not transferred over the network
+    InvalidString,
+    Inconsistent(&'static str), // the decoded values contradict each other
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::WrongMagic => write!(f, "not a market economy simulation peer"),
+            DecodeError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported protocol version {}, expected {}",
+                version, VERSION
+            ),
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of data"),
+            DecodeError::TrailingBytes(nr_bytes) => {
+                write!(f, "{} bytes left after the end of the message", nr_bytes)
+            }
+            DecodeError::InvalidTag { name, tag } => write!(f, "invalid tag {} for {}", tag, name),
+            DecodeError::LocalOnly(name) => write!(f, "{} is not transferable", name),
+            DecodeError::InvalidString => write!(f, "invalid utf-8 string"),
+            DecodeError::Inconsistent(what) => write!(f, "inconsistent {}", what),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub fn header() -> [u8; HEADER_SIZE] {
+    let version = VERSION.to_le_bytes();
+    [
+        MAGIC[0], MAGIC[1], MAGIC[2], MAGIC[3], version[0], version[1],
+    ]
+}
+
+pub fn check_header(header: &[u8; HEADER_SIZE]) -> Result<(), DecodeError> {
+    if header[0..4] != MAGIC {
+        return Err(DecodeError::WrongMagic);
+    }
+
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    Ok(())
+}
+
+pub fn encode<T: Encode>(value: &T) -> Vec<u8> {
+    let mut writer = Writer::new();
+    value.encode(&mut writer);
+    writer.into_bytes()
+}
+
+/// Decodes a value that has to span all the bytes
+pub fn decode<T: Decode>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let mut reader = Reader::new(bytes);
+    let value = T::decode(&mut reader)?;
+
+    match reader.remaining() {
+        0 => Ok(value),
+        nr_bytes => Err(DecodeError::TrailingBytes(nr_bytes)),
+    }
+}
+
+/// Encodes a standalone value, e.g. for a file, prefixed by the header
+pub fn encode_with_header<T: Encode>(value: &T) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.write_header();
+    value.encode(&mut writer);
+    writer.into_bytes()
+}
+
+/// Decodes a value written by `encode_with_header`, fails for other versions
+pub fn decode_with_header<T: Decode>(bytes: &[u8]) -> Result<T, DecodeError> {
+    decode(strip_header(bytes)?)
+}
+
+/// Checks the header and returns the bytes following it
+pub fn strip_header(bytes: &[u8]) -> Result<&[u8], DecodeError> {
+    let mut reader = Reader::new(bytes);
+    reader.read_header()?;
+
+    Ok(reader.bytes)
+}
+
+/// FNV-1a hash of encoded data, e.g. to detect a replay diverging from the recorded session
+pub fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+pub trait Encode {
+    fn encode(&self, writer: &mut Writer);
+}
+
+pub trait Decode: Sized {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError>;
+}
+
+#[derive(Default)]
+pub struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn write_header(&mut self) {
+        self.bytes.extend_from_slice(&header());
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    pub fn write_isize(&mut self, value: isize) {
+        self.write_u64(value as i64 as u64);
+    }
+
+    pub fn write_str(&mut self, value: &str) {
+        self.write_usize(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn read_header(&mut self) -> Result<(), DecodeError> {
+        check_header(&self.take()?)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        if self.bytes.len() < N {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+
+        let mut res = [0; N];
+        res.copy_from_slice(head);
+        Ok(res)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, DecodeError> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64, DecodeError> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, DecodeError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Err(DecodeError::InvalidTag { name: "bool", tag }),
+        }
+    }
+
+    pub fn read_usize(&mut self) -> Result<usize, DecodeError> {
+        Ok(self.read_u64()? as usize)
+    }
+
+    pub fn read_isize(&mut self) -> Result<isize, DecodeError> {
+        Ok(self.read_u64()? as i64 as isize)
+    }
+
+    pub fn read_string(&mut self) -> Result<String, DecodeError> {
+        let len = self.read_len(1)?;
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+
+        String::from_utf8(head.to_vec()).map_err(|_err| DecodeError::InvalidString)
+    }
+
+    /// Reads the length of a sequence, which can not be longer than the remaining data
+    pub fn read_len(&mut self, element_size: usize) -> Result<usize, DecodeError> {
+        let len = self.read_usize()?;
+        match len.checked_mul(element_size) {
+            Some(size) if size <= self.remaining() => Ok(len),
+            _ => Err(DecodeError::UnexpectedEnd),
+        }
+    }
+}
+
+macro_rules! impl_primitive {
+    ($type:ty, $write:ident, $read:ident) => {
+        impl Encode for $type {
+            fn encode(&self, writer: &mut Writer) {
+                writer.$write(*self);
+            }
+        }
+
+        impl Decode for $type {
+            fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+                reader.$read()
+            }
+        }
+    };
+}
+
+impl_primitive!(u8, write_u8, read_u8);
+impl_primitive!(u32, write_u32, read_u32);
+impl_primitive!(u64, write_u64, read_u64);
+impl_primitive!(usize, write_usize, read_usize);
+impl_primitive!(f32, write_f32, read_f32);
+impl_primitive!(f64, write_f64, read_f64);
+impl_primitive!(bool, write_bool, read_bool);
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            None => writer.write_u8(0),
+            Some(value) => {
+                writer.write_u8(1);
+                value.encode(writer);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(reader)?)),
+            tag => Err(DecodeError::InvalidTag {
+                name: "Option",
+                tag,
+            }),
+        }
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_usize(self.len());
+        for elem in self {
+            elem.encode(writer);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, writer: &mut Writer) {
+        self.as_slice().encode(writer);
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let len = reader.read_len(1)?;
+        let mut res = Vec::with_capacity(len);
+        for _i in 0..len {
+            res.push(T::decode(reader)?);
+        }
+        Ok(res)
+    }
+}
+
+impl<T: Encode> Encode for VecDeque<T> {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_usize(self.len());
+        for elem in self {
+            elem.encode(writer);
+        }
+    }
+}
+
+impl<T: Decode> Decode for VecDeque<T> {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Vec::decode(reader)?.into())
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, writer: &mut Writer) {
+        self.0.encode(writer);
+        self.1.encode(writer);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok((A::decode(reader)?, B::decode(reader)?))
+    }
+}
+
+impl<A: Encode, B: Encode, C: Encode> Encode for (A, B, C) {
+    fn encode(&self, writer: &mut Writer) {
+        self.0.encode(writer);
+        self.1.encode(writer);
+        self.2.encode(writer);
+    }
+}
+
+impl<A: Decode, B: Decode, C: Decode> Decode for (A, B, C) {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok((A::decode(reader)?, B::decode(reader)?, C::decode(reader)?))
+    }
+}
+
+impl Encode for Vector2<f32> {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_f32(self.x);
+        writer.write_f32(self.y);
+    }
+}
+
+impl Decode for Vector2<f32> {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Vector2::new(reader.read_f32()?, reader.read_f32()?))
+    }
+}
+
+impl Encode for Vector3<f32> {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_f32(self.x);
+        writer.write_f32(self.y);
+        writer.write_f32(self.z);
+    }
+}
+
+impl Decode for Vector3<f32> {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Vector3::new(
+            reader.read_f32()?,
+            reader.read_f32()?,
+            reader.read_f32()?,
+        ))
+    }
+}
+
+impl Encode for Vector2<isize> {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_isize(self.x);
+        writer.write_isize(self.y);
+    }
+}
+
+impl Decode for Vector2<isize> {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Vector2::new(reader.read_isize()?, reader.read_isize()?))
+    }
+}
+
+impl Encode for GameLogicSettings {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_bool(self.enable_multithreading);
+        writer.write_usize(self.max_nr_ants);
+        writer.write_u64(self.seed);
+        writer.write_usize(self.nr_terrain_workers);
+    }
+}
+
+impl Decode for GameLogicSettings {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            enable_multithreading: reader.read_bool()?,
+            max_nr_ants: reader.read_usize()?,
+            seed: reader.read_u64()?,
+            nr_terrain_workers: reader.read_usize()?,
+        })
+    }
+}
+
+impl Encode for HeightMapDetails {
+    fn encode(&self, writer: &mut Writer) {
+        self.pos_0.encode(writer);
+        self.pos_1.encode(writer);
+        writer.write_usize(self.point_distance);
+        writer.write_usize(self.size_0);
+        writer.write_usize(self.size_1);
+        writer.write_usize(self.nr_tiles);
+        writer.write_usize(self.depth);
+        writer.write_usize(self.node_index);
+    }
+}
+
+impl Decode for HeightMapDetails {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            pos_0: Vector2::decode(reader)?,
+            pos_1: Vector2::decode(reader)?,
+            point_distance: reader.read_usize()?,
+            size_0: reader.read_usize()?,
+            size_1: reader.read_usize()?,
+            nr_tiles: reader.read_usize()?,
+            depth: reader.read_usize()?,
+            node_index: reader.read_usize()?,
+        })
+    }
+}
+
+impl Encode for HeightMap {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_usize(self.heights.len());
+        for height in &self.heights {
+            writer.write_f32(*height);
+        }
+        self.details.encode(writer);
+    }
+}
+
+impl Decode for HeightMap {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let len = reader.read_len(4)?;
+        let mut heights = Vec::with_capacity(len);
+        for _i in 0..len {
+            heights.push(reader.read_f32()?);
+        }
+
+        Ok(Self {
+            heights,
+            details: HeightMapDetails::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for PheromoneField {
+    fn encode(&self, writer: &mut Writer) {
+        self.origin.encode(writer);
+        writer.write_usize(self.cell_size);
+        writer.write_usize(self.size_x);
+        writer.write_usize(self.size_y);
+        self.intensities.encode(writer);
+    }
+}
+
+impl Decode for PheromoneField {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let field = Self {
+            origin: Vector2::decode(reader)?,
+            cell_size: reader.read_usize()?,
+            size_x: reader.read_usize()?,
+            size_y: reader.read_usize()?,
+            intensities: Vec::decode(reader)?,
+        };
+
+        let nr_cells = field.size_x.checked_mul(field.size_y);
+        if field.cell_size == 0 || nr_cells == Some(0) || nr_cells != Some(field.intensities.len())
+        {
+            return Err(DecodeError::Inconsistent("shape of the pheromone field"));
+        }
+
+        Ok(field)
+    }
+}
+
+impl Encode for ClockState {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(self.tick);
+        writer.write_f64(self.time);
+        writer.write_bool(self.is_paused);
+        writer.write_f64(self.time_scale);
+    }
+}
+
+impl Decode for ClockState {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            tick: reader.read_u64()?,
+            time: reader.read_f64()?,
+            is_paused: reader.read_bool()?,
+            time_scale: reader.read_f64()?,
+        })
+    }
+}
+
+impl Encode for Trader {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            Trader::Ant(id) => {
+                writer.write_u8(0);
+                writer.write_usize(*id);
+            }
+            Trader::Factory(id) => {
+                writer.write_u8(1);
+                writer.write_usize(*id);
+            }
+        }
+    }
+}
+
+impl Decode for Trader {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(Trader::Ant(reader.read_usize()?)),
+            1 => Ok(Trader::Factory(reader.read_usize()?)),
+            tag => Err(DecodeError::InvalidTag {
+                name: "Trader",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for Side {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            Side::Bid => writer.write_u8(0),
+            Side::Ask => writer.write_u8(1),
+        }
+    }
+}
+
+impl Decode for Side {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(Side::Bid),
+            1 => Ok(Side::Ask),
+            tag => Err(DecodeError::InvalidTag { name: "Side", tag }),
+        }
+    }
+}
+
+impl Encode for Order {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(self.id);
+        self.trader.encode(writer);
+        writer.write_usize(self.good);
+        self.side.encode(writer);
+        writer.write_u64(self.price);
+        writer.write_u32(self.quantity);
+    }
+}
+
+impl Decode for Order {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            id: reader.read_u64()?,
+            trader: Trader::decode(reader)?,
+            good: reader.read_usize()?,
+            side: Side::decode(reader)?,
+            price: reader.read_u64()?,
+            quantity: reader.read_u32()?,
+        })
+    }
+}
+
+impl Encode for PricePoint {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(self.tick);
+        self.last_trade.encode(writer);
+        self.mid.encode(writer);
+    }
+}
+
+impl Decode for PricePoint {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            tick: reader.read_u64()?,
+            last_trade: Option::decode(reader)?,
+            mid: Option::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Account {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            Account::Ant(id) => {
+                writer.write_u8(0);
+                writer.write_usize(*id);
+            }
+            Account::Factory(id) => {
+                writer.write_u8(1);
+                writer.write_usize(*id);
+            }
+            Account::Household(id) => {
+                writer.write_u8(2);
+                writer.write_usize(*id);
+            }
+            Account::Treasury => writer.write_u8(3),
+        }
+    }
+}
+
+impl Decode for Account {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(Account::Ant(reader.read_usize()?)),
+            1 => Ok(Account::Factory(reader.read_usize()?)),
+            2 => Ok(Account::Household(reader.read_usize()?)),
+            3 => Ok(Account::Treasury),
+            tag => Err(DecodeError::InvalidTag {
+                name: "Account",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for EntryKind {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            EntryKind::Mint => writer.write_u8(0),
+            EntryKind::Burn => writer.write_u8(1),
+            EntryKind::Trade => writer.write_u8(2),
+            EntryKind::Wage => writer.write_u8(3),
+            EntryKind::Tax => writer.write_u8(4),
+            EntryKind::Inheritance => writer.write_u8(5),
+        }
+    }
+}
+
+impl Decode for EntryKind {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(EntryKind::Mint),
+            1 => Ok(EntryKind::Burn),
+            2 => Ok(EntryKind::Trade),
+            3 => Ok(EntryKind::Wage),
+            4 => Ok(EntryKind::Tax),
+            5 => Ok(EntryKind::Inheritance),
+            tag => Err(DecodeError::InvalidTag {
+                name: "EntryKind",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for Entry {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(self.tick);
+        self.kind.encode(writer);
+        self.from.encode(writer);
+        self.to.encode(writer);
+        writer.write_u64(self.amount);
+    }
+}
+
+impl Decode for Entry {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            tick: reader.read_u64()?,
+            kind: EntryKind::decode(reader)?,
+            from: Option::decode(reader)?,
+            to: Option::decode(reader)?,
+            amount: reader.read_u64()?,
+        })
+    }
+}
+
+impl Encode for LiveStates {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_f64(self.age);
+        writer.write_f32(self.health);
+        writer.write_f32(self.sleep);
+        writer.write_f32(self.food);
+        self.home.encode(writer);
+        writer.write_bool(self.is_resting);
+    }
+}
+
+impl Decode for LiveStates {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            age: reader.read_f64()?,
+            health: reader.read_f32()?,
+            sleep: reader.read_f32()?,
+            food: reader.read_f32()?,
+            home: Option::decode(reader)?,
+            is_resting: reader.read_bool()?,
+        })
+    }
+}
+
+impl Encode for Trade {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(self.tick);
+        writer.write_usize(self.good);
+        writer.write_u64(self.price);
+        writer.write_u32(self.quantity);
+        self.buyer.encode(writer);
+        self.seller.encode(writer);
+        writer.write_u64(self.bid);
+        writer.write_u64(self.ask);
+    }
+}
+
+impl Decode for Trade {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            tick: reader.read_u64()?,
+            good: reader.read_usize()?,
+            price: reader.read_u64()?,
+            quantity: reader.read_u32()?,
+            buyer: Trader::decode(reader)?,
+            seller: Trader::decode(reader)?,
+            bid: reader.read_u64()?,
+            ask: reader.read_u64()?,
+        })
+    }
+}
+
+impl Encode for FactoryState {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_usize(self.id);
+        self.pos.encode(writer);
+        writer.write_usize(self.recipe);
+        writer.write_u32(self.workers);
+        writer.write_u64(self.wage);
+        writer.write_f64(self.progress);
+        writer.write_bool(self.is_producing);
+        writer.write_u32(self.produced_agents);
+
+        writer.write_usize(self.stock.len());
+        for (good, quantity) in &self.stock {
+            writer.write_usize(*good);
+            writer.write_u32(*quantity);
+        }
+    }
+}
+
+impl Decode for FactoryState {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let id = reader.read_usize()?;
+        let pos = Vector2::decode(reader)?;
+        let recipe = reader.read_usize()?;
+        let workers = reader.read_u32()?;
+        let wage = reader.read_u64()?;
+        let progress = reader.read_f64()?;
+        let is_producing = reader.read_bool()?;
+        let produced_agents = reader.read_u32()?;
+
+        let len = reader.read_len(12)?;
+        let mut stock = Vec::with_capacity(len);
+        for _i in 0..len {
+            stock.push((reader.read_usize()?, reader.read_u32()?));
+        }
+
+        Ok(Self {
+            id,
+            pos,
+            recipe,
+            workers,
+            wage,
+            progress,
+            is_producing,
+            produced_agents,
+            stock,
+        })
+    }
+}
+
+impl Encode for Ant {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_usize(self.id);
+        self.pos.encode(writer);
+        writer.write_f32(self.rot_z);
+        self.task.encode(writer);
+        writer.write_f32(self.light_strength);
+        self.light_color.encode(writer);
+    }
+}
+
+impl Decode for Ant {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            id: reader.read_usize()?,
+            pos: Vector2::decode(reader)?,
+            rot_z: reader.read_f32()?,
+            task: Task::decode(reader)?,
+            light_strength: reader.read_f32()?,
+            light_color: Vector3::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Task {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u8(*self as u8);
+    }
+}
+
+impl Decode for Task {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(Task::Idle),
+            1 => Ok(Task::MoveTo),
+            2 => Ok(Task::Gather),
+            3 => Ok(Task::Carry),
+            4 => Ok(Task::Deliver),
+            5 => Ok(Task::Rest),
+            6 => Ok(Task::Forage),
+            tag => Err(DecodeError::InvalidTag { name: "Task", tag }),
+        }
+    }
+}
+
+impl Encode for CauseOfDeath {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            CauseOfDeath::Starvation => writer.write_u8(0),
+            CauseOfDeath::Age => writer.write_u8(1),
+            CauseOfDeath::Exhaustion => writer.write_u8(2),
+        }
+    }
+}
+
+impl Decode for CauseOfDeath {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(CauseOfDeath::Starvation),
+            1 => Ok(CauseOfDeath::Age),
+            2 => Ok(CauseOfDeath::Exhaustion),
+            tag => Err(DecodeError::InvalidTag {
+                name: "CauseOfDeath",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for Attenuation {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u8(*self as u8);
+    }
+}
+
+impl Decode for Attenuation {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(Attenuation::_7),
+            1 => Ok(Attenuation::_13),
+            2 => Ok(Attenuation::_20),
+            3 => Ok(Attenuation::_32),
+            4 => Ok(Attenuation::_50),
+            5 => Ok(Attenuation::_65),
+            6 => Ok(Attenuation::_100),
+            7 => Ok(Attenuation::_160),
+            8 => Ok(Attenuation::_200),
+            9 => Ok(Attenuation::_325),
+            10 => Ok(Attenuation::_600),
+            11 => Ok(Attenuation::_3250),
+            tag => Err(DecodeError::InvalidTag {
+                name: "Attenuation",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for PointLight {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u32(self.id);
+        self.position.encode(writer);
+        self.color.encode(writer);
+        self.attenuation.encode(writer);
+    }
+}
+
+impl Decode for PointLight {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            id: reader.read_u32()?,
+            position: Vector3::decode(reader)?,
+            color: Vector3::decode(reader)?,
+            attenuation: Attenuation::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for AntBatch {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_bool(self.is_keyframe);
+        writer.write_usize(self.chunk);
+        self.moved.encode(writer);
+        self.positions.encode(writer);
+        self.rotations.encode(writer);
+        self.recoloured.encode(writer);
+        self.light_strengths.encode(writer);
+        self.light_colors.encode(writer);
+        self.retasked.encode(writer);
+        self.tasks.encode(writer);
+    }
+}
+
+impl Decode for AntBatch {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            is_keyframe: reader.read_bool()?,
+            chunk: reader.read_usize()?,
+            moved: Vec::decode(reader)?,
+            positions: Vec::decode(reader)?,
+            rotations: Vec::decode(reader)?,
+            recoloured: Vec::decode(reader)?,
+            light_strengths: Vec::decode(reader)?,
+            light_colors: Vec::decode(reader)?,
+            retasked: Vec::decode(reader)?,
+            tasks: Vec::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for OutboxStatistics {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(self.sent);
+        writer.write_u64(self.coalesced);
+        writer.write_u64(self.dropped);
+        writer.write_u64(self.backlog);
+    }
+}
+
+impl Decode for OutboxStatistics {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            sent: reader.read_u64()?,
+            coalesced: reader.read_u64()?,
+            dropped: reader.read_u64()?,
+            backlog: reader.read_u64()?,
+        })
+    }
+}
+
+impl Encode for ChannelStatistics {
+    fn encode(&self, writer: &mut Writer) {
+        self.heavy.encode(writer);
+        self.medium.encode(writer);
+        self.light.encode(writer);
+        self.critical.encode(writer);
+    }
+}
+
+impl Decode for ChannelStatistics {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            heavy: OutboxStatistics::decode(reader)?,
+            medium: OutboxStatistics::decode(reader)?,
+            light: OutboxStatistics::decode(reader)?,
+            critical: OutboxStatistics::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for GameLogicMessageRequest {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            GameLogicMessageRequest::GetTerrain(details) => {
+                writer.write_u8(0);
+                details.encode(writer);
+            }
+            GameLogicMessageRequest::Pause => writer.write_u8(1),
+            GameLogicMessageRequest::Resume => writer.write_u8(2),
+            GameLogicMessageRequest::SetTimeScale(time_scale) => {
+                writer.write_u8(3);
+                writer.write_f64(*time_scale);
+            }
+            GameLogicMessageRequest::Step => writer.write_u8(4),
+            GameLogicMessageRequest::Shutdown => writer.write_u8(5),
+            GameLogicMessageRequest::Reset(settings) => {
+                writer.write_u8(6);
+                settings.encode(writer);
+            }
+            GameLogicMessageRequest::SaveRecording(path) => {
+                writer.write_u8(7);
+                writer.write_str(path);
+            }
+            GameLogicMessageRequest::SaveSnapshot(path) => {
+                writer.write_u8(8);
+                writer.write_str(path);
+            }
+            GameLogicMessageRequest::LoadSnapshot(path) => {
+                writer.write_u8(9);
+                writer.write_str(path);
+            }
+            GameLogicMessageRequest::Cancel(id) => {
+                writer.write_u8(10);
+                writer.write_u64(*id);
+            }
+            GameLogicMessageRequest::SetViewPosition(view_position) => {
+                writer.write_u8(11);
+                view_position.encode(writer);
+            }
+            GameLogicMessageRequest::Sync => writer.write_u8(12),
+        }
+    }
+}
+
+impl Decode for GameLogicMessageRequest {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(GameLogicMessageRequest::GetTerrain(
+                HeightMapDetails::decode(reader)?,
+            )),
+            1 => Ok(GameLogicMessageRequest::Pause),
+            2 => Ok(GameLogicMessageRequest::Resume),
+            3 => Ok(GameLogicMessageRequest::SetTimeScale(reader.read_f64()?)),
+            4 => Ok(GameLogicMessageRequest::Step),
+            5 => Ok(GameLogicMessageRequest::Shutdown),
+            6 => Ok(GameLogicMessageRequest::Reset(GameLogicSettings::decode(
+                reader,
+            )?)),
+            7 => Ok(GameLogicMessageRequest::SaveRecording(
+                reader.read_string()?,
+            )),
+            8 => Ok(GameLogicMessageRequest::SaveSnapshot(reader.read_string()?)),
+            9 => Ok(GameLogicMessageRequest::LoadSnapshot(reader.read_string()?)),
+            10 => Ok(GameLogicMessageRequest::Cancel(reader.read_u64()?)),
+            11 => Ok(GameLogicMessageRequest::SetViewPosition(Vector2::decode(
+                reader,
+            )?)),
+            12 => Ok(GameLogicMessageRequest::Sync),
+            tag => Err(DecodeError::InvalidTag {
+                name: "GameLogicMessageRequest",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for Request {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(self.id);
+        self.message.encode(writer);
+    }
+}
+
+impl Decode for Request {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Self {
+            id: reader.read_u64()?,
+            message: GameLogicMessageRequest::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Recording {
+    fn encode(&self, writer: &mut Writer) {
+        self.settings.encode(writer);
+        self.snapshot.encode(writer);
+        writer.write_u64(self.end_tick);
+
+        writer.write_usize(self.requests.len());
+        for recorded_request in &self.requests {
+            writer.write_u64(recorded_request.tick);
+            recorded_request.request.encode(writer);
+        }
+
+        self.checksums.encode(writer);
+    }
+}
+
+impl Decode for Recording {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let settings = GameLogicSettings::decode(reader)?;
+        let snapshot = Option::decode(reader)?;
+        let end_tick = reader.read_u64()?;
+
+        let len = reader.read_len(17)?;
+        let mut requests = Vec::with_capacity(len);
+        for _i in 0..len {
+            requests.push(RecordedRequest {
+                tick: reader.read_u64()?,
+                request: Request::decode(reader)?,
+            });
+        }
+
+        Ok(Self {
+            settings,
+            snapshot,
+            requests,
+            checksums: Vec::decode(reader)?,
+            end_tick,
+        })
+    }
+}
+
+impl Encode for GameLogicMessageHeavy {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            GameLogicMessageHeavy::Terrain { id, height_map } => {
+                writer.write_u8(0);
+                writer.write_u64(*id);
+                height_map.encode(writer);
+            }
+            GameLogicMessageHeavy::Pheromones(field) => {
+                writer.write_u8(1);
+                field.encode(writer);
+            }
+        }
+    }
+}
+
+impl Decode for GameLogicMessageHeavy {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(GameLogicMessageHeavy::Terrain {
+                id: reader.read_u64()?,
+                height_map: HeightMap::decode(reader)?,
+            }),
+            1 => Ok(GameLogicMessageHeavy::Pheromones(PheromoneField::decode(
+                reader,
+            )?)),
+            tag => Err(DecodeError::InvalidTag {
+                name: "GameLogicMessageHeavy",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for GameLogicMessageMedium {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            // the watch points measure the local process, only the tag is reserved
+            GameLogicMessageMedium::UpdateWatchPoints(_watch_viewer_data) => writer.write_u8(0),
+            GameLogicMessageMedium::UpdateClock(clock_state) => {
+                writer.write_u8(1);
+                clock_state.encode(writer);
+            }
+            GameLogicMessageMedium::MarketFill(trade) => {
+                writer.write_u8(2);
+                trade.encode(writer);
+            }
+            GameLogicMessageMedium::UpdateFactory(factory_state) => {
+                writer.write_u8(3);
+                factory_state.encode(writer);
+            }
+            GameLogicMessageMedium::AntBorn(ant) => {
+                writer.write_u8(4);
+                ant.encode(writer);
+            }
+            GameLogicMessageMedium::AntDied { id, cause } => {
+                writer.write_u8(5);
+                writer.write_usize(*id);
+                cause.encode(writer);
+            }
+            GameLogicMessageMedium::SnapshotLoaded(settings) => {
+                writer.write_u8(6);
+                settings.encode(writer);
+            }
+            GameLogicMessageMedium::UpdateChannelStatistics(channel_statistics) => {
+                writer.write_u8(7);
+                channel_statistics.encode(writer);
+            }
+            GameLogicMessageMedium::Synced {
+                id,
+                settings,
+                clock,
+                ants,
+                factories,
+            } => {
+                writer.write_u8(8);
+                writer.write_u64(*id);
+                settings.encode(writer);
+                clock.encode(writer);
+                ants.encode(writer);
+                factories.encode(writer);
+            }
+        }
+    }
+}
+
+impl Decode for GameLogicMessageMedium {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Err(DecodeError::LocalOnly("UpdateWatchPoints")),
+            1 => Ok(GameLogicMessageMedium::UpdateClock(ClockState::decode(
+                reader,
+            )?)),
+            2 => Ok(GameLogicMessageMedium::MarketFill(Trade::decode(reader)?)),
+            3 => Ok(GameLogicMessageMedium::UpdateFactory(FactoryState::decode(
+                reader,
+            )?)),
+            4 => Ok(GameLogicMessageMedium::AntBorn(Ant::decode(reader)?)),
+            5 => Ok(GameLogicMessageMedium::AntDied {
+                id: reader.read_usize()?,
+                cause: CauseOfDeath::decode(reader)?,
+            }),
+            6 => Ok(GameLogicMessageMedium::SnapshotLoaded(
+                GameLogicSettings::decode(reader)?,
+            )),
+            7 => Ok(GameLogicMessageMedium::UpdateChannelStatistics(
+                ChannelStatistics::decode(reader)?,
+            )),
+            8 => Ok(GameLogicMessageMedium::Synced {
+                id: reader.read_u64()?,
+                settings: GameLogicSettings::decode(reader)?,
+                clock: ClockState::decode(reader)?,
+                ants: Vec::decode(reader)?,
+                factories: Vec::decode(reader)?,
+            }),
+            tag => Err(DecodeError::InvalidTag {
+                name: "GameLogicMessageMedium",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for GameLogicMessageLight {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            GameLogicMessageLight::UpdatePointLight(point_light) => {
+                writer.write_u8(0);
+                point_light.encode(writer);
+            }
+            GameLogicMessageLight::UpdateAnt(ant) => {
+                writer.write_u8(1);
+                ant.encode(writer);
+            }
+            GameLogicMessageLight::UpdateAnts(batch) => {
+                writer.write_u8(2);
+                batch.encode(writer);
+            }
+        }
+    }
+}
+
+impl Decode for GameLogicMessageLight {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(GameLogicMessageLight::UpdatePointLight(PointLight::decode(
+                reader,
+            )?)),
+            1 => Ok(GameLogicMessageLight::UpdateAnt(Ant::decode(reader)?)),
+            2 => Ok(GameLogicMessageLight::UpdateAnts(AntBatch::decode(reader)?)),
+            tag => Err(DecodeError::InvalidTag {
+                name: "GameLogicMessageLight",
+                tag,
+            }),
+        }
+    }
+}
+
+impl Encode for GameLogicMessageCritical {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            GameLogicMessageCritical::ServerPanicked(msg) => {
+                writer.write_u8(0);
+                writer.write_str(msg);
+            }
+            GameLogicMessageCritical::InvalidRequest { id, reason } => {
+                writer.write_u8(1);
+                writer.write_u64(*id);
+                writer.write_str(reason);
+            }
+            GameLogicMessageCritical::InvariantViolated(msg) => {
+                writer.write_u8(2);
+                writer.write_str(msg);
+            }
+            GameLogicMessageCritical::Desync {
+                tick,
+                expected,
+                actual,
+            } => {
+                writer.write_u8(3);
+                writer.write_u64(*tick);
+                writer.write_u64(*expected);
+                writer.write_u64(*actual);
+            }
+            GameLogicMessageCritical::Error(msg) => {
+                writer.write_u8(4);
+                writer.write_str(msg);
+            }
+            GameLogicMessageCritical::RequestFailed { id, reason } => {
+                writer.write_u8(5);
+                writer.write_u64(*id);
+                writer.write_str(reason);
+            }
+        }
+    }
+}
+
+impl Decode for GameLogicMessageCritical {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(GameLogicMessageCritical::ServerPanicked(
+                reader.read_string()?,
+            )),
+            1 => Ok(GameLogicMessageCritical::InvalidRequest {
+                id: reader.read_u64()?,
+                reason: reader.read_string()?,
+            }),
+            2 => Ok(GameLogicMessageCritical::InvariantViolated(
+                reader.read_string()?,
+            )),
+            3 => Ok(GameLogicMessageCritical::Desync {
+                tick: reader.read_u64()?,
+                expected: reader.read_u64()?,
+                actual: reader.read_u64()?,
+            }),
+            4 => Ok(GameLogicMessageCritical::Error(reader.read_string()?)),
+            5 => Ok(GameLogicMessageCritical::RequestFailed {
+                id: reader.read_u64()?,
+                reason: reader.read_string()?,
+            }),
+            tag => Err(DecodeError::InvalidTag {
+                name: "GameLogicMessageCritical",
+                tag,
+            }),
+        }
+    }
+}
+
+#[test]
+fn test_request_round_trip() {
+    let bytes = encode(&GameLogicMessageRequest::SetTimeScale(2.0));
+    let res: GameLogicMessageRequest = decode(&bytes).unwrap();
+    assert!(matches!(res, GameLogicMessageRequest::SetTimeScale(2.0)));
+
+    assert_eq!(
+        decode::<GameLogicMessageRequest>(&bytes[..4]).err(),
+        Some(DecodeError::UnexpectedEnd)
+    );
+    assert!(check_header(&header()).is_ok());
+}
+
+#[cfg(test)]
+fn assert_round_trip<T: Encode + Decode>(value: &T) {
+    // compares the encodings, as the messages do not implement PartialEq
+    let bytes = encode(value);
+    let res: T = decode(&bytes).unwrap();
+    assert_eq!(encode(&res), bytes);
+
+    let bytes = encode_with_header(value);
+    let res: T = decode_with_header(&bytes).unwrap();
+    assert_eq!(encode_with_header(&res), bytes);
+}
+
+#[test]
+fn test_all_messages_round_trip() {
+    let details = HeightMapDetails {
+        pos_0: Vector2::new(-4, 8),
+        pos_1: Vector2::new(-3, 9),
+        point_distance: 1,
+        size_0: 3,
+        size_1: 1,
+        nr_tiles: 0,
+        depth: 2,
+        node_index: 5,
+    };
+    let ant = Ant {
+        id: 3,
+        pos: Vector2::new(1.5, -2.0),
+        rot_z: 0.25,
+        task: Task::Carry,
+        light_strength: 1.0,
+        light_color: Vector3::new(0.1, 0.2, 0.3),
+    };
+    let settings = GameLogicSettings {
+        enable_multithreading: true,
+        max_nr_ants: 100,
+        seed: u64::MAX,
+        nr_terrain_workers: 2,
+    };
+
+    let requests = [
+        GameLogicMessageRequest::GetTerrain(details.clone()),
+        GameLogicMessageRequest::Pause,
+        GameLogicMessageRequest::Resume,
+        GameLogicMessageRequest::SetTimeScale(0.25),
+        GameLogicMessageRequest::Step,
+        GameLogicMessageRequest::Shutdown,
+        GameLogicMessageRequest::Reset(settings.clone()),
+        GameLogicMessageRequest::SaveRecording("recording.mes".to_string()),
+        GameLogicMessageRequest::SaveSnapshot("snapshot.mes".to_string()),
+        GameLogicMessageRequest::LoadSnapshot("snapshot.mes".to_string()),
+        GameLogicMessageRequest::Cancel(12),
+        GameLogicMessageRequest::SetViewPosition(Vector2::new(-12.5, 3.0)),
+        GameLogicMessageRequest::Sync,
+    ];
+    for (id, message) in requests.into_iter().enumerate() {
+        assert_round_trip(&Request {
+            id: id as u64,
+            message,
+        });
+    }
+
+    assert_round_trip(&GameLogicMessageHeavy::Terrain {
+        id: 12,
+        height_map: HeightMap {
+            heights: (0..9).map(|i| i as f32 * 0.5).collect(),
+            details,
+        },
+    });
+    assert_round_trip(&GameLogicMessageHeavy::Pheromones(PheromoneField {
+        origin: Vector2::new(-18, 4),
+        cell_size: 2,
+        size_x: 3,
+        size_y: 2,
+        intensities: vec![0.0, 0.5, 1.0, 0.0, 2.5, 0.125],
+    }));
+
+    let medium = [
+        GameLogicMessageMedium::UpdateClock(ClockState {
+            tick: 42,
+            time: 0.672,
+            is_paused: true,
+            time_scale: 2.0,
+        }),
+        GameLogicMessageMedium::MarketFill(Trade {
+            tick: 42,
+            good: 1,
+            price: 17,
+            quantity: 3,
+            buyer: Trader::Ant(3),
+            seller: Trader::Factory(0),
+            bid: 10,
+            ask: 11,
+        }),
+        GameLogicMessageMedium::UpdateFactory(FactoryState {
+            id: 0,
+            pos: Vector2::new(10.0, 20.0),
+            recipe: 2,
+            workers: 4,
+            wage: 12,
+            progress: 0.5,
+            is_producing: true,
+            produced_agents: 1,
+            stock: vec![(0, 5), (2, 1)],
+        }),
+        GameLogicMessageMedium::AntBorn(ant.clone()),
+        GameLogicMessageMedium::AntDied {
+            id: 3,
+            cause: CauseOfDeath::Age,
+        },
+        GameLogicMessageMedium::SnapshotLoaded(settings.clone()),
+        GameLogicMessageMedium::Synced {
+            id: 5,
+            settings,
+            clock: ClockState {
+                tick: 7,
+                time: 0.112,
+                is_paused: false,
+                time_scale: 1.0,
+            },
+            ants: vec![ant.clone()],
+            factories: Vec::new(),
+        },
+        GameLogicMessageMedium::UpdateChannelStatistics(ChannelStatistics {
+            light: OutboxStatistics {
+                sent: 1000,
+                coalesced: 20,
+                dropped: 0,
+                backlog: 3,
+            },
+            ..ChannelStatistics::default()
+        }),
+    ];
+    for msg in &medium {
+        assert_round_trip(msg);
+    }
+
+    let light = [
+        GameLogicMessageLight::UpdatePointLight(PointLight {
+            id: 7,
+            position: Vector3::new(1.0, 2.0, 3.0),
+            color: Vector3::new(1.0, 0.5, 0.0),
+            attenuation: Attenuation::_3250,
+        }),
+        GameLogicMessageLight::UpdateAnt(ant),
+        GameLogicMessageLight::UpdateAnts(AntBatch {
+            is_keyframe: false,
+            chunk: 0,
+            moved: vec![3, 1025],
+            positions: vec![Vector2::new(2.0, -1.5), Vector2::new(0.0, 1.0)],
+            rotations: vec![0.5, 0.0],
+            recoloured: vec![3],
+            light_strengths: vec![0.5],
+            light_colors: vec![Vector3::new(0.3, 0.2, 0.1)],
+            retasked: vec![1],
+            tasks: vec![Task::Rest],
+        }),
+    ];
+    for msg in &light {
+        assert_round_trip(msg);
+    }
+
+    let critical = [
+        GameLogicMessageCritical::ServerPanicked("index out of bounds".to_string()),
+        GameLogicMessageCritical::InvalidRequest {
+            id: 12,
+            reason: "depth 99 of the terrain".to_string(),
+        },
+        GameLogicMessageCritical::InvariantViolated("money is not conserved".to_string()),
+        GameLogicMessageCritical::Desync {
+            tick: 256,
+            expected: 1,
+            actual: 2,
+        },
+        GameLogicMessageCritical::Error("file not found".to_string()),
+        GameLogicMessageCritical::RequestFailed {
+            id: 13,
+            reason: "file not found".to_string(),
+        },
+    ];
+    for msg in &critical {
+        assert_round_trip(msg);
+    }
+}
+
+#[test]
+fn test_encoding_is_stable() {
+    // changing these bytes breaks recordings and peers, increase VERSION instead
+    assert_eq!(header(), [b'M', b'E', b'S', b'S', 20, 0]);
+    assert_eq!(
+        encode(&GameLogicMessageMedium::AntDied {
+            id: 258,
+            cause: CauseOfDeath::Starvation,
+        }),
+        [5, 2, 1, 0, 0, 0, 0, 0, 0, 0]
+    );
+
+    let mut field = PheromoneField {
+        origin: Vector2::new(0, 0),
+        cell_size: 2,
+        size_x: 2,
+        size_y: 2,
+        intensities: vec![0.0; 3],
+    };
+    assert_eq!(
+        decode::<PheromoneField>(&encode(&field)).err(),
+        Some(DecodeError::Inconsistent("shape of the pheromone field"))
+    );
+    field.intensities.push(0.0);
+    field.cell_size = 0;
+    assert!(decode::<PheromoneField>(&encode(&field)).is_err());
+
+    let mut bytes = encode_with_header(&GameLogicMessageRequest::Step);
+    bytes[4] = 1;
+    assert_eq!(
+        decode_with_header::<GameLogicMessageRequest>(&bytes).err(),
+        Some(DecodeError::UnsupportedVersion(1))
+    );
+    assert_eq!(
+        decode::<GameLogicMessageLight>(&[9]).err(),
+        Some(DecodeError::InvalidTag {
+            name: "GameLogicMessageLight",
+            tag: 9
+        })
+    );
+}